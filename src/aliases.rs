@@ -0,0 +1,171 @@
+//! Custom aliases for repositories, which are searchable like their names
+//!
+//! The aliases file has one `key -> alias` line per repository, where the key is
+//! either the repository URL or its name. Empty lines and lines starting with `#`
+//! are ignored.
+
+use crate::cache::{self, RepoData};
+use crate::config;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const ALIASES_FILE: &str = "aliases";
+
+/// Where the aliases were kept before they moved next to the config file
+const LEGACY_ALIASES_FILE: &str = ".repo-aliases";
+
+/// Aliases by repository URL or name
+#[derive(Debug, Default, PartialEq)]
+pub struct Aliases {
+    entries: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    /// Parses the aliases file format, skipping lines without a key and alias
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once("->"))
+            .map(|(key, alias)| (key.trim().to_string(), alias.trim().to_string()))
+            .filter(|(key, alias)| !key.is_empty() && !alias.is_empty())
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Serializes the aliases in the file format
+    pub fn to_file_string(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(key, alias)| format!("{} -> {}\n", key, alias))
+            .collect()
+    }
+
+    /// Returns the alias of a repository, preferring one set for its URL over its name
+    pub fn get(&self, repo: &RepoData) -> Option<&str> {
+        self.entries
+            .get(&repo.url)
+            .or_else(|| self.entries.get(&repo.name))
+            .map(String::as_str)
+    }
+
+    /// Sets the alias of a repository by its URL, removing it if the alias is empty
+    pub fn set(&mut self, repo: &RepoData, alias: &str) {
+        // A name key would otherwise still apply after removing the URL key
+        self.entries.remove(&repo.name);
+
+        let alias = alias.trim();
+        if alias.is_empty() {
+            self.entries.remove(&repo.url);
+        } else {
+            self.entries.insert(repo.url.clone(), alias.to_string());
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path).map(|text| Self::parse(&text)).unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_file_string())
+    }
+}
+
+/// Returns the text a repository is searched by, its formatted display text and its alias
+pub fn search_text(formatted: &str, alias: Option<&str>) -> String {
+    match alias {
+        Some(alias) => format!("{} {}", formatted, alias),
+        None => formatted.to_string(),
+    }
+}
+
+/// Returns where the aliases are kept, next to the config file.
+///
+/// Falls back to the working directory without a home directory.
+pub fn aliases_path() -> PathBuf {
+    match config::config_path() {
+        Some(path) => path.with_file_name(ALIASES_FILE),
+        None => PathBuf::from(LEGACY_ALIASES_FILE),
+    }
+}
+
+/// Loads the aliases file, which may not exist yet.
+///
+/// An aliases file left in the working directory by an earlier version is moved next to the config first.
+pub fn load_aliases() -> Aliases {
+    let path = aliases_path();
+    if let Err(e) = cache::migrate_legacy_file(Path::new(LEGACY_ALIASES_FILE), &path) {
+        eprintln!("Error moving {} to {}: {}", LEGACY_ALIASES_FILE, path.display(), e);
+    }
+    Aliases::load_from(&path)
+}
+
+pub fn save_aliases(aliases: &Aliases) -> io::Result<()> {
+    aliases.save_to(&aliases_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter;
+
+    #[test]
+    fn test_parse_aliases() {
+        let aliases = Aliases::parse(
+            "# My aliases\n\
+             git@github.com:user/clj-basic-image-cache-server.git -> imgcache\n\
+             \n\
+             rust-web-server->  rws \n\
+             missing arrow\n\
+             -> no key\n",
+        );
+
+        assert_eq!(aliases.get(&RepoData::named("clj-basic-image-cache-server")), Some("imgcache"));
+        assert_eq!(aliases.get(&RepoData::named("rust-web-server")), Some("rws"));
+        assert_eq!(aliases.get(&RepoData::named("go-microservices")), None);
+        assert_eq!(aliases.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_alias_round_trip_through_file() {
+        // The config directory is created on the first save
+        let dir = std::env::temp_dir().join("repo-searcher-test-aliases");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config").join("aliases");
+        let mut aliases = Aliases::parse("notes -> old-notes\n");
+        aliases.set(&RepoData::named("rust-web-server"), "rws");
+        aliases.set(&RepoData::named("notes"), "journal");
+        aliases.save_to(&path).unwrap();
+
+        let loaded = Aliases::load_from(&path);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(loaded, aliases);
+        assert_eq!(loaded.get(&RepoData::named("rust-web-server")), Some("rws"));
+        assert_eq!(loaded.get(&RepoData::named("notes")), Some("journal"));
+
+        // An empty alias removes it
+        let mut loaded = loaded;
+        loaded.set(&RepoData::named("notes"), " ");
+        assert_eq!(loaded.get(&RepoData::named("notes")), None);
+    }
+
+    #[test]
+    fn test_query_matching_only_alias() {
+        let mut aliases = Aliases::default();
+        aliases.set(&RepoData::named("clj-basic-image-cache-server"), "imgcache");
+        let repos = vec![RepoData::named("clj-basic-image-cache-server"), RepoData::named("rust-web-server")];
+
+        let matching = filter::filter_human(&repos, "imgcache", |r| search_text(&r.name, aliases.get(r)));
+
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].name, "clj-basic-image-cache-server");
+    }
+}
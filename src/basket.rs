@@ -0,0 +1,144 @@
+//! A basket of repositories collected across sessions for later review
+//!
+//! Repositories are added and removed with Ctrl+B in the fuzzy finder, and `--basket`
+//! opens the finder with only the basket.
+
+use crate::cache::{self, RepoData};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const BASKET_FILE: &str = "basket.json";
+
+/// Where the basket was kept before it moved into the user's data directory
+const LEGACY_BASKET_FILE: &str = ".repo-basket.json";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Basket {
+    pub repositories: Vec<RepoData>,
+}
+
+impl Basket {
+    /// Check if a repository is in the basket, matched by URL
+    pub fn contains(&self, repo: &RepoData) -> bool {
+        self.repositories.iter().any(|r| r.url == repo.url)
+    }
+
+    /// Adds a repository unless it is already in the basket
+    pub fn add(&mut self, repo: &RepoData) {
+        if !self.contains(repo) {
+            self.repositories.push(repo.clone());
+        }
+    }
+
+    pub fn remove(&mut self, repo: &RepoData) {
+        self.repositories.retain(|r| r.url != repo.url);
+    }
+
+    /// Adds a repository or removes it if it is already in the basket.
+    ///
+    /// Returns whether the repository is in the basket afterwards.
+    pub fn toggle(&mut self, repo: &RepoData) -> bool {
+        if self.contains(repo) {
+            self.remove(repo);
+            false
+        } else {
+            self.add(repo);
+            true
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.repositories.clear();
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+}
+
+/// Returns where the basket is kept, like `~/.local/share/github-repo-searcher/basket.json` on Linux.
+///
+/// Falls back to the working directory if there is no data directory.
+pub fn basket_path() -> PathBuf {
+    match dirs::data_dir() {
+        Some(dir) => dir.join(cache::APP_DIR).join(BASKET_FILE),
+        None => PathBuf::from(LEGACY_BASKET_FILE),
+    }
+}
+
+/// Loads the basket, which is empty if it was never saved.
+///
+/// A basket left in the working directory by an earlier version is moved to the data directory first.
+pub fn load_basket() -> Basket {
+    let path = basket_path();
+    if let Err(e) = cache::migrate_legacy_file(Path::new(LEGACY_BASKET_FILE), &path) {
+        eprintln!("Error moving {} to {}: {}", LEGACY_BASKET_FILE, path.display(), e);
+    }
+    Basket::load_from(&path)
+}
+
+pub fn save_basket(basket: &Basket) -> io::Result<()> {
+    basket.save_to(&basket_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(basket: &Basket) -> Vec<&str> {
+        basket.repositories.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_basket_add_remove_clear() {
+        let mut basket = Basket::default();
+        basket.add(&RepoData::named("a"));
+        basket.add(&RepoData::named("b"));
+        basket.add(&RepoData::named("a"));
+        assert_eq!(names(&basket), vec!["a", "b"]);
+
+        basket.remove(&RepoData::named("a"));
+        assert_eq!(names(&basket), vec!["b"]);
+        assert!(!basket.contains(&RepoData::named("a")));
+
+        // Toggling removes a repository which is already in the basket
+        assert!(basket.toggle(&RepoData::named("c")));
+        assert!(!basket.toggle(&RepoData::named("b")));
+        assert_eq!(names(&basket), vec!["c"]);
+
+        basket.clear();
+        assert!(basket.repositories.is_empty());
+    }
+
+    #[test]
+    fn test_basket_round_trip_through_file() {
+        // The data directory is created on the first save
+        let dir = std::env::temp_dir().join("repo-searcher-test-basket");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("data").join("basket.json");
+        let mut basket = Basket::default();
+        basket.add(&RepoData::named("research-a"));
+        basket.add(&RepoData::named("research-b"));
+        basket.save_to(&path).unwrap();
+
+        let loaded = Basket::load_from(&path);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(names(&loaded), vec!["research-a", "research-b"]);
+
+        // A missing basket is empty
+        assert!(Basket::load_from(&path).repositories.is_empty());
+    }
+}
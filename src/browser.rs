@@ -1,35 +1,25 @@
 use std::process;
 use std::time::Duration;
-use tokio;
+
+/// Returns the program and leading arguments which open a URL on an operating system like
+/// `std::env::consts::OS`. `xdg-open` works in both X11 and Wayland sessions.
+pub fn opener_command(os: &str) -> Option<(&'static str, &'static [&'static str])> {
+    match os {
+        "macos" => Some(("open", &[])),
+        "windows" => Some(("cmd", &["/c", "start"])),
+        "linux" => Some(("xdg-open", &[])),
+        _ => None,
+    }
+}
 
 /// Opens a URL in the default browser
 pub async fn open_in_browser(url: &str) -> Result<(), Box<dyn std::error::Error>> {
     println!("\nOpening URL in browser: {}", url);
     
     // Open URL in browser based on the operating system
-    #[cfg(target_os = "macos")]
-    {
-        process::Command::new("open")
-            .arg(url)
-            .spawn()
-            .map_err(|e| format!("Failed to open URL in browser: {}", e))?
-            .wait()
-            .map_err(|e| format!("Failed to wait on browser process: {}", e))?;
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        process::Command::new("cmd")
-            .args(["/c", "start", url])
-            .spawn()
-            .map_err(|e| format!("Failed to open URL in browser: {}", e))?
-            .wait()
-            .map_err(|e| format!("Failed to wait on browser process: {}", e))?;
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        process::Command::new("xdg-open")
+    if let Some((program, args)) = opener_command(std::env::consts::OS) {
+        process::Command::new(program)
+            .args(args)
             .arg(url)
             .spawn()
             .map_err(|e| format!("Failed to open URL in browser: {}", e))?
@@ -42,3 +32,15 @@ pub async fn open_in_browser(url: &str) -> Result<(), Box<dyn std::error::Error>
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opener_command() {
+        assert_eq!(opener_command("windows"), Some(("cmd", &["/c", "start"][..])));
+        assert_eq!(opener_command("linux"), Some(("xdg-open", &[][..])));
+        assert_eq!(opener_command("plan9"), None);
+    }
+}
@@ -1,23 +1,45 @@
-use crate::github::Repository as GitHubRepo;
-use crate::gitlab::Repository as GitLabRepo;
+use crate::github::Repository as FetchedRepo;
 use crate::formatter::RepoSource;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
-const CACHE_FILE: &str = ".repo-cache.json";
-const CACHE_EXPIRY: Duration = Duration::from_secs(30 * 60); // 30 minutes
+/// Directory of this app inside the user's cache, data and config directories
+pub const APP_DIR: &str = "github-repo-searcher";
+const CACHE_FILE: &str = "cache.json";
+
+/// Where the cache was kept before it moved into the user's cache directory
+const LEGACY_CACHE_FILE: &str = ".repo-cache.json";
+
+/// Version of the cache format, raised whenever cached fields change meaning
+pub const CACHE_VERSION: u32 = 3;
+
+/// How long a cache is used before fetching again, unless configured otherwise
+pub const DEFAULT_CACHE_EXPIRY_MINUTES: u64 = 30;
 
 #[derive(Serialize, Deserialize)]
 pub struct SourceCache {
     pub timestamp: u64,
     pub username: String,
+    /// Fingerprint of the token the username belongs to, see `token_fingerprint`
+    #[serde(default)]
+    pub token_fingerprint: Option<String>,
+    /// When that token expires, if it does
+    #[serde(default)]
+    pub token_expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct CacheData {
+    /// Format version the cache was written with, 0 for caches from before versioning
+    #[serde(default)]
+    pub version: u32,
     pub github: Option<SourceData>,
     pub gitlab: Option<SourceData>,
 }
@@ -28,6 +50,15 @@ pub struct SourceData {
     pub repositories: Vec<RepoData>,
 }
 
+/// Whether a repository is a regular one or a gist, which GitLab calls a snippet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RepoKind {
+    #[default]
+    Repository,
+    /// A GitHub gist or GitLab snippet, fetched with `--include-gists`
+    Gist,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct RepoData {
     pub name: String,
@@ -37,6 +68,70 @@ pub struct RepoData {
     pub is_fork: bool,
     pub is_private: bool,
     pub source: RepoSource,
+    /// Gists are on the platform of their source, like snippets on GitLab
+    #[serde(default)]
+    pub kind: RepoKind,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Unix timestamp of the last update, if the source reports one
+    #[serde(default)]
+    pub updated_at: Option<u64>,
+    /// Unix timestamp of the creation, if the source reports one
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub stars: Option<u64>,
+    #[serde(default)]
+    pub open_issues: Option<u64>,
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// Starred by the user rather than owned, fetched with `--include-starred`
+    #[serde(default)]
+    pub is_starred: bool,
+    /// Unix timestamp of the last push, or the last activity on GitLab
+    #[serde(default)]
+    pub pushed_at: Option<u64>,
+    /// Also on the other platform, set when `--dedup` collapses mirrors and never cached
+    #[serde(skip)]
+    pub mirrored: bool,
+}
+
+#[cfg(test)]
+impl RepoData {
+    /// A public GitHub repository of "user" with nothing but a name, for tests to fill in
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: format!("git@github.com:user/{}.git", name),
+            description: String::new(),
+            owner: "user".to_string(),
+            is_fork: false,
+            is_private: false,
+            source: RepoSource::GitHub,
+            kind: RepoKind::Repository,
+            language: None,
+            topics: Vec::new(),
+            updated_at: None,
+            created_at: None,
+            is_archived: false,
+            stars: None,
+            open_issues: None,
+            default_branch: None,
+            is_starred: false,
+            pushed_at: None,
+            mirrored: false,
+        }
+    }
+}
+
+impl RepoData {
+    pub fn is_gist(&self) -> bool {
+        self.kind == RepoKind::Gist
+    }
 }
 
 impl SourceCache {
@@ -49,37 +144,46 @@ impl SourceCache {
         Self {
             timestamp: now,
             username,
+            token_fingerprint: None,
+            token_expires_at: None,
         }
     }
 
-    pub fn is_expired(&self) -> bool {
+    /// Remembers which token the username belongs to, and when it expires
+    pub fn set_token(&mut self, token: &str, expires_at: Option<DateTime<Utc>>) {
+        self.token_fingerprint = Some(token_fingerprint(token));
+        self.token_expires_at = expires_at;
+    }
+
+    pub fn is_expired(&self, expiry: Duration) -> bool {
         let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
-        now - self.timestamp > CACHE_EXPIRY.as_secs()
+        now - self.timestamp > expiry.as_secs()
     }
 }
 
 impl CacheData {
     pub fn new() -> Self {
         Self {
+            version: CACHE_VERSION,
             github: None,
             gitlab: None,
         }
     }
 
-    pub fn is_expired(&self) -> bool {
+    pub fn is_expired(&self, expiry: Duration) -> bool {
         // If any source cache is expired, consider the entire cache expired
         if let Some(github) = &self.github {
-            if github.cache_info.is_expired() {
+            if github.cache_info.is_expired(expiry) {
                 return true;
             }
         }
 
         if let Some(gitlab) = &self.gitlab {
-            if gitlab.cache_info.is_expired() {
+            if gitlab.cache_info.is_expired(expiry) {
                 return true;
             }
         }
@@ -88,18 +192,42 @@ impl CacheData {
         self.github.is_none() && self.gitlab.is_none()
     }
 
-    pub fn update_github(&mut self, username: String, repositories: Vec<RepoData>) {
-        self.github = Some(SourceData {
+    /// Replaces the GitHub repositories, returning the cache info to remember the token with
+    pub fn update_github(&mut self, username: String, repositories: Vec<RepoData>) -> &mut SourceCache {
+        let github = self.github.insert(SourceData {
             cache_info: SourceCache::new(username),
             repositories,
         });
+        &mut github.cache_info
     }
 
-    pub fn update_gitlab(&mut self, username: String, repositories: Vec<RepoData>) {
-        self.gitlab = Some(SourceData {
+    /// Replaces the GitLab repositories, returning the cache info to remember the token with
+    pub fn update_gitlab(&mut self, username: String, repositories: Vec<RepoData>) -> &mut SourceCache {
+        let gitlab = self.gitlab.insert(SourceData {
             cache_info: SourceCache::new(username),
             repositories,
         });
+        &mut gitlab.cache_info
+    }
+
+    /// Takes the cached data of the given sources from `previous`, for sources which could
+    /// not be fetched this time. Their data is kept with its old timestamp.
+    pub fn keep_sources(&mut self, previous: CacheData, sources: &[RepoSource]) {
+        if self.github.is_none() && sources.contains(&RepoSource::GitHub) {
+            self.github = previous.github;
+        }
+        if self.gitlab.is_none() && sources.contains(&RepoSource::GitLab) {
+            self.gitlab = previous.gitlab;
+        }
+    }
+
+    /// Returns the timestamp of the oldest source cache, if any source is cached
+    pub fn oldest_timestamp(&self) -> Option<u64> {
+        [&self.github, &self.gitlab]
+            .into_iter()
+            .flatten()
+            .map(|source| source.cache_info.timestamp)
+            .min()
     }
 
     pub fn get_all_repositories(&self) -> Vec<RepoData> {
@@ -117,56 +245,681 @@ impl CacheData {
     }
 }
 
-// Convert GitHub repository format to our unified RepoData format
-pub fn github_repo_to_repo_data(repo: &GitHubRepo) -> RepoData {
-    let (name, url, description, owner, is_fork, is_private) = repo.clone();
+/// Returns a hash of a token, telling whether a cached username belongs to it without saving the token.
+///
+/// The hash may differ between builds, which only means the username is fetched once more.
+pub fn token_fingerprint(token: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    token.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Check if a repository was updated after the given cache timestamp.
+///
+/// Repositories without an update timestamp are never considered changed.
+pub fn changed_since(repo: &RepoData, timestamp: u64) -> bool {
+    repo.updated_at.is_some_and(|updated_at| updated_at > timestamp)
+}
+
+/// Merges incrementally fetched repositories into a cached list.
+///
+/// Repositories are matched by URL: updated ones replace their cached entry in place,
+/// and ones not in the cache are added at the end.
+pub fn merge_repositories(cached: &[RepoData], updates: Vec<RepoData>) -> Vec<RepoData> {
+    let mut merged = cached.to_vec();
+    // Positions by URL, so large lists are not scanned once per update
+    let mut positions: HashMap<String, usize> = HashMap::with_capacity(merged.len());
+    for (index, repo) in merged.iter().enumerate() {
+        positions.entry(repo.url.clone()).or_insert(index);
+    }
+
+    for update in updates {
+        match positions.get(&update.url) {
+            Some(&index) => merged[index] = update,
+            None => {
+                positions.insert(update.url.clone(), merged.len());
+                merged.push(update);
+            }
+        }
+    }
+
+    merged
+}
+
+/// Adds starred repositories marked as such, leaving out the ones already listed by URL
+pub fn add_starred(repos: &mut Vec<RepoData>, starred: Vec<RepoData>) {
+    for repo in starred {
+        if !repos.iter().any(|existing| existing.url == repo.url) {
+            repos.push(RepoData { is_starred: true, ..repo });
+        }
+    }
+}
+
+/// What changed between two snapshots of the repositories
+#[derive(Debug, Default, PartialEq)]
+pub struct RepoDiff {
+    /// Names of repositories which are new
+    pub added: Vec<String>,
+    /// Names of repositories which are gone
+    pub removed: Vec<String>,
+    /// Previous and new names of renamed repositories
+    pub renamed: Vec<(String, String)>,
+    /// Names of repositories with changed flags, with a description of each change
+    pub changed: Vec<(String, Vec<String>)>,
+}
+
+impl RepoDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Short summary for the status line, like "+2 new, -1 removed"
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "no changes".to_string();
+        }
+
+        [
+            (self.added.len(), "+", "new"),
+            (self.removed.len(), "-", "removed"),
+            (self.renamed.len(), "", "renamed"),
+            (self.changed.len(), "", "changed"),
+        ]
+        .iter()
+        .filter(|(count, _, _)| *count > 0)
+        .map(|(count, sign, label)| format!("{}{} {}", sign, count, label))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    /// One line per difference, for printing with --show-diff
+    pub fn detail_lines(&self) -> Vec<String> {
+        let added = self.added.iter().map(|name| format!("+ {}", name));
+        let removed = self.removed.iter().map(|name| format!("- {}", name));
+        let renamed = self.renamed.iter().map(|(old, new)| format!("~ {} -> {}", old, new));
+        let changed = self
+            .changed
+            .iter()
+            .map(|(name, changes)| format!("* {}: {}", name, changes.join(", ")));
+
+        added.chain(removed).chain(renamed).chain(changed).collect()
+    }
+}
+
+/// Describes the flag changes of a repository between two snapshots
+fn flag_changes(previous: &RepoData, fresh: &RepoData) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if previous.is_private != fresh.is_private {
+        changes.push(if fresh.is_private { "now private" } else { "now public" }.to_string());
+    }
+    if previous.is_archived != fresh.is_archived {
+        changes.push(if fresh.is_archived { "archived" } else { "unarchived" }.to_string());
+    }
+    if previous.description != fresh.description {
+        changes.push("description changed".to_string());
+    }
+
+    changes
+}
+
+/// Compares a previous snapshot of the repositories against a fresh one.
+///
+/// Repositories are matched by URL. A renamed repository gets a new URL, so a removed
+/// and an added repository of the same owner and source with the same creation time
+/// are considered a rename instead.
+pub fn diff_repositories(previous: &[RepoData], fresh: &[RepoData]) -> RepoDiff {
+    let mut diff = RepoDiff::default();
+    let mut removed: Vec<&RepoData> = Vec::new();
+
+    // Indexed by URL, so large lists are not scanned once per repository
+    let fresh_by_url: HashMap<&str, &RepoData> = fresh.iter().map(|repo| (repo.url.as_str(), repo)).collect();
+    let previous_urls: HashSet<&str> = previous.iter().map(|repo| repo.url.as_str()).collect();
+
+    for old in previous {
+        match fresh_by_url.get(old.url.as_str()) {
+            Some(new) => {
+                let changes = flag_changes(old, new);
+                if !changes.is_empty() {
+                    diff.changed.push((new.name.clone(), changes));
+                }
+            }
+            None => removed.push(old),
+        }
+    }
+
+    let mut added: Vec<&RepoData> = fresh
+        .iter()
+        .filter(|repo| !previous_urls.contains(repo.url.as_str()))
+        .collect();
+
+    for old in removed {
+        let rename = added.iter().position(|new| {
+            old.created_at.is_some()
+                && new.created_at == old.created_at
+                && new.owner == old.owner
+                && new.source == old.source
+        });
+
+        match rename {
+            Some(index) => {
+                let new = added.remove(index);
+                diff.renamed.push((old.name.clone(), new.name.clone()));
+            }
+            None => diff.removed.push(old.name.clone()),
+        }
+    }
+
+    diff.added = added.into_iter().map(|repo| repo.name.clone()).collect();
+    diff
+}
+
+/// Makes a description fit on a single line and truncates it to `max_chars` characters.
+///
+/// Newlines and tabs become spaces and other control characters are dropped, so they
+/// cannot break the single-line display. Truncated descriptions end with `…`.
+pub fn sanitize_description(description: &str, max_chars: usize) -> String {
+    let cleaned: String = description
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.chars().count() <= max_chars {
+        return cleaned.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+
+    let kept: String = cleaned.chars().take(max_chars - 1).collect();
+    format!("{}…", kept.trim_end())
+}
+
+// Convert a fetched repository of a source to our unified RepoData format
+fn fetched_repo_to_repo_data(repo: &FetchedRepo, source: RepoSource, max_description_length: usize) -> RepoData {
+    let repo = repo.clone();
     RepoData {
-        name,
-        url,
-        description,
-        owner,
-        is_fork,
-        is_private,
-        source: RepoSource::GitHub,
+        name: repo.name,
+        url: repo.ssh_url,
+        description: sanitize_description(&repo.description, max_description_length),
+        owner: repo.owner,
+        is_fork: repo.is_fork,
+        is_private: repo.is_private,
+        source,
+        kind: RepoKind::Repository,
+        language: repo.language,
+        topics: repo.topics,
+        updated_at: repo.updated_at,
+        created_at: repo.created_at,
+        is_archived: repo.is_archived,
+        stars: repo.stars,
+        open_issues: repo.open_issues,
+        default_branch: repo.default_branch,
+        is_starred: false,
+        pushed_at: repo.pushed_at,
+        mirrored: false,
     }
 }
 
+// Convert GitHub repository format to our unified RepoData format
+pub fn github_repo_to_repo_data(repo: &FetchedRepo, max_description_length: usize) -> RepoData {
+    fetched_repo_to_repo_data(repo, RepoSource::GitHub, max_description_length)
+}
+
 // Convert GitLab repository format to our unified RepoData format
-pub fn gitlab_repo_to_repo_data(repo: &GitLabRepo) -> RepoData {
-    let (name, url, description, owner, is_fork, is_private) = repo.clone();
+pub fn gitlab_repo_to_repo_data(repo: &FetchedRepo, max_description_length: usize) -> RepoData {
+    fetched_repo_to_repo_data(repo, RepoSource::GitLab, max_description_length)
+}
+
+// Convert a gist or snippet, which come in the repository format, to our unified RepoData format
+pub fn gist_to_repo_data(repo: &FetchedRepo, source: RepoSource, max_description_length: usize) -> RepoData {
     RepoData {
-        name,
-        url,
-        description,
-        owner,
-        is_fork,
-        is_private,
-        source: RepoSource::GitLab,
+        kind: RepoKind::Gist,
+        ..fetched_repo_to_repo_data(repo, source, max_description_length)
+    }
+}
+
+/// Check if a URL looks like something git or a browser can use
+fn is_plausible_url(url: &str) -> bool {
+    if let Some(rest) = url.strip_prefix("git@") {
+        return matches!(rest.split_once(':'), Some((host, path)) if !host.is_empty() && !path.is_empty());
+    }
+
+    ["https://", "http://", "ssh://"]
+        .iter()
+        .any(|scheme| url.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty()))
+}
+
+/// Checks every cached repository and returns a description of each problem found
+pub fn validate_cache(cache_data: &CacheData) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    let sources = [
+        ("github", &cache_data.github, RepoSource::GitHub),
+        ("gitlab", &cache_data.gitlab, RepoSource::GitLab),
+    ];
+
+    for (section, source_data, expected_source) in sources {
+        let Some(source_data) = source_data else {
+            continue;
+        };
+
+        for (i, repo) in source_data.repositories.iter().enumerate() {
+            if repo.name.trim().is_empty() {
+                issues.push(format!("{} repository #{} has an empty name", section, i));
+            }
+            if !is_plausible_url(&repo.url) {
+                issues.push(format!("{} repository #{} ({}) has an invalid URL: {:?}", section, i, repo.name, repo.url));
+            }
+            if repo.source != expected_source {
+                issues.push(format!("{} repository #{} ({}) has source {:?}", section, i, repo.name, repo.source));
+            }
+        }
+    }
+
+    issues
+}
+
+/// Parses cache file contents and validates them, reporting a parse failure as an issue
+pub fn validate_cache_json(json: &str) -> Vec<String> {
+    match serde_json::from_str::<CacheData>(json) {
+        Ok(cache_data) if cache_data.version != CACHE_VERSION => vec![format!(
+            "Cache version {} is not the current version {}, so it is discarded",
+            cache_data.version, CACHE_VERSION
+        )],
+        Ok(cache_data) => validate_cache(&cache_data),
+        Err(e) => vec![format!("Cache file could not be parsed: {}", e)],
+    }
+}
+
+/// Returns where the cache is kept, like `~/.cache/github-repo-searcher/cache.json` on Linux.
+///
+/// Falls back to the working directory if there is no cache directory.
+pub fn cache_path() -> PathBuf {
+    match dirs::cache_dir() {
+        Some(dir) => dir.join(APP_DIR).join(CACHE_FILE),
+        None => PathBuf::from(LEGACY_CACHE_FILE),
+    }
+}
+
+/// Moves a file like the cache left in the working directory by an earlier version to `path`.
+///
+/// A file already at `path` is kept, and returns whether a file was moved.
+pub fn migrate_legacy_file(legacy: &Path, path: &Path) -> io::Result<bool> {
+    if legacy == path || !legacy.exists() || path.exists() {
+        return Ok(false);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Renaming fails across file systems, so copy the file there instead
+    if fs::rename(legacy, path).is_err() {
+        fs::copy(legacy, path)?;
+        fs::remove_file(legacy)?;
+    }
+    Ok(true)
+}
+
+/// Returns the cache path after moving a cache from the working directory there
+fn migrated_cache_path() -> PathBuf {
+    let path = cache_path();
+    if let Err(e) = migrate_legacy_file(Path::new(LEGACY_CACHE_FILE), &path) {
+        eprintln!("Error moving {} to {}: {}", LEGACY_CACHE_FILE, path.display(), e);
+    }
+    path
+}
+
+/// Validates the cache file on disk without fetching anything
+pub fn validate_cache_file() -> Vec<String> {
+    let path = migrated_cache_path();
+    match fs::read_to_string(&path) {
+        Ok(json) => validate_cache_json(&json),
+        Err(e) => vec![format!("Cache file {} could not be read: {}", path.display(), e)],
     }
 }
 
 pub fn save_cache(cache_data: &CacheData) -> io::Result<()> {
-    let json = serde_json::to_string_pretty(cache_data)?;
-    fs::write(CACHE_FILE, json)?;
+    let path = cache_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string_pretty(cache_data)?)?;
     Ok(())
 }
 
+/// Deletes a cache file, succeeding if there is none
+fn remove_cache_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Deletes the cache, along with one in the working directory which would be moved there
+pub fn clear_cache() -> io::Result<()> {
+    remove_cache_file(&cache_path())?;
+    remove_cache_file(Path::new(LEGACY_CACHE_FILE))
+}
+
+/// Parses a cache, treating one written with another format version as absent
+fn parse_cache(json: &str) -> Option<CacheData> {
+    match serde_json::from_str::<CacheData>(json) {
+        Ok(cache_data) if cache_data.version == CACHE_VERSION => Some(cache_data),
+        // Its fields may mean something else, so fetch everything again instead
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Error parsing cache file: {}", e);
+            None
+        },
+    }
+}
+
 pub fn load_cache() -> Option<CacheData> {
-    if !Path::new(CACHE_FILE).exists() {
+    let path = migrated_cache_path();
+    if !path.exists() {
         return None;
     }
 
-    match fs::read_to_string(CACHE_FILE) {
-        Ok(json) => match serde_json::from_str(&json) {
-            Ok(cache_data) => Some(cache_data),
-            Err(e) => {
-                eprintln!("Error parsing cache file: {}", e);
-                None
-            },
-        },
+    match fs::read_to_string(&path) {
+        Ok(json) => parse_cache(&json),
         Err(e) => {
             eprintln!("Error reading cache file: {}", e);
             None
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str, updated_at: Option<u64>) -> RepoData {
+        RepoData {
+            updated_at,
+            ..RepoData::named(name)
+        }
+    }
+
+    #[test]
+    fn test_cache_expiry_is_configurable() {
+        let mut cache_info = SourceCache::new("user".to_string());
+        cache_info.timestamp -= 5 * 60;
+
+        assert!(cache_info.is_expired(Duration::from_secs(60)));
+        assert!(!cache_info.is_expired(Duration::from_secs(DEFAULT_CACHE_EXPIRY_MINUTES * 60)));
+
+        let cache_data = CacheData {
+            version: CACHE_VERSION,
+            github: Some(SourceData { cache_info, repositories: Vec::new() }),
+            gitlab: None,
+        };
+        assert!(cache_data.is_expired(Duration::from_secs(60)));
+        assert!(!cache_data.is_expired(Duration::from_secs(10 * 60)));
+    }
+
+    #[test]
+    fn test_failed_source_keeps_cached_data() {
+        let mut previous = CacheData::new();
+        previous.update_github("user".to_string(), vec![repo("old-github", None)]);
+        previous.update_gitlab("user".to_string(), vec![repo("old-gitlab", None)]);
+
+        // GitHub was fetched again, GitLab failed
+        let mut fetched = CacheData::new();
+        fetched.update_github("user".to_string(), vec![repo("new-github", None)]);
+        fetched.keep_sources(previous, &[RepoSource::GitLab]);
+
+        let names: Vec<String> = fetched.get_all_repositories().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["new-github", "old-gitlab"]);
+
+        // Sources not asked for are not taken
+        let mut previous = CacheData::new();
+        previous.update_gitlab("user".to_string(), vec![repo("old-gitlab", None)]);
+        let mut fetched = CacheData::new();
+        fetched.keep_sources(previous, &[RepoSource::GitHub]);
+        assert!(fetched.gitlab.is_none());
+    }
+
+    #[test]
+    fn test_migrate_legacy_file() {
+        let dir = std::env::temp_dir().join("repo-searcher-test-migrate-cache");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join(".repo-cache.json");
+        let path = dir.join("cache").join("cache.json");
+
+        // Nothing to move yet
+        assert!(!migrate_legacy_file(&legacy, &path).unwrap());
+
+        fs::write(&legacy, "{}").unwrap();
+        assert!(migrate_legacy_file(&legacy, &path).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        // A cache already in the cache directory is not overwritten
+        fs::write(&legacy, "{\"github\": null}").unwrap();
+        assert!(!migrate_legacy_file(&legacy, &path).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        // Removing a cache succeeds whether it exists or not
+        remove_cache_file(&path).unwrap();
+        assert!(!path.exists());
+        remove_cache_file(&path).unwrap();
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let cache_timestamp = 1_700_000_000;
+
+        assert!(changed_since(&repo("pushed-after", Some(cache_timestamp + 60)), cache_timestamp));
+        assert!(!changed_since(&repo("pushed-before", Some(cache_timestamp - 60)), cache_timestamp));
+        assert!(!changed_since(&repo("pushed-at-cache-time", Some(cache_timestamp)), cache_timestamp));
+        assert!(!changed_since(&repo("no-timestamp", None), cache_timestamp));
+    }
+
+    fn cache_json(github_repos: &[RepoData]) -> String {
+        let mut cache_data = CacheData::new();
+        cache_data.update_github("user".to_string(), github_repos.to_vec());
+        serde_json::to_string(&cache_data).unwrap()
+    }
+
+    #[test]
+    fn test_validate_valid_cache() {
+        let json = cache_json(&[repo("first", None), repo("second", Some(1))]);
+        assert!(validate_cache_json(&json).is_empty());
+    }
+
+    #[test]
+    fn test_validate_broken_repositories() {
+        let mut no_name = repo("", None);
+        no_name.name = "  ".to_string();
+        let mut bad_url = repo("bad-url", None);
+        bad_url.url = "git@github.com".to_string();
+        let mut wrong_source = repo("wrong-source", None);
+        wrong_source.source = RepoSource::GitLab;
+
+        let json = cache_json(&[repo("fine", None), no_name, bad_url, wrong_source]);
+        let issues = validate_cache_json(&json);
+
+        assert_eq!(issues, vec![
+            "github repository #1 has an empty name".to_string(),
+            "github repository #2 (bad-url) has an invalid URL: \"git@github.com\"".to_string(),
+            "github repository #3 (wrong-source) has source GitLab".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_other_cache_version_is_absent() {
+        let json = cache_json(&[repo("first", None)]);
+        assert!(parse_cache(&json).is_some());
+
+        let old = json.replace(&format!("\"version\":{}", CACHE_VERSION), "\"version\":0");
+        assert!(parse_cache(&old).is_none());
+        assert_eq!(validate_cache_json(&old).len(), 1);
+
+        // Caches from before versioning have no version at all
+        let unversioned = json.replace(&format!("\"version\":{},", CACHE_VERSION), "");
+        assert!(parse_cache(&unversioned).is_none());
+    }
+
+    #[test]
+    fn test_validate_unparsable_cache() {
+        // A field with the wrong type, as left behind by an incompatible version
+        let json = cache_json(&[repo("first", None)]).replace("\"is_fork\":false", "\"is_fork\":\"no\"");
+        let issues = validate_cache_json(&json);
+
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("Cache file could not be parsed"));
+    }
+
+    #[test]
+    fn test_plausible_urls() {
+        assert!(is_plausible_url("git@github.com:user/repo.git"));
+        assert!(is_plausible_url("https://gitlab.com/user/repo"));
+        assert!(!is_plausible_url(""));
+        assert!(!is_plausible_url("git@:user/repo.git"));
+        assert!(!is_plausible_url("github.com/user/repo"));
+    }
+
+    #[test]
+    fn test_merge_repositories() {
+        let cached = vec![repo("first", Some(100)), repo("second", Some(100)), repo("third", Some(100))];
+
+        let mut renamed_description = repo("second", Some(500));
+        renamed_description.description = "Now with a description".to_string();
+        let updates = vec![repo("new", Some(600)), renamed_description];
+
+        let merged = merge_repositories(&cached, updates);
+        let names: Vec<&str> = merged.iter().map(|r| r.name.as_str()).collect();
+
+        assert_eq!(names, vec!["first", "second", "third", "new"]);
+        assert_eq!(merged[1].description, "Now with a description");
+        assert_eq!(merged[1].updated_at, Some(500));
+        assert_eq!(merged[0].updated_at, Some(100));
+    }
+
+    #[test]
+    fn test_add_starred_skips_owned() {
+        let mut repos = vec![repo("owned", Some(100))];
+        add_starred(&mut repos, vec![repo("owned", Some(200)), repo("starred", Some(300))]);
+
+        let names: Vec<(&str, bool)> = repos.iter().map(|r| (r.name.as_str(), r.is_starred)).collect();
+        assert_eq!(names, vec![("owned", false), ("starred", true)]);
+        assert_eq!(repos[0].updated_at, Some(100));
+    }
+
+    #[test]
+    fn test_merge_without_updates_keeps_cache() {
+        let cached = vec![repo("first", Some(100))];
+        let merged = merge_repositories(&cached, Vec::new());
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].name, "first");
+    }
+
+    #[test]
+    fn test_oldest_timestamp() {
+        let mut cache_data = CacheData::new();
+        assert_eq!(cache_data.oldest_timestamp(), None);
+
+        cache_data.update_github("user".to_string(), Vec::new());
+        cache_data.update_gitlab("user".to_string(), Vec::new());
+        cache_data.github.as_mut().unwrap().cache_info.timestamp = 200;
+        cache_data.gitlab.as_mut().unwrap().cache_info.timestamp = 100;
+        assert_eq!(cache_data.oldest_timestamp(), Some(100));
+    }
+
+    fn created(name: &str, created_at: u64) -> RepoData {
+        RepoData {
+            created_at: Some(created_at),
+            ..repo(name, None)
+        }
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let previous = vec![created("kept", 1), created("deleted", 2)];
+        let fresh = vec![created("kept", 1), created("brand-new", 3)];
+
+        let diff = diff_repositories(&previous, &fresh);
+        assert_eq!(diff.added, vec!["brand-new"]);
+        assert_eq!(diff.removed, vec!["deleted"]);
+        assert!(diff.renamed.is_empty());
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.summary(), "+1 new, -1 removed");
+    }
+
+    #[test]
+    fn test_diff_rename() {
+        let previous = vec![created("old-name", 5), repo("no-date", None)];
+        let fresh = vec![created("new-name", 5), repo("other-no-date", None)];
+
+        let diff = diff_repositories(&previous, &fresh);
+        assert_eq!(diff.renamed, vec![("old-name".to_string(), "new-name".to_string())]);
+
+        // Without a creation time a rename can't be told apart from a new repository
+        assert_eq!(diff.added, vec!["other-no-date"]);
+        assert_eq!(diff.removed, vec!["no-date"]);
+    }
+
+    #[test]
+    fn test_diff_flag_changes() {
+        let previous = vec![repo("secret", None), repo("old", None), repo("same", None)];
+        let mut fresh = previous.clone();
+        fresh[0].is_private = true;
+        fresh[1].is_archived = true;
+        fresh[1].description = "Superseded".to_string();
+
+        let diff = diff_repositories(&previous, &fresh);
+        assert_eq!(
+            diff.changed,
+            vec![
+                ("secret".to_string(), vec!["now private".to_string()]),
+                ("old".to_string(), vec!["archived".to_string(), "description changed".to_string()]),
+            ]
+        );
+        assert_eq!(diff.summary(), "2 changed");
+        assert_eq!(diff.detail_lines()[1], "* old: archived, description changed");
+    }
+
+    #[test]
+    fn test_diff_without_changes() {
+        let repos = vec![repo("a", None), repo("b", None)];
+        let diff = diff_repositories(&repos, &repos);
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "no changes");
+        assert!(diff.detail_lines().is_empty());
+    }
+
+    #[test]
+    fn test_description_truncated_at_boundary() {
+        let exact = "a".repeat(10);
+        assert_eq!(sanitize_description(&exact, 10), exact);
+
+        // One character too long keeps room for the ellipsis
+        let long = "b".repeat(11);
+        assert_eq!(sanitize_description(&long, 10), format!("{}…", "b".repeat(9)));
+        assert_eq!(sanitize_description(&long, 10).chars().count(), 10);
+
+        // Characters are counted, not bytes
+        assert_eq!(sanitize_description("äöü", 3), "äöü");
+        assert_eq!(sanitize_description("äöüß", 3), "äö…");
+        assert_eq!(sanitize_description("abc", 0), "");
+    }
+
+    #[test]
+    fn test_description_control_characters_stripped() {
+        assert_eq!(sanitize_description("line one\nline two", 300), "line one line two");
+        assert_eq!(sanitize_description("\ttabbed\r\n", 300), "tabbed");
+        assert_eq!(sanitize_description("bell\u{7}\u{1b}[31mred", 300), "bell[31mred");
+    }
+}
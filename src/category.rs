@@ -0,0 +1,204 @@
+//! Category emojis inferred from keywords in repository names and descriptions
+//!
+//! Rules are checked in priority order and the first one with a matching keyword wins,
+//! so a name like `test-tools` gets the emoji of whichever category comes first.
+//! Keywords match whole words only, so `scrapbook` does not count as an app.
+//! Languages have no categories, their icons come from the language reported by the API.
+
+use crate::formatter::FormatOptions;
+
+/// A category with its emoji and the keywords which put a repository into it
+#[derive(Debug, PartialEq)]
+pub struct CategoryRule {
+    pub name: &'static str,
+    pub emoji: &'static str,
+    pub keywords: &'static [&'static str],
+}
+
+/// All categories in their default priority order.
+///
+/// Tests come before tools on purpose, so `test-utils` counts as a test repository.
+pub const RULES: &[CategoryRule] = &[
+    CategoryRule { name: "test", emoji: "🧪", keywords: &["test", "testing", "spec", "bench", "benchmark"] },
+    CategoryRule { name: "tools", emoji: "🔧", keywords: &["tool", "util", "utility", "utilities", "cli", "script"] },
+    CategoryRule { name: "docs", emoji: "📚", keywords: &["doc", "documentation", "notes", "wiki", "blog"] },
+    CategoryRule { name: "web", emoji: "🌐", keywords: &["web", "website", "site", "frontend"] },
+    CategoryRule { name: "backend", emoji: "📡", keywords: &["api", "server", "backend", "service"] },
+    CategoryRule { name: "data", emoji: "💾", keywords: &["database", "data", "sql"] },
+    CategoryRule { name: "mobile", emoji: "📱", keywords: &["app", "mobile", "android", "ios"] },
+    CategoryRule { name: "game", emoji: "🎮", keywords: &["game"] },
+    CategoryRule { name: "devops", emoji: "🚀", keywords: &["devops", "cicd", "deploy", "deployment", "docker", "ansible"] },
+    CategoryRule { name: "infra", emoji: "🧱", keywords: &["infra", "infrastructure", "terraform", "kubernetes", "k8s"] },
+    CategoryRule { name: "crypto", emoji: "🔐", keywords: &["crypto", "cryptography", "blockchain", "bitcoin", "ethereum", "wallet"] },
+    CategoryRule { name: "bot", emoji: "🤖", keywords: &["bot", "chatbot"] },
+];
+
+/// The order categories are checked in, set with `--category-order`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryOrder {
+    rules: Vec<&'static CategoryRule>,
+}
+
+impl Default for CategoryOrder {
+    fn default() -> Self {
+        Self { rules: RULES.iter().collect() }
+    }
+}
+
+impl CategoryOrder {
+    /// Parses comma-separated category names like `backend,web`.
+    ///
+    /// The listed categories come first in the given order, the others follow in
+    /// their default order.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let rule = RULES.iter().find(|rule| rule.name == name).ok_or_else(|| {
+                let names: Vec<&str> = RULES.iter().map(|rule| rule.name).collect();
+                format!("unknown category '{}', expected one of {}", name, names.join(", "))
+            })?;
+
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+
+        for rule in RULES {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+
+        Ok(Self { rules })
+    }
+}
+
+/// Splits text into lowercase words at anything but letters and digits, and at camelCase humps.
+///
+/// A hump is an uppercase letter starting a capitalized word, so `myWebApp` is split while
+/// `iOS` and `macOS` stay whole words.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lowercase = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let capitalized = chars.peek().is_some_and(|next| next.is_lowercase());
+        let hump = c.is_uppercase() && previous_lowercase && capitalized;
+        if (!c.is_alphanumeric() || hump) && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        }
+        previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Check if a word is a keyword, or its plural like `tools` for `tool`
+fn is_keyword(word: &str, keyword: &str) -> bool {
+    word == keyword || word.strip_suffix('s') == Some(keyword)
+}
+
+/// Returns the emoji of the first category in `order` whose keywords are words of the name or description.
+///
+/// Without emoji the category name in brackets is returned instead, like `[web]`.
+pub fn get_category_emoji(name: &str, description: &str, order: &CategoryOrder, options: FormatOptions) -> Option<String> {
+    let words = words(&format!("{} {}", name, description));
+
+    let rule = order.rules.iter().find(|rule| {
+        rule.keywords
+            .iter()
+            .any(|keyword| words.iter().any(|word| is_keyword(word, keyword)))
+    })?;
+
+    if options.emoji {
+        Some(rule.emoji.to_string())
+    } else {
+        Some(format!("[{}]", rule.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_order() {
+        let order = CategoryOrder::default();
+
+        assert_eq!(get_category_emoji("rust-web-server", "", &order, FormatOptions::default()).as_deref(), Some("🌐"));
+        assert_eq!(get_category_emoji("test-utils", "", &order, FormatOptions::default()).as_deref(), Some("🧪"));
+        assert_eq!(get_category_emoji("notes", "", &order, FormatOptions::default()).as_deref(), Some("📚"));
+        assert_eq!(get_category_emoji("misc", "Random things", &order, FormatOptions::default()).as_deref(), None);
+
+        // The description counts as well
+        assert_eq!(get_category_emoji("misc", "A small GAME", &order, FormatOptions::default()).as_deref(), Some("🎮"));
+
+        // Without emoji the category is named instead
+        let ascii = FormatOptions { emoji: false };
+        assert_eq!(get_category_emoji("rust-web-server", "", &order, ascii).as_deref(), Some("[web]"));
+    }
+
+    #[test]
+    fn test_keywords_match_whole_words() {
+        let order = CategoryOrder::default();
+        let emoji = |name: &str, description: &str| get_category_emoji(name, description, &order, FormatOptions::default());
+
+        // Keywords inside other words used to match
+        assert_eq!(emoji("scrapbook", "").as_deref(), None);
+        assert_eq!(emoji("gotcha", "Happy little things").as_deref(), None);
+        assert_eq!(emoji("dockerless", "").as_deref(), None);
+
+        // Names split at separators and camelCase humps, plurals match too
+        assert_eq!(emoji("database-tools", "").as_deref(), Some("🔧"));
+        assert_eq!(emoji("myWebApp", "").as_deref(), Some("🌐"));
+        assert_eq!(emoji("slack_bots", "").as_deref(), Some("🤖"));
+        assert_eq!(emoji("k8s-cluster", "").as_deref(), Some("🧱"));
+
+        // Mixed case names are not split into their letters
+        assert_eq!(emoji("iOS client", "").as_deref(), Some("📱"));
+
+        // Short English words are no keywords, languages have their own icons
+        assert_eq!(emoji("advent", "Let's go, the ci of it all").as_deref(), None);
+        assert_eq!(emoji("rust-cli", "").as_deref(), Some("🔧"));
+        assert_eq!(emoji("cicd-pipelines", "").as_deref(), Some("🚀"));
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("myWebApp v2_final-CLI"), vec!["my", "web", "app", "v2", "final", "cli"]);
+        assert_eq!(words("iOS and macOS"), vec!["ios", "and", "macos"]);
+    }
+
+    #[test]
+    fn test_reordering_changes_ambiguous_names() {
+        let order = CategoryOrder::parse("backend, web").unwrap();
+        assert_eq!(get_category_emoji("rust-web-server", "", &order, FormatOptions::default()).as_deref(), Some("📡"));
+
+        let order = CategoryOrder::parse("tools").unwrap();
+        assert_eq!(get_category_emoji("test-utils", "", &order, FormatOptions::default()).as_deref(), Some("🔧"));
+
+        // Unlisted categories keep their default order after the listed ones
+        assert_eq!(get_category_emoji("notes", "", &order, FormatOptions::default()).as_deref(), Some("📚"));
+    }
+
+    #[test]
+    fn test_parse_category_order() {
+        let order = CategoryOrder::parse("web,web,,data").unwrap();
+        let names: Vec<&str> = order.rules.iter().map(|rule| rule.name).collect();
+        assert_eq!(
+            names,
+            vec!["web", "data", "test", "tools", "docs", "backend", "mobile", "game", "devops", "infra", "crypto", "bot"]
+        );
+
+        assert_eq!(CategoryOrder::parse("").unwrap(), CategoryOrder::default());
+        assert!(CategoryOrder::parse("web,nope").unwrap_err().contains("unknown category 'nope'"));
+    }
+}
@@ -12,17 +12,118 @@
 //! - (fork) or (fork: description) - Fork of another repository
 //! - 🔒 - Private repository (shown at the end of repository name)
 
-use clap::{Arg, Command};
+use crate::cache;
+use crate::category::CategoryOrder;
+use crate::config;
+use crate::export;
+use crate::formatter::{self, RepoSource};
+use crate::github;
+use crate::gitlab;
+use crate::grouping::GroupBy;
+use crate::multiplexer::{self, Multiplexer};
+use crate::quick_filter::{self, QuickFilter};
+use crate::sort::{ActivityWeights, SortKey, SortSpec};
+use crate::user_agent;
+use clap::error::ErrorKind;
+use clap::{Arg, ArgMatches, Command};
+use std::path::PathBuf;
+use std::time::Duration;
 
+#[derive(Clone)]
 pub struct AppArgs {
     pub use_dummy: bool,
     pub github_token: Option<String>,
     pub gitlab_token: Option<String>,
+    pub github_host: String,
+    pub gitlab_base_url: String,
     pub force_download: bool,
+    pub cache_expiry: Duration,
+    pub exclude_languages: Vec<String>,
+    pub exclude_topics: Vec<String>,
+    pub highlight_new: bool,
+    pub validate_cache: bool,
+    pub clear_cache: bool,
+    pub clone_dir: PathBuf,
+    pub git_tui: String,
+    pub multiplexer: Multiplexer,
+    pub clone_command: String,
+    pub sort: Option<SortSpec>,
+    pub activity_weights: ActivityWeights,
+    pub group_by: GroupBy,
+    pub category_order: CategoryOrder,
+    pub format_options: formatter::FormatOptions,
+    pub display_template: formatter::DisplayTemplate,
+    pub strip_common_prefix: bool,
+    pub cycle: bool,
+    pub incremental: bool,
+    pub refresh_identity: bool,
+    pub post_action_delay_ms: u64,
+    pub repo: Option<String>,
+    pub source: Option<RepoSource>,
+    pub json: bool,
+    pub json_fields: Option<Vec<String>>,
+    pub confirm_quit_when_busy: bool,
+    pub csv: Option<String>,
+    pub show_aliases: bool,
+    pub show_owner_source: bool,
+    pub show_diff: bool,
+    pub gitlab_languages: bool,
+    pub max_concurrency: usize,
+    pub include_gists: bool,
+    pub include_starred: bool,
+    pub hide_archived: bool,
+    pub dedup: bool,
+    pub orgs: Vec<String>,
+    pub gitlab_groups: Vec<String>,
+    pub max_description_length: usize,
+    pub token_expiry_warning_days: u64,
+    pub min_score: i64,
+    pub quick_filters: Vec<QuickFilter>,
+    pub ellipsis: String,
+    pub simple: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub verbose: bool,
+    pub path: Option<String>,
+    pub check_local: bool,
+    pub user_agent: String,
+    pub require_nonempty: Vec<RepoSource>,
+    pub basket: bool,
+    pub clear_basket: bool,
+    pub query: String,
+    pub restore_query: bool,
+    pub multi: bool,
+    pub print: bool,
+    pub output_json: bool,
 }
 
-pub fn parse_args() -> AppArgs {
-    let matches = Command::new("repo-url-picker")
+/// Splits a comma-separated flag value into its trimmed, non-empty parts
+fn parse_comma_list(value: Option<&String>) -> Vec<String> {
+    value
+        .map(|v| {
+            v.split(',')
+                .map(|part| part.trim().to_string())
+                .filter(|part| !part.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the query given with `--query`, or the positional query words joined with spaces
+fn initial_query(matches: &ArgMatches) -> String {
+    if let Some(query) = matches.get_one::<String>("query-option") {
+        return query.trim().to_string();
+    }
+
+    matches
+        .get_many::<String>("query")
+        .map(|words| words.map(String::as_str).collect::<Vec<_>>().join(" "))
+        .unwrap_or_default()
+}
+
+/// Builds the command-line parser
+pub fn command() -> Command {
+    Command::new("repo-url-picker")
         .version("0.1.0")
         .author("Your Name <you@example.com>")
         .about("Pick GitHub and GitLab repos by fuzzy filtering with visual indicators for repository types")
@@ -34,6 +135,20 @@ pub fn parse_args() -> AppArgs {
                 .help("GitHub personal access token")
                 .conflicts_with("dummy"),
         )
+        .arg(
+            Arg::new("github-host")
+                .long("github-host")
+                .value_name("HOST")
+                .value_parser(github::parse_host)
+                .help("GitHub Enterprise host like github.example.com, for the API and repository URLs [default: github.com]"),
+        )
+        .arg(
+            Arg::new("gitlab-host")
+                .long("gitlab-host")
+                .value_name("URL")
+                .value_parser(gitlab::parse_base_url)
+                .help("Self-hosted GitLab like https://gitlab.example.com, for the API and repository URLs [default: https://gitlab.com]"),
+        )
         .arg(
             Arg::new("gitlab-token")
                 .short('l')
@@ -56,38 +171,810 @@ pub fn parse_args() -> AppArgs {
                 .help("Force download repositories from GitHub, ignoring cache")
                 .action(clap::ArgAction::SetTrue),
         )
-        .get_matches();
+        .arg(
+            Arg::new("no-force-download")
+                .long("no-force-download")
+                .help("Use the cache even with force_download = true in the config file")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("force-download"),
+        )
+        .arg(
+            Arg::new("cache-expiry")
+                .long("cache-expiry")
+                .value_name("MINUTES")
+                .value_parser(clap::value_parser!(u64))
+                .help("Minutes the cache is used before fetching again [default: 30]"),
+        )
+        .arg(
+            Arg::new("exclude-language")
+                .long("exclude-language")
+                .value_name("LANGS")
+                .help("Hide repositories whose primary language is in this comma-separated list"),
+        )
+        .arg(
+            Arg::new("exclude-topic")
+                .long("exclude-topic")
+                .value_name("TOPICS")
+                .help("Hide repositories tagged with any topic in this comma-separated list"),
+        )
+        .arg(
+            Arg::new("highlight-new")
+                .long("highlight-new")
+                .help("List repositories changed since the previous cache first once a refresh completes")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("validate-cache")
+                .long("validate-cache")
+                .help("Check the repository cache for problems and exit, without fetching anything")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clear-cache")
+                .long("clear-cache")
+                .help("Delete the repository cache and exit, without fetching anything")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clone-dir")
+                .long("clone-dir")
+                .value_name("DIR")
+                .default_value(".")
+                .help("Directory repositories are cloned into"),
+        )
+        .arg(
+            Arg::new("git-tui")
+                .long("git-tui")
+                .value_name("CMD")
+                .default_value("lazygit")
+                .help("Git TUI launched in the repository with Ctrl+G, cloning it first if needed"),
+        )
+        .arg(
+            Arg::new("multiplexer")
+                .long("multiplexer")
+                .value_name("NAME")
+                .value_parser(Multiplexer::parse)
+                .default_value("none")
+                .help("Open the clone command of every basket repository in its own tmux window or iTerm tab with Ctrl+E (tmux, iterm, none)"),
+        )
+        .arg(
+            Arg::new("clone-command")
+                .long("clone-command")
+                .value_name("TEMPLATE")
+                .default_value(multiplexer::DEFAULT_CLONE_COMMAND)
+                .help("Command run in each window opened with Ctrl+E, with {url}, {name} and {dir} filled in"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("KEY")
+                .value_parser(SortKey::NAMES.to_vec())
+                .help("Sort repositories, 'created' lists the newest first, 'activity' the most active first, 'recent' the last pushed first"),
+        )
+        .arg(
+            Arg::new("sort-by")
+                .long("sort-by")
+                .value_name("KEYS")
+                .value_parser(SortSpec::parse)
+                .conflicts_with("sort")
+                .help("Sort by several comma-separated keys with an optional direction, like source,stars:desc,name:asc"),
+        )
+        .arg(
+            Arg::new("activity-weights")
+                .long("activity-weights")
+                .value_name("STARS,ISSUES,RECENCY")
+                .value_parser(|value: &str| {
+                    ActivityWeights::parse(value).ok_or("expected three non-negative numbers like 1,0.5,2")
+                })
+                .help("Weights of stars, open issues and recent updates in the score used by --sort activity"),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .value_name("GROUPING")
+                .value_parser(GroupBy::parse)
+                .default_value("none")
+                .help("Show the repositories in labeled sections, 'visibility' splits public from private ones (none, visibility)"),
+        )
+        .arg(
+            Arg::new("category-order")
+                .long("category-order")
+                .value_name("CATEGORIES")
+                .value_parser(|value: &str| CategoryOrder::parse(value))
+                .help("Categories whose emoji wins when several match, like backend,web (the others keep their default order)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("TEMPLATE")
+                .value_parser(|value: &str| formatter::DisplayTemplate::parse(value))
+                .help("Layout of each row, with placeholders {name}, {owner}, {desc}, {details}, {fork}, {private}, {gist}, {source}, {language} and {stars}"),
+        )
+        .arg(
+            Arg::new("no-emoji")
+                .long("no-emoji")
+                .help("Mark repositories with ASCII like [priv] and [web] instead of emoji, for terminals drawing them at the wrong width")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("cycle")
+                .long("cycle")
+                .help("Move from the last repository to the first one and back when moving past the ends of the list")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strip-common-prefix")
+                .long("strip-common-prefix")
+                .help("Show a name prefix shared by all matching repositories once instead of on every line")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("incremental")
+                .long("incremental")
+                .help("Only fetch repositories updated since the cache was saved and merge them into it")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("force-download"),
+        )
+        .arg(
+            Arg::new("refresh-identity")
+                .long("refresh-identity")
+                .help("Ask the APIs who the tokens belong to instead of reusing the cached usernames")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("post-action-delay")
+                .long("post-action-delay")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("1000")
+                .help("Milliseconds to wait after opening a repository in the browser before returning to the search, 0 returns immediately"),
+        )
+        .arg(
+            Arg::new("repo")
+                .long("repo")
+                .value_name("OWNER/NAME")
+                .help("Fetch a single repository and open it in the browser, without listing all repositories")
+                .conflicts_with("dummy"),
+        )
+        .arg(
+            Arg::new("source")
+                .long("source")
+                .value_name("SOURCE")
+                .value_parser(["github", "gitlab", "all"])
+                .default_value("all")
+                .help("Only list the repositories of this source, and only look up --repo on it instead of trying GitHub and then GitLab"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print the repository fetched with --repo as JSON instead of opening it")
+                .action(clap::ArgAction::SetTrue)
+                .requires("repo"),
+        )
+        .arg(
+            Arg::new("json-fields")
+                .long("json-fields")
+                .value_name("FIELDS")
+                .value_parser(export::parse_json_fields)
+                .help("Only print these comma-separated fields with --json, like name,url")
+                .requires("json"),
+        )
+        .arg(
+            Arg::new("confirm-quit-when-busy")
+                .long("confirm-quit-when-busy")
+                .help("Ask for a second Esc or Ctrl+C before quitting while repositories are being fetched")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("csv")
+                .long("csv")
+                .value_name("FILE")
+                .help("Write all repositories to a CSV file and exit, instead of opening the fuzzy finder"),
+        )
+        .arg(
+            Arg::new("show-aliases")
+                .long("show-aliases")
+                .help("Show the aliases from .repo-aliases next to the repositories, they are searchable either way")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show-owner-source")
+                .long("show-owner-source")
+                .help("Show the owner and source of each repository in columns before it, toggle with Ctrl+O")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show-diff")
+                .long("show-diff")
+                .help("Fetch all repositories, print which ones changed since the cache and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("gitlab-languages")
+                .long("gitlab-languages")
+                .help("Fetch the language of every GitLab project, which takes one extra request per project")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-concurrency")
+                .long("max-concurrency")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("4")
+                .help("Maximum number of GitLab page and per-repository requests, like --gitlab-languages, running at once"),
+        )
+        .arg(
+            Arg::new("include-gists")
+                .long("include-gists")
+                .help("Also list your GitHub gists and GitLab snippets, marked with 📝")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("org")
+                .long("org")
+                .value_name("NAME")
+                .action(clap::ArgAction::Append)
+                .help("Also list the repositories of this GitHub organization, owned by it (repeatable)"),
+        )
+        .arg(
+            Arg::new("gitlab-group")
+                .long("gitlab-group")
+                .value_name("PATH")
+                .action(clap::ArgAction::Append)
+                .help("Also list the projects of this GitLab group and its subgroups, like company/team, owned by it (repeatable)"),
+        )
+        .arg(
+            Arg::new("include-starred")
+                .long("include-starred")
+                .help("Also list the repositories you starred on GitHub and GitLab, marked with ⭐")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("hide-archived")
+                .long("hide-archived")
+                .help("Leave archived repositories, marked with 📦, out of the list, the finder shows them again with Ctrl+X")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .help("List repositories with the same name on GitHub and GitLab once, marked with 🔁")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-description-length")
+                .long("max-description-length")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("300")
+                .help("Truncate fetched descriptions to this many characters to keep the cache small"),
+        )
+        .arg(
+            Arg::new("token-expiry-warning-days")
+                .long("token-expiry-warning-days")
+                .value_name("DAYS")
+                .value_parser(clap::value_parser!(u64).range(..=36_500))
+                .default_value("7")
+                .help("Warn when a token expires within this many days, 0 only warns on the last day"),
+        )
+        .arg(
+            Arg::new("min-score")
+                .long("min-score")
+                .value_name("N")
+                .value_parser(clap::value_parser!(i64).range(0..))
+                .default_value("0")
+                .help("Hide fuzzy matches scoring below N, 0 shows all matches"),
+        )
+        .arg(
+            Arg::new("quick-filter")
+                .long("quick-filter")
+                .value_name("PRESET")
+                .value_parser(QuickFilter::parse)
+                .action(clap::ArgAction::Append)
+                .default_values(quick_filter::DEFAULT_QUICK_FILTERS)
+                .help("Preset toggled with Alt+1 to Alt+9, repeat for more: forks, private, stale, or a query like rust=rust -deprecated"),
+        )
+        .arg(
+            Arg::new("ellipsis")
+                .long("ellipsis")
+                .value_name("STR")
+                .value_parser(|value: &str| formatter::parse_ellipsis(value))
+                .default_value(formatter::DEFAULT_ELLIPSIS)
+                .help("Marker for truncated repositories and queries, like ... for terminals without the … glyph"),
+        )
+        .arg(
+            Arg::new("simple")
+                .long("simple")
+                .help("Pick repositories from a numbered list with line-based queries instead of the fuzzy finder")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Save the keys pressed in the fuzzy finder with their timing as JSON, for demos")
+                .conflicts_with("simple"),
+        )
+        .arg(
+            Arg::new("replay")
+                .long("replay")
+                .value_name("FILE")
+                .help("Press the keys saved with --record in the fuzzy finder at their recorded pace")
+                .conflicts_with_all(["simple", "record"]),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Log details like how long filtering takes per keystroke to repo-searcher.log next to the cache")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check-local")
+                .long("check-local")
+                .help("Mark repositories cloned into --clone-dir with 📂, or 📂* with uncommitted changes")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("user-agent")
+                .long("user-agent")
+                .value_name("STRING")
+                .value_parser(user_agent::parse_user_agent)
+                .help("User-Agent sent to the GitHub and GitLab APIs [default: github-repo-searcher/<version>]"),
+        )
+        .arg(
+            Arg::new("require-nonempty")
+                .long("require-nonempty")
+                .value_name("SOURCE")
+                .value_parser(["github", "gitlab"])
+                .action(clap::ArgAction::Append)
+                .help("Exit with an error from --csv or --show-diff if this source has no repositories (repeatable)"),
+        )
+        .arg(
+            Arg::new("path")
+                .long("path")
+                .value_name("PATH")
+                .help("File or directory to open with Ctrl+F in the file browser, like src/main.rs"),
+        )
+        .arg(
+            Arg::new("basket")
+                .long("basket")
+                .help("Only show the repositories added to the basket with Ctrl+B, without fetching")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("dummy"),
+        )
+        .arg(
+            Arg::new("clear-basket")
+                .long("clear-basket")
+                .help("Remove all repositories from the basket and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("query-option")
+                .long("query")
+                .value_name("TEXT")
+                .conflicts_with("query")
+                .help("Initial query for the fuzzy finder, like the positional query but easier to script"),
+        )
+        .arg(
+            Arg::new("no-restore-query")
+                .long("no-restore-query")
+                .help("Start with an empty query instead of the one from the previous run, which is not saved either")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("multi")
+                .long("multi")
+                .help("Mark several repositories with Tab in the fuzzy finder, Enter copies their SSH URLs one per line")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("simple"),
+        )
+        .arg(
+            Arg::new("print")
+                .long("print")
+                .help("Print the SSH URLs of the repositories matching the query and exit, instead of opening the fuzzy finder")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["simple", "record", "replay"]),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FORMAT")
+                .value_parser(["json"])
+                .help("Print the repositories matching the query in this format and exit, instead of opening the fuzzy finder")
+                .conflicts_with_all(["print", "simple", "record", "replay"]),
+        )
+        .arg(
+            Arg::new("query")
+                .value_name("QUERY")
+                .help("Initial query for the fuzzy finder, several words are joined with spaces")
+                .num_args(1..),
+        )
+}
+
+/// Exits like clap does for an invalid flag value, for values clap cannot check itself
+fn invalid_value(message: impl std::fmt::Display) -> ! {
+    command().error(ErrorKind::ValueValidation, message).exit()
+}
+
+pub fn parse_args() -> AppArgs {
+    let matches = command().get_matches();
+
+    // Read the config file, whose settings are used where no flag is given
+    let config = config::load_config().unwrap_or_else(|e| invalid_value(e));
+
+    args_from(matches, config)
+}
+
+/// Builds the arguments from parsed command line flags and the config file
+pub fn args_from(matches: ArgMatches, config: config::Config) -> AppArgs {
+    // Get the initial query, unquoted words are joined like a quoted query
+    let query = initial_query(&matches);
+
+    // Check if the query of the previous run should be restored and saved
+    let restore_query = !matches.get_flag("no-restore-query");
+
+    // Check if several repositories can be marked for copying their URLs
+    let multi = matches.get_flag("multi");
+
+    // Check if the matching URLs should be printed instead of opening the fuzzy finder
+    let print = matches.get_flag("print");
+
+    // Check if the matching repositories should be printed as JSON
+    let output_json = matches.get_one::<String>("output").is_some_and(|format| format == "json");
 
     // Check if dummy mode is enabled
     let use_dummy = matches.get_flag("dummy");
 
     // Get GitHub and GitLab tokens
     let github_token = if !use_dummy {
-        matches.get_one::<String>("github-token").cloned()
+        matches.get_one::<String>("github-token").cloned().or(config.github_token)
     } else {
         None
     };
 
     let gitlab_token = if !use_dummy {
-        matches.get_one::<String>("gitlab-token").cloned()
+        matches.get_one::<String>("gitlab-token").cloned().or(config.gitlab_token)
     } else {
         None
     };
 
+    // Get the GitHub Enterprise host, the config file's is checked like a flag
+    let github_host = match (matches.get_one::<String>("github-host"), config.github_host) {
+        (Some(host), _) => host.clone(),
+        (None, Some(host)) => github::parse_host(&host)
+            .unwrap_or_else(|e| invalid_value(format!("Invalid github_host in the config file: {}", e))),
+        (None, None) => github::DEFAULT_HOST.to_string(),
+    };
+
+    // Get the self-hosted GitLab, the config file's is checked like a flag
+    let gitlab_base_url = match (matches.get_one::<String>("gitlab-host"), config.gitlab_host) {
+        (Some(base_url), _) => base_url.clone(),
+        (None, Some(host)) => gitlab::parse_base_url(&host)
+            .unwrap_or_else(|e| invalid_value(format!("Invalid gitlab_host in the config file: {}", e))),
+        (None, None) => gitlab::DEFAULT_BASE_URL.to_string(),
+    };
+
+    // Check if only the cache should be validated, which needs no tokens
+    let validate_cache = matches.get_flag("validate-cache");
+
+    // Check if only the cache should be deleted, which needs no tokens either
+    let clear_cache = matches.get_flag("clear-cache");
+
+    // Check if only the basket is used, which needs no tokens either
+    let basket = matches.get_flag("basket");
+    let clear_basket = matches.get_flag("clear-basket");
+
+    // Check if force download is enabled, --no-force-download overriding the config file
+    let force_download = matches.get_flag("force-download")
+        || (!matches.get_flag("no-force-download") && config.force_download.unwrap_or(false));
+
+    // Printing the matches is fine with any cache, only fetching needs a token
+    let prints_from_cache = (print || output_json) && !force_download && cache::load_cache().is_some();
+
     // Validate that at least one token is provided if not in dummy mode
-    if !use_dummy && github_token.is_none() && gitlab_token.is_none() {
-        eprintln!("Error: At least one of --github-token or --gitlab-token must be provided");
+    let needs_token = !use_dummy && !validate_cache && !clear_cache && !basket && !clear_basket && !prints_from_cache;
+    if needs_token && github_token.is_none() && gitlab_token.is_none() {
+        eprintln!("Error: At least one of --github-token or --gitlab-token must be provided, or set in the config file");
         eprintln!("       Alternatively, use --dummy for testing with sample data");
         std::process::exit(1);
     }
 
-    // Check if force download is enabled
-    let force_download = matches.get_flag("force-download");
+    // Get how long the cache is used before fetching again, the flag winning over the config file
+    let cache_expiry_minutes = matches
+        .get_one::<u64>("cache-expiry")
+        .copied()
+        .or(config.cache_expiry_minutes)
+        .unwrap_or(cache::DEFAULT_CACHE_EXPIRY_MINUTES);
+    // Huge values from the flag or config file mean the cache never expires, instead of overflowing
+    let cache_expiry = Duration::from_secs(cache_expiry_minutes.saturating_mul(60));
+
+    // Get language and topic exclusions
+    let exclude_languages = parse_comma_list(matches.get_one::<String>("exclude-language"));
+    let exclude_topics = parse_comma_list(matches.get_one::<String>("exclude-topic"));
+
+    // Check if changed repositories should be sorted first
+    let highlight_new = matches.get_flag("highlight-new");
+
+    // Get where to clone repositories and which git TUI to open them in
+    let clone_dir = PathBuf::from(matches.get_one::<String>("clone-dir").unwrap());
+    let git_tui = matches.get_one::<String>("git-tui").unwrap().clone();
+
+    // Get where and how Ctrl+E opens the basket repositories
+    let multiplexer = *matches.get_one::<Multiplexer>("multiplexer").unwrap();
+    let clone_command = matches.get_one::<String>("clone-command").unwrap().clone();
+
+    // Get the sort order, clap already rejected unknown keys
+    let sort = matches
+        .get_one::<SortSpec>("sort-by")
+        .cloned()
+        .or_else(|| matches.get_one::<String>("sort").and_then(|name| SortKey::parse(name)).map(SortSpec::from));
+    let activity_weights = matches.get_one::<ActivityWeights>("activity-weights").copied().unwrap_or_default();
+
+    // Get how the finder list is split into sections
+    let group_by = *matches.get_one::<GroupBy>("group-by").unwrap();
+
+    // Get the category priority for the emojis
+    let category_order = matches.get_one::<CategoryOrder>("category-order").cloned().unwrap_or_default();
+
+    // Check if the markers should be ASCII instead of emoji
+    let no_emoji = matches.get_flag("no-emoji") || config.no_emoji.unwrap_or(false);
+    let format_options = formatter::FormatOptions { emoji: !no_emoji };
+
+    // Get the layout of each row
+    let display_template = matches.get_one::<formatter::DisplayTemplate>("format").cloned().unwrap_or_default();
+
+    // Check if shared name prefixes should be elided
+    let strip_common_prefix = matches.get_flag("strip-common-prefix");
+
+    // Check if the selection wraps around at the ends of the list
+    let cycle = matches.get_flag("cycle");
+
+    // Check if only updates since the cache should be fetched
+    let incremental = matches.get_flag("incremental");
+
+    // Check if the cached usernames should be fetched again
+    let refresh_identity = matches.get_flag("refresh-identity");
+
+    // Get how long to wait after opening a repository, clap already validated the number
+    let post_action_delay_ms = *matches.get_one::<u64>("post-action-delay").unwrap();
+
+    // Get the single repository to look up, and the source it and the list are limited to
+    let repo = matches.get_one::<String>("repo").cloned();
+    let source = match matches.get_one::<String>("source").map(String::as_str) {
+        Some("github") => Some(RepoSource::GitHub),
+        Some("gitlab") => Some(RepoSource::GitLab),
+        _ => None,
+    };
+    let json = matches.get_flag("json");
+    let json_fields = matches.get_one::<Vec<String>>("json-fields").cloned();
+
+    // Check if quitting during a fetch needs confirmation
+    let confirm_quit_when_busy = matches.get_flag("confirm-quit-when-busy");
+
+    // Get the file to export repositories to
+    let csv = matches.get_one::<String>("csv").cloned();
+
+    // Check if aliases should be displayed
+    let show_aliases = matches.get_flag("show-aliases");
+
+    // Check if the owner and source columns should be shown
+    let show_owner_source = matches.get_flag("show-owner-source");
+
+    // Check if only the changes since the cache should be printed
+    let show_diff = matches.get_flag("show-diff");
+
+    // Get which per-repository requests to make, and how many at once
+    let gitlab_languages = matches.get_flag("gitlab-languages");
+    let max_concurrency = *matches.get_one::<usize>("max-concurrency").unwrap();
+
+    // Check if gists and snippets should be listed too
+    let include_gists = matches.get_flag("include-gists");
+
+    // Check if starred repositories should be listed too
+    let include_starred = matches.get_flag("include-starred");
+
+    // Check if archived repositories should be hidden
+    let hide_archived = matches.get_flag("hide-archived");
+
+    // Check if mirrors on GitHub and GitLab should be listed once
+    let dedup = matches.get_flag("dedup");
+
+    // Get the GitHub organizations whose repositories are listed too
+    let orgs: Vec<String> = matches.get_many::<String>("org").unwrap_or_default().cloned().collect();
+
+    // Get the GitLab groups whose projects are listed too
+    let gitlab_groups: Vec<String> = matches.get_many::<String>("gitlab-group").unwrap_or_default().cloned().collect();
+
+    // Get how long fetched descriptions may be
+    let max_description_length = *matches.get_one::<usize>("max-description-length").unwrap();
+
+    // Get how many days before a token expires to warn about it
+    let token_expiry_warning_days = *matches.get_one::<u64>("token-expiry-warning-days").unwrap();
+
+    // Get the score weak fuzzy matches are hidden below
+    let min_score = *matches.get_one::<i64>("min-score").unwrap();
+
+    // Get the presets for the number keys, which only has keys for nine
+    let quick_filters: Vec<QuickFilter> = matches.get_many::<QuickFilter>("quick-filter").unwrap().cloned().collect();
+    if quick_filters.len() > quick_filter::MAX_QUICK_FILTERS {
+        invalid_value(format!("At most {} quick filters can be given", quick_filter::MAX_QUICK_FILTERS));
+    }
+
+    // Get the marker for truncated text, clap already checked its width
+    let ellipsis = matches.get_one::<String>("ellipsis").unwrap().clone();
+
+    // Check if the line-based prompt should replace the fuzzy finder
+    let simple = matches.get_flag("simple");
+
+    // Get where to record the pressed keys to, or replay them from
+    let record = matches.get_one::<String>("record").map(PathBuf::from);
+    let replay = matches.get_one::<String>("replay").map(PathBuf::from);
+
+    // Check if details should be logged
+    let verbose = matches.get_flag("verbose");
+
+    // Get the path Ctrl+F deep-links to
+    let path = matches.get_one::<String>("path").cloned();
+
+    // Check if local clones should be looked for
+    let check_local = matches.get_flag("check-local");
+
+    // Get the sources which must not come back empty
+    let require_nonempty = matches
+        .get_many::<String>("require-nonempty")
+        .unwrap_or_default()
+        .map(|source| match source.as_str() {
+            "gitlab" => RepoSource::GitLab,
+            _ => RepoSource::GitHub,
+        })
+        .collect();
+
+    // Get the User-Agent identifying the API requests
+    let user_agent = matches
+        .get_one::<String>("user-agent")
+        .cloned()
+        .unwrap_or_else(user_agent::default_user_agent);
 
     AppArgs {
         use_dummy,
         github_token,
         gitlab_token,
+        github_host,
+        gitlab_base_url,
         force_download,
+        cache_expiry,
+        exclude_languages,
+        exclude_topics,
+        highlight_new,
+        validate_cache,
+        clear_cache,
+        clone_dir,
+        git_tui,
+        multiplexer,
+        clone_command,
+        sort,
+        activity_weights,
+        group_by,
+        category_order,
+        format_options,
+        display_template,
+        strip_common_prefix,
+        cycle,
+        incremental,
+        refresh_identity,
+        post_action_delay_ms,
+        repo,
+        source,
+        json,
+        json_fields,
+        confirm_quit_when_busy,
+        csv,
+        show_aliases,
+        show_owner_source,
+        show_diff,
+        gitlab_languages,
+        max_concurrency,
+        include_gists,
+        include_starred,
+        hide_archived,
+        dedup,
+        orgs,
+        gitlab_groups,
+        max_description_length,
+        token_expiry_warning_days,
+        min_score,
+        quick_filters,
+        ellipsis,
+        simple,
+        record,
+        replay,
+        verbose,
+        path,
+        check_local,
+        user_agent,
+        require_nonempty,
+        basket,
+        clear_basket,
+        query,
+        restore_query,
+        multi,
+        print,
+        output_json,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_of(args: &[&str]) -> String {
+        let matches = command().try_get_matches_from(args).unwrap();
+        initial_query(&matches)
+    }
+
+    #[test]
+    fn test_positional_query() {
+        assert_eq!(query_of(&["repo-url-picker", "--dummy"]), "");
+        assert_eq!(query_of(&["repo-url-picker", "rust"]), "rust");
+
+        // A quoted query and separate words end up the same
+        assert_eq!(query_of(&["repo-url-picker", "rust web"]), "rust web");
+        assert_eq!(query_of(&["repo-url-picker", "rust", "web"]), "rust web");
+
+        // Flags are still parsed around the query
+        let matches = command()
+            .try_get_matches_from(["repo-url-picker", "rust", "--dummy", "--sort", "created"])
+            .unwrap();
+        assert_eq!(initial_query(&matches), "rust");
+        assert!(matches.get_flag("dummy"));
+        assert_eq!(matches.get_one::<String>("sort").map(String::as_str), Some("created"));
+    }
+
+    #[test]
+    fn test_query_option() {
+        assert_eq!(query_of(&["repo-url-picker", "--query", "rust web"]), "rust web");
+        assert_eq!(query_of(&["repo-url-picker", "--query=", "--dummy"]), "");
+
+        // Only one way of passing the query at a time
+        assert!(command().try_get_matches_from(["repo-url-picker", "--query", "rust", "web"]).is_err());
+
+        // Printing the matches needs no terminal, so it cannot be combined with a prompt
+        assert!(command().try_get_matches_from(["repo-url-picker", "--print", "--query", "rust"]).is_ok());
+        assert!(command().try_get_matches_from(["repo-url-picker", "--print", "--simple"]).is_err());
+        assert!(command().try_get_matches_from(["repo-url-picker", "--output", "json", "--dummy"]).is_ok());
+        assert!(command().try_get_matches_from(["repo-url-picker", "--output", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let source_of = |args: &[&str]| {
+            let matches = command().try_get_matches_from(args).unwrap();
+            matches.get_one::<String>("source").cloned()
+        };
+
+        // Both sources are listed unless one is picked, with or without --repo
+        assert_eq!(source_of(&["repo-url-picker"]).as_deref(), Some("all"));
+        assert_eq!(source_of(&["repo-url-picker", "--source", "gitlab"]).as_deref(), Some("gitlab"));
+        assert!(command().try_get_matches_from(["repo-url-picker", "--source", "bitbucket"]).is_err());
+    }
+
+    #[test]
+    fn test_no_force_download_overrides_the_config() {
+        let force_download = |args: &[&str]| {
+            let matches = command().try_get_matches_from(args).unwrap();
+            args_from(matches, config::Config { force_download: Some(true), ..Default::default() }).force_download
+        };
+
+        assert!(force_download(&["repo-url-picker", "--dummy"]));
+        assert!(!force_download(&["repo-url-picker", "--dummy", "--no-force-download"]));
+        assert!(command().try_get_matches_from(["repo-url-picker", "-f", "--no-force-download"]).is_err());
+    }
+
+    #[test]
+    fn test_token_expiry_warning_days_range() {
+        assert!(command().try_get_matches_from(["repo-url-picker", "--token-expiry-warning-days", "0"]).is_ok());
+        // Larger windows would overflow the duration they are turned into
+        assert!(command().try_get_matches_from(["repo-url-picker", "--token-expiry-warning-days", "99999999999999999"]).is_err());
     }
 }
@@ -0,0 +1,112 @@
+//! Copying text like a repository's SSH URL to the system clipboard
+//!
+//! The text is piped into the platform's clipboard tool: `pbcopy` on macOS, `clip.exe`
+//! on Windows, and on Linux `wl-copy` in Wayland sessions or `xclip` otherwise.
+
+use std::io::{self, Write};
+use std::process::{self, Stdio};
+
+/// A program and its arguments, which puts its standard input on the clipboard
+pub type ClipboardTool = (&'static str, &'static [&'static str]);
+
+const WL_COPY: ClipboardTool = ("wl-copy", &[]);
+const XCLIP: ClipboardTool = ("xclip", &["-selection", "clipboard"]);
+
+/// Returns whether this is a Wayland session, where X11 tools may not reach the clipboard
+pub fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty())
+}
+
+/// Returns the clipboard tools of an operating system like `std::env::consts::OS`, in the
+/// order they are tried. Wayland sessions try `wl-copy` before falling back to `xclip`,
+/// which works through XWayland.
+pub fn clipboard_tools(os: &str, wayland: bool) -> Vec<ClipboardTool> {
+    match os {
+        "macos" => vec![("pbcopy", &[])],
+        "windows" => vec![("clip.exe", &[])],
+        "linux" if wayland => vec![WL_COPY, XCLIP],
+        "linux" => vec![XCLIP],
+        _ => Vec::new(),
+    }
+}
+
+/// Runs a program with the text as its standard input
+fn pipe_to(program: &str, args: &[&str], text: &str) -> io::Result<()> {
+    let mut child = process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // The tool only finishes once its input is closed, which dropping stdin does
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("exited with {}", status)));
+    }
+    Ok(())
+}
+
+/// Copies text to the clipboard, returning a message saying what was copied or why it was not.
+///
+/// A missing tool falls through to the next one, so the error lists all of them.
+pub fn copy_to_clipboard(text: &str) -> Result<String, String> {
+    let tools = clipboard_tools(std::env::consts::OS, is_wayland());
+    if tools.is_empty() {
+        return Err("Copying to the clipboard is not supported on this platform".to_string());
+    }
+
+    for (program, args) in &tools {
+        match pipe_to(program, args, text) {
+            Ok(()) => return Ok(format!("Copied to clipboard: {}", text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to run {}: {}", program, e)),
+        }
+    }
+
+    let programs: Vec<&str> = tools.iter().map(|(program, _)| *program).collect();
+    Err(format!("No clipboard tool found, install {}", programs.join(" or ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_windows_uses_clip() {
+        assert_eq!(clipboard_tools(std::env::consts::OS, false), vec![("clip.exe", &[][..])]);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_macos_uses_pbcopy() {
+        assert_eq!(clipboard_tools(std::env::consts::OS, false), vec![("pbcopy", &[][..])]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_linux_uses_xclip() {
+        assert_eq!(clipboard_tools(std::env::consts::OS, false), vec![XCLIP]);
+    }
+
+    #[test]
+    fn test_wayland_tries_wl_copy_first() {
+        assert_eq!(clipboard_tools("linux", true), vec![WL_COPY, XCLIP]);
+        assert_eq!(clipboard_tools("linux", false), vec![XCLIP]);
+
+        // Only Linux has a display server to choose by
+        assert_eq!(clipboard_tools("macos", true), clipboard_tools("macos", false));
+        assert!(clipboard_tools("plan9", false).is_empty());
+    }
+
+    #[test]
+    fn test_missing_tool_is_an_error() {
+        let error = pipe_to("repo-searcher-no-such-clipboard-tool", &[], "git@github.com:user/notes.git").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,78 @@
+//! Bounded concurrency for batches of per-repository API requests
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Runs `f` for every item with at most `max_concurrency` running at once.
+///
+/// Results are returned in the order of the items. A limit of 0 is treated as 1.
+pub async fn map_bounded<T, R, F, Fut>(items: Vec<T>, max_concurrency: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(max_concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    let mut results: Vec<Option<R>> = (0..items.len()).map(|_| None).collect();
+
+    for (index, item) in items.into_iter().enumerate() {
+        // Wait for a free slot before starting the next request
+        let permit = semaphore.clone().acquire_owned().await.expect("semaphore is never closed");
+        let future = f(item);
+
+        tasks.spawn(async move {
+            let result = future.await;
+            drop(permit);
+            (index, result)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined.expect("bounded task panicked");
+        results[index] = Some(result);
+    }
+
+    results.into_iter().map(|result| result.expect("every task finished")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_never_more_than_limit_in_flight() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let results = map_bounded((0..8).collect(), 2, |i: u64| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            async move {
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now_running, Ordering::SeqCst);
+
+                // Finish in a different order than started
+                tokio::time::sleep(Duration::from_millis(5 + (8 - i) * 2)).await;
+
+                running.fetch_sub(1, Ordering::SeqCst);
+                i * 10
+            }
+        })
+        .await;
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(results, vec![0, 10, 20, 30, 40, 50, 60, 70]);
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit_still_runs() {
+        let results = map_bounded(vec!["a", "b"], 0, |s: &str| async move { s.len() }).await;
+        assert_eq!(results, vec![1, 1]);
+    }
+}
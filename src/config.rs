@@ -0,0 +1,99 @@
+//! Defaults read from a TOML config file, so tokens and options need not be passed every run
+//!
+//! The file lives at `$XDG_CONFIG_HOME/github-repo-searcher/config.toml`, or under
+//! `~/.config` without `XDG_CONFIG_HOME`. Flags given on the command line win over it.
+
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CONFIG_DIR: &str = "github-repo-searcher";
+const CONFIG_FILE: &str = "config.toml";
+
+/// The settings of the config file, all of them optional
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub github_token: Option<String>,
+    pub gitlab_token: Option<String>,
+    pub github_host: Option<String>,
+    pub gitlab_host: Option<String>,
+    pub cache_expiry_minutes: Option<u64>,
+    pub force_download: Option<bool>,
+    pub no_emoji: Option<bool>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Loads a config file, a missing file is an empty config
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Returns where the config file is read from, or None without a home directory
+pub fn config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join(CONFIG_DIR).join(CONFIG_FILE))
+}
+
+/// Loads the config file, which is empty if it does not exist
+pub fn load_config() -> Result<Config, String> {
+    match config_path() {
+        Some(path) => Config::load_from(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            # Tokens are used unless given with --github-token or --gitlab-token
+            github_token = "ghp_example"
+            cache_expiry_minutes = 120
+            force_download = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                github_token: Some("ghp_example".to_string()),
+                gitlab_token: None,
+                github_host: None,
+                gitlab_host: None,
+                cache_expiry_minutes: Some(120),
+                force_download: Some(true),
+                no_emoji: None,
+            }
+        );
+
+        // Typos are reported instead of silently ignored
+        assert!(Config::parse("github_tokn = \"ghp_example\"").unwrap_err().contains("github_tokn"));
+        assert!(Config::parse("cache_expiry_minutes = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn test_missing_config_is_empty() {
+        let path = std::env::temp_dir().join("repo-searcher-test-missing-config.toml");
+        assert_eq!(Config::load_from(&path), Ok(Config::default()));
+    }
+}
@@ -0,0 +1,112 @@
+//! Collapsing mirrors, repositories with the same name on both GitHub and GitLab, set with `--dedup`
+//!
+//! Of two mirrors the one listed first is kept and marked as mirrored. GitHub is fetched
+//! before GitLab, so that is usually the GitHub one. Gists and snippets are never collapsed.
+
+use crate::cache::RepoData;
+use crate::formatter::RepoSource;
+use std::collections::HashMap;
+
+/// Returns the name mirrors are matched by, lowercased and without a `.git` suffix
+pub fn mirror_key(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.strip_suffix(".git") {
+        Some(stripped) => stripped.to_string(),
+        None => name,
+    }
+}
+
+/// Removes the batch repositories mirroring one listed before them, in `kept` or earlier in
+/// the batch, and marks the one listed before as mirrored.
+///
+/// The kept repositories are not collapsed among themselves, as they already were.
+pub fn collapse_mirrors(kept: &mut [RepoData], batch: &mut Vec<RepoData>) {
+    // The first repository of each source by mirror key, as its index in `kept` followed by `batch`
+    let mut firsts: HashMap<String, Vec<(RepoSource, usize)>> = HashMap::new();
+    let mut originals = Vec::new();
+    let mut collapsed = vec![false; batch.len()];
+
+    for (index, repo) in kept.iter().chain(batch.iter()).enumerate() {
+        if repo.is_gist() {
+            continue;
+        }
+
+        let sources = firsts.entry(mirror_key(&repo.name)).or_default();
+        if let Some(batch_index) = index.checked_sub(kept.len()) {
+            if let Some(&(_, original)) = sources.iter().find(|(source, _)| *source != repo.source) {
+                originals.push(original);
+                collapsed[batch_index] = true;
+                continue;
+            }
+        }
+        if !sources.iter().any(|(source, _)| *source == repo.source) {
+            sources.push((repo.source, index));
+        }
+    }
+
+    for index in originals {
+        match index.checked_sub(kept.len()) {
+            Some(batch_index) => batch[batch_index].mirrored = true,
+            None => kept[index].mirrored = true,
+        }
+    }
+
+    let mut collapsed = collapsed.into_iter();
+    batch.retain(|_| !collapsed.next().unwrap_or(false));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::RepoKind;
+
+    fn repo(name: &str, source: RepoSource) -> RepoData {
+        RepoData {
+            url: format!("git@{:?}:user/{}.git", source, name).to_lowercase(),
+            source,
+            ..RepoData::named(name)
+        }
+    }
+
+    fn names_and_sources(repos: &[RepoData]) -> Vec<(&str, RepoSource, bool)> {
+        repos.iter().map(|repo| (repo.name.as_str(), repo.source, repo.mirrored)).collect()
+    }
+
+    #[test]
+    fn test_mirror_key() {
+        assert_eq!(mirror_key("Rust-Web.git"), "rust-web");
+        assert_eq!(mirror_key("rust-web"), "rust-web");
+        assert_eq!(mirror_key(".git"), "");
+    }
+
+    #[test]
+    fn test_collapse_mirrors_keeps_the_first() {
+        let mut repos = vec![
+            repo("Notes", RepoSource::GitHub),
+            repo("api", RepoSource::GitHub),
+            repo("notes", RepoSource::GitLab),
+            RepoData { kind: RepoKind::Gist, ..repo("notes", RepoSource::GitHub) },
+        ];
+        collapse_mirrors(&mut [], &mut repos);
+
+        assert_eq!(
+            names_and_sources(&repos),
+            vec![("Notes", RepoSource::GitHub, true), ("api", RepoSource::GitHub, false), ("notes", RepoSource::GitHub, false)]
+        );
+
+        // Same names on one platform, like in two organizations, are not mirrors
+        let mut repos = vec![repo("api", RepoSource::GitLab), repo("api", RepoSource::GitLab)];
+        collapse_mirrors(&mut [], &mut repos);
+        assert_eq!(repos.len(), 2);
+    }
+
+    #[test]
+    fn test_collapse_batch_into_kept() {
+        let mut kept = vec![repo("api", RepoSource::GitHub)];
+        let mut batch = vec![repo("docs", RepoSource::GitLab), repo("api", RepoSource::GitLab)];
+        collapse_mirrors(&mut kept, &mut batch);
+
+        assert_eq!(names_and_sources(&kept), vec![("api", RepoSource::GitHub, true)]);
+        assert_eq!(names_and_sources(&batch), vec![("docs", RepoSource::GitLab, false)]);
+    }
+}
@@ -0,0 +1,193 @@
+//! Exporting the repository list for use in other programs
+
+use crate::cache::{RepoData, RepoKind};
+use crate::formatter::RepoSource;
+use std::fs;
+use std::io;
+
+/// Column names of the CSV export, in order
+const CSV_HEADER: &str = "name,owner,source,url,description,private,fork";
+
+/// Fields of a repository in its JSON output, which `--json-fields` can select
+pub const JSON_FIELDS: &[&str] = &[
+    "name",
+    "url",
+    "description",
+    "owner",
+    "is_fork",
+    "is_private",
+    "source",
+    "kind",
+    "language",
+    "topics",
+    "updated_at",
+    "created_at",
+    "is_archived",
+    "stars",
+    "open_issues",
+    "default_branch",
+    "is_starred",
+    "pushed_at",
+];
+
+/// Parses comma-separated JSON field names like `name,url`, rejecting unknown ones
+pub fn parse_json_fields(value: &str) -> Result<Vec<String>, String> {
+    let mut fields: Vec<String> = Vec::new();
+
+    for field in value.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+        if !JSON_FIELDS.contains(&field) {
+            return Err(format!("unknown field '{}', expected some of {}", field, JSON_FIELDS.join(", ")));
+        }
+        if !fields.iter().any(|f| f == field) {
+            fields.push(field.to_string());
+        }
+    }
+
+    if fields.is_empty() {
+        return Err("at least one field is needed".to_string());
+    }
+    Ok(fields)
+}
+
+/// Projects a repository onto the given JSON fields, leaving out all others
+pub fn project_json(repo: &RepoData, fields: &[String]) -> serde_json::Map<String, serde_json::Value> {
+    let serde_json::Value::Object(mut all) = serde_json::to_value(repo).unwrap_or_default() else {
+        return serde_json::Map::new();
+    };
+
+    fields
+        .iter()
+        .filter_map(|field| all.remove(field).map(|value| (field.clone(), value)))
+        .collect()
+}
+
+/// Quotes a CSV field if it contains a comma, quote or line break, doubling any quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Serializes a repository as one CSV row, without the line ending
+pub fn csv_row(repo: &RepoData) -> String {
+    let source = match (repo.source, repo.kind) {
+        (RepoSource::GitHub, RepoKind::Repository) => "github",
+        (RepoSource::GitLab, RepoKind::Repository) => "gitlab",
+        (RepoSource::GitHub, RepoKind::Gist) => "gist",
+        (RepoSource::GitLab, RepoKind::Gist) => "snippet",
+    };
+
+    [
+        repo.name.as_str(),
+        repo.owner.as_str(),
+        source,
+        repo.url.as_str(),
+        repo.description.as_str(),
+        if repo.is_private { "true" } else { "false" },
+        if repo.is_fork { "true" } else { "false" },
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Serializes repositories as CSV with a header row
+pub fn to_csv(repos: &[RepoData]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+
+    for repo in repos {
+        csv.push_str(&csv_row(repo));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Serializes repositories as a JSON array with all of their fields
+pub fn to_json(repos: &[RepoData]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(repos)
+}
+
+/// Writes repositories as CSV to a file
+pub fn write_csv_file(path: &str, repos: &[RepoData]) -> io::Result<()> {
+    fs::write(path, to_csv(repos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str, description: &str) -> RepoData {
+        RepoData {
+            description: description.to_string(),
+            is_private: true,
+            ..RepoData::named(name)
+        }
+    }
+
+    #[test]
+    fn test_csv_row_quoting() {
+        assert_eq!(
+            csv_row(&repo("plain", "A simple tool")),
+            "plain,user,github,git@github.com:user/plain.git,A simple tool,true,false"
+        );
+        assert_eq!(
+            csv_row(&repo("comma", "Fast, small")),
+            "comma,user,github,git@github.com:user/comma.git,\"Fast, small\",true,false"
+        );
+        assert_eq!(
+            csv_row(&repo("quote", "The \"best\" tool")),
+            "quote,user,github,git@github.com:user/quote.git,\"The \"\"best\"\" tool\",true,false"
+        );
+        assert_eq!(
+            csv_row(&repo("newline", "First line\nSecond line")),
+            "newline,user,github,git@github.com:user/newline.git,\"First line\nSecond line\",true,false"
+        );
+    }
+
+    #[test]
+    fn test_json_projection() {
+        let fields = parse_json_fields("name, url,name").unwrap();
+        let projected = project_json(&repo("rust-web", "A web server"), &fields);
+
+        let keys: Vec<&str> = projected.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["name", "url"]);
+        assert_eq!(projected["name"], "rust-web");
+
+        // Every field can be selected, and selecting all of them gives the full output
+        let all = parse_json_fields(&JSON_FIELDS.join(",")).unwrap();
+        let full = serde_json::to_value(repo("rust-web", "")).unwrap();
+        assert_eq!(serde_json::Value::Object(project_json(&repo("rust-web", ""), &all)), full);
+    }
+
+    #[test]
+    fn test_unknown_json_fields_rejected() {
+        assert!(parse_json_fields("name,stargazers").unwrap_err().contains("unknown field 'stargazers'"));
+        assert!(parse_json_fields(" , ").is_err());
+    }
+
+    #[test]
+    fn test_to_json_is_an_array() {
+        let json = to_json(&[repo("a", ""), repo("b", "Fast, small")]).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value.as_array().map(Vec::len), Some(2));
+        assert_eq!(value[1]["description"], "Fast, small");
+        assert_eq!(value[1]["source"], serde_json::to_value(RepoSource::GitHub).unwrap());
+        assert_eq!(to_json(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_to_csv_has_header() {
+        let csv = to_csv(&[repo("a", ""), repo("b", "")]);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].starts_with("b,"));
+    }
+}
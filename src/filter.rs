@@ -1,3 +1,218 @@
+use crate::cache::RepoData;
+use chrono::{Months, NaiveDate};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Score of every matched character
+const MATCH_SCORE: i64 = 1;
+/// Extra score of a matched character right after the previous one
+const CONTIGUOUS_BONUS: i64 = 5;
+/// Extra score of a matched character starting a word, like after `-` or a space
+const WORD_START_BONUS: i64 = 8;
+/// Most score lost by a match starting late in the item
+const MAX_START_PENALTY: i64 = 20;
+
+/// Check if a repository's language or any of its topics is in the exclusion lists.
+///
+/// Comparisons are case insensitive. Repositories without a language are never
+/// excluded by language.
+pub fn is_excluded(repo: &RepoData, exclude_languages: &[String], exclude_topics: &[String]) -> bool {
+    if let Some(language) = &repo.language {
+        if exclude_languages.iter().any(|l| l.eq_ignore_ascii_case(language)) {
+            return true;
+        }
+    }
+
+    repo.topics
+        .iter()
+        .any(|topic| exclude_topics.iter().any(|t| t.eq_ignore_ascii_case(topic)))
+}
+
+/// Splits a query into lowercase parts, with exclusions sorted first.
+fn parse_query(query: &str) -> Vec<String> {
+    let mut parts: Vec<String> = query
+        .trim()
+        .to_lowercase()
+        .split(' ')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_string())
+        .collect();
+
+    // Sort query parts to handle exclusions first
+    parts.sort_by(|a, b| {
+        if a.starts_with('-') && !b.starts_with('-') {
+            std::cmp::Ordering::Less
+        } else if !a.starts_with('-') && b.starts_with('-') {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    parts
+}
+
+/// Check if a query has no terms, so every item matches it.
+pub fn is_empty_query(query: &str) -> bool {
+    query.trim().split(' ').all(|part| part.is_empty())
+}
+
+/// Check if a query part excludes items instead of including them.
+fn is_exclusion(query_part: &str) -> bool {
+    // Check length, so a single minus is still matched
+    query_part.len() >= 2 && query_part.starts_with('-')
+}
+
+/// Check if all characters of the needle appear in the haystack in the same order.
+///
+/// The characters do not have to be next to each other, so `gsrch` matches
+/// `github-repo-searcher`. Both are compared as they are, without changing case.
+pub fn fuzzy_subsequence(haystack: &str, needle: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+/// Check a lowercased item against the parsed query parts.
+///
+/// Include terms match as subsequences, while exclusions have to appear as they are
+/// typed, so `-go` does not hide everything with a `g` before an `o`.
+fn matches_parts(mapped: &str, query_parts: &[String]) -> bool {
+    for query_part in query_parts {
+        if is_exclusion(query_part) {
+            if mapped.contains(&query_part[1..]) {
+                return false;
+            }
+        } else if !fuzzy_subsequence(mapped, query_part) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Scores matching the needle greedily from `start`, passing each matched position on.
+///
+/// Returns None if the needle does not match from there.
+fn score_from(haystack: &[char], needle: &[char], start: usize, mut on_match: impl FnMut(usize)) -> Option<i64> {
+    let mut score = -(start as i64).min(MAX_START_PENALTY);
+    let mut previous: Option<usize> = None;
+    let mut matched = 0;
+
+    for pos in start..haystack.len() {
+        if matched == needle.len() {
+            break;
+        }
+        if haystack[pos] != needle[matched] {
+            continue;
+        }
+
+        score += MATCH_SCORE;
+        if previous.is_some_and(|previous| previous + 1 == pos) {
+            score += CONTIGUOUS_BONUS;
+        }
+        if pos == 0 || !haystack[pos - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+        on_match(pos);
+        previous = Some(pos);
+        matched += 1;
+    }
+
+    (matched == needle.len()).then_some(score)
+}
+
+/// Returns the score and start of the best way the needle matches the haystack as a
+/// subsequence, or None if it does not. The needle must not be empty.
+fn best_start(haystack: &[char], needle: &[char]) -> Option<(i64, usize)> {
+    let first = needle[0];
+    let mut best: Option<(i64, usize)> = None;
+
+    for start in (0..haystack.len()).filter(|&start| haystack[start] == first) {
+        // Starting later cannot match if starting here did not
+        let Some(score) = score_from(haystack, needle, start, |_| {}) else {
+            break;
+        };
+        if best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, start));
+        }
+    }
+    best
+}
+
+/// Scores the best way the needle matches the haystack as a subsequence, or None if it does not.
+///
+/// Matches starting earlier, running contiguously and starting words score higher.
+fn subsequence_score(haystack: &[char], needle: &[char]) -> Option<i64> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    best_start(haystack, needle).map(|(score, _)| score)
+}
+
+/// Returns the sorted character positions in an item matched by the include terms of a query.
+///
+/// Positions count characters, not bytes, so they stay aligned with emojis. Each term
+/// contributes the characters of its best scoring match, exclusions none.
+pub fn match_indices(item: &str, query: &str) -> Vec<usize> {
+    // Lowercase each character on its own, so positions cannot shift
+    let haystack: Vec<char> = item.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+    let mut positions = Vec::new();
+
+    for part in parse_query(query).iter().filter(|part| !is_exclusion(part)) {
+        let needle: Vec<char> = part.chars().collect();
+        if let Some((_, start)) = best_start(&haystack, &needle) {
+            score_from(&haystack, &needle, start, |pos| positions.push(pos));
+        }
+    }
+
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
+
+/// Scores an item against the parsed query parts, or None if it does not match them.
+fn score_item(mapped: &str, query_parts: &[String]) -> Option<i64> {
+    let mapped = mapped.to_lowercase();
+    if !matches_parts(&mapped, query_parts) {
+        return None;
+    }
+
+    let haystack: Vec<char> = mapped.chars().collect();
+    query_parts
+        .iter()
+        .filter(|part| !is_exclusion(part))
+        .map(|part| subsequence_score(&haystack, &part.chars().collect::<Vec<_>>()))
+        .sum()
+}
+
+/// Sorts scored items by descending score, keeping their order for equal scores.
+///
+/// Items scoring below `min_score` are dropped, unless it is 0 or the query has no
+/// include terms, which scores every item 0.
+fn rank<T>(mut scored: Vec<(T, i64)>, query_parts: &[String], min_score: i64) -> Vec<(T, i64)> {
+    if min_score > 0 && query_parts.iter().any(|part| !is_exclusion(part)) {
+        scored.retain(|&(_, score)| score >= min_score);
+    }
+    scored.sort_by_key(|&(_, score)| Reverse(score));
+    scored
+}
+
+/// Filter list by query case insensitively, ranking the matches by their score.
+///
+/// Earlier matches, contiguous runs and matches at word starts score higher.
+/// Exclusions work like in `filter_human` and hide items before they are scored.
+/// Matches scoring below `min_score` are dropped, 0 keeps all of them.
+pub fn filter_human_scored<T, F>(items: &[T], query: &str, min_score: i64, mapper: F) -> Vec<(T, i64)>
+where
+    T: Clone,
+    F: Fn(&T) -> String,
+{
+    let query_parts = parse_query(query);
+    let scored = items
+        .iter()
+        .filter_map(|item| score_item(&mapper(item), &query_parts).map(|score| (item.clone(), score)))
+        .collect();
+    rank(scored, &query_parts, min_score)
+}
+
 /// Filter list by query case insensitively.
 pub fn filter_human<T, F>(items: &[T], query: &str, mapper: F) -> Vec<T>
 where
@@ -8,63 +223,416 @@ where
         return Vec::new();
     }
 
-    let trimmed = query.trim();
-    if trimmed.is_empty() {
+    let query_parts = parse_query(query);
+    if query_parts.is_empty() {
         return items.to_vec();
     }
 
-    let mut result = Vec::new();
-    let query_parts: Vec<String> = trimmed
-        .to_lowercase()
+    items
+        .iter()
+        .filter(|item| matches_parts(&mapper(item).to_lowercase(), &query_parts))
+        .cloned()
+        .collect()
+}
+
+/// The fields of an item which `name:` and `desc:` terms are matched against
+pub struct SearchFields {
+    pub name: String,
+    pub description: String,
+}
+
+/// A field a query term can be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Description,
+}
+
+impl Field {
+    fn of(self, fields: &SearchFields) -> &str {
+        match self {
+            Field::Name => &fields.name,
+            Field::Description => &fields.description,
+        }
+    }
+}
+
+/// Returns the field a term like `name:api` is restricted to and the rest of the term.
+///
+/// The field is None for unprefixed terms, which match the name and description.
+fn scoped_term(term: &str) -> (Option<Field>, &str) {
+    let prefix = |prefix: &str| term.get(..prefix.len()).filter(|p| p.eq_ignore_ascii_case(prefix)).map(|_| &term[prefix.len()..]);
+
+    if let Some(rest) = prefix("name:") {
+        (Some(Field::Name), rest)
+    } else if let Some(rest) = prefix("desc:") {
+        (Some(Field::Description), rest)
+    } else {
+        (None, term)
+    }
+}
+
+/// Check lowercased fields against the parsed query parts, like `matches_parts` for each term's field
+fn matches_fields(fields: &SearchFields, query_parts: &[String]) -> bool {
+    let anywhere = format!("{} {}", fields.name, fields.description);
+
+    query_parts.iter().all(|query_part| {
+        let (exclude, term) = if is_exclusion(query_part) {
+            (true, &query_part[1..])
+        } else {
+            (false, query_part.as_str())
+        };
+        let (field, term) = scoped_term(term);
+        let haystack = field.map_or(anywhere.as_str(), |field| field.of(fields));
+
+        if exclude {
+            // A bare `-name:` excludes nothing, like a bare `-`
+            term.is_empty() || !haystack.contains(term)
+        } else {
+            fuzzy_subsequence(haystack, term)
+        }
+    })
+}
+
+/// Filter list by query case insensitively, where terms can be restricted to a field.
+///
+/// `name:api` only matches the name and `desc:api` only the description, also as
+/// exclusions like `-desc:deprecated`. Unprefixed terms match either field.
+pub fn filter_structured<T, F>(items: &[T], query: &str, mapper: F) -> Vec<T>
+where
+    T: Clone,
+    F: Fn(&T) -> SearchFields,
+{
+    let query_parts = parse_query(query);
+    if query_parts.is_empty() {
+        return items.to_vec();
+    }
+
+    items
+        .iter()
+        .filter(|item| {
+            let fields = mapper(item);
+            let fields = SearchFields {
+                name: fields.name.to_lowercase(),
+                description: fields.description.to_lowercase(),
+            };
+            matches_fields(&fields, &query_parts)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Splits `name:` and `desc:` terms off a query, also when they are exclusions.
+///
+/// Returns the remaining text query and the field terms, both joined with spaces.
+pub fn split_field_terms(query: &str) -> (String, String) {
+    let (field_parts, text_parts): (Vec<&str>, Vec<&str>) = query
         .split(' ')
         .filter(|part| !part.is_empty())
-        .map(|part| part.to_string())
-        .collect();
+        .partition(|part| scoped_term(part.strip_prefix('-').unwrap_or(part)).0.is_some());
 
-    // Sort query parts to handle exclusions first
-    let query_parts = {
-        let mut parts = query_parts;
-        parts.sort_by(|a, b| {
-            if a.starts_with('-') && !b.starts_with('-') {
-                std::cmp::Ordering::Less
-            } else if !a.starts_with('-') && b.starts_with('-') {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Equal
+    (text_parts.join(" "), field_parts.join(" "))
+}
+
+/// How a date term compares against its period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOp {
+    Before,
+    AtOrBefore,
+    After,
+    AtOrAfter,
+    Within,
+}
+
+/// A query term like `created:>2020` comparing a timestamp against a year, month or day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateFilter {
+    op: DateOp,
+    /// First second of the period
+    start: i64,
+    /// First second after the period
+    end: i64,
+}
+
+impl DateFilter {
+    /// Parses the value of a date term like `>2020`, `<=2019-06` or `2021-03-14`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (op, date) = [
+            (">=", DateOp::AtOrAfter),
+            ("<=", DateOp::AtOrBefore),
+            (">", DateOp::After),
+            ("<", DateOp::Before),
+            ("=", DateOp::Within),
+        ]
+        .iter()
+        .find_map(|(prefix, op)| value.strip_prefix(prefix).map(|date| (*op, date)))
+        .unwrap_or((DateOp::Within, value));
+
+        let parts: Vec<&str> = date.split('-').collect();
+        let number = |i: usize| parts.get(i).and_then(|part| part.parse::<u32>().ok());
+
+        let (start, end) = match parts.len() {
+            1 => {
+                let start = NaiveDate::from_ymd_opt(number(0)? as i32, 1, 1)?;
+                (start, start.checked_add_months(Months::new(12))?)
+            }
+            2 => {
+                let start = NaiveDate::from_ymd_opt(number(0)? as i32, number(1)?, 1)?;
+                (start, start.checked_add_months(Months::new(1))?)
+            }
+            3 => {
+                let start = NaiveDate::from_ymd_opt(number(0)? as i32, number(1)?, number(2)?)?;
+                (start, start.succ_opt()?)
+            }
+            _ => return None,
+        };
+
+        let timestamp = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|t| t.and_utc().timestamp());
+        Some(Self {
+            op,
+            start: timestamp(start)?,
+            end: timestamp(end)?,
+        })
+    }
+
+    /// Check a timestamp against the filter, missing timestamps never match
+    pub fn matches(&self, timestamp: Option<u64>) -> bool {
+        let Some(timestamp) = timestamp else {
+            return false;
+        };
+        let timestamp = timestamp as i64;
+
+        match self.op {
+            DateOp::Before => timestamp < self.start,
+            DateOp::AtOrBefore => timestamp < self.end,
+            DateOp::After => timestamp >= self.end,
+            DateOp::AtOrAfter => timestamp >= self.start,
+            DateOp::Within => timestamp >= self.start && timestamp < self.end,
+        }
+    }
+}
+
+/// Splits `created:` terms off a query.
+///
+/// Returns the remaining text query and the parsed creation date filters. Terms with
+/// an invalid date are kept in the text query.
+pub fn split_created_terms(query: &str) -> (String, Vec<DateFilter>) {
+    let mut text_parts = Vec::new();
+    let mut filters = Vec::new();
+
+    for part in query.split(' ').filter(|part| !part.is_empty()) {
+        let filter = part
+            .get(..8)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("created:"))
+            .and_then(|_| DateFilter::parse(&part[8..]));
+
+        match filter {
+            Some(filter) => filters.push(filter),
+            None => text_parts.push(part),
+        }
+    }
+
+    (text_parts.join(" "), filters)
+}
+
+/// Which repositories to show by their archived state, cycled with Ctrl+X
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchivedFilter {
+    #[default]
+    All,
+    Active,
+    Archived,
+}
+
+impl ArchivedFilter {
+    /// Returns the next mode in the cycle all, active, archived
+    pub fn next(self) -> Self {
+        match self {
+            ArchivedFilter::All => ArchivedFilter::Active,
+            ArchivedFilter::Active => ArchivedFilter::Archived,
+            ArchivedFilter::Archived => ArchivedFilter::All,
+        }
+    }
+
+    /// Check a repository's archived state against the mode
+    pub fn matches(self, is_archived: bool) -> bool {
+        match self {
+            ArchivedFilter::All => true,
+            ArchivedFilter::Active => !is_archived,
+            ArchivedFilter::Archived => is_archived,
+        }
+    }
+
+    /// Short description for the status line
+    pub fn label(self) -> &'static str {
+        match self {
+            ArchivedFilter::All => "active and archived",
+            ArchivedFilter::Active => "active only",
+            ArchivedFilter::Archived => "archived only",
+        }
+    }
+}
+
+/// Inverted index from characters to the ids of the items containing them.
+///
+/// Built once when the item list changes, so a keystroke only has to verify the
+/// items that contain every character of the include terms instead of scanning all
+/// of them. Characters instead of longer runs are indexed, since include terms match
+/// as subsequences whose characters need not be next to each other.
+pub struct FilterIndex {
+    postings: HashMap<char, Vec<usize>>,
+}
+
+impl FilterIndex {
+    pub fn new<T, F>(items: &[T], mapper: F) -> Self
+    where
+        F: Fn(&T) -> String,
+    {
+        let mut postings: HashMap<char, Vec<usize>> = HashMap::new();
+
+        for (id, item) in items.iter().enumerate() {
+            for c in mapper(item).to_lowercase().chars() {
+                let ids = postings.entry(c).or_default();
+                // Ids are added in order, so only the last one can be a duplicate
+                if ids.last() != Some(&id) {
+                    ids.push(id);
+                }
             }
-        });
-        parts
-    };
-
-    for item in items {
-        let mapped = mapper(item).to_lowercase();
-        let mut pass = true;
-
-        for query_part in &query_parts {
-            // Check length, so a single minus is still matched
-            if query_part.len() >= 2 && query_part.starts_with('-') {
-                if mapped.contains(&query_part[1..]) {
-                    pass = false;
-                    break;
+        }
+
+        Self { postings }
+    }
+
+    /// Adds an item after the indexed ones, whose id has to be larger than all of theirs
+    pub fn push(&mut self, id: usize, text: &str) {
+        for c in text.to_lowercase().chars() {
+            let ids = self.postings.entry(c).or_default();
+            if ids.last() != Some(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    /// Returns the sorted ids of the items which can match all include terms,
+    /// or `None` if there are no include terms to narrow down the items.
+    fn candidates(&self, query_parts: &[String]) -> Option<Vec<usize>> {
+        let mut lists: Vec<&[usize]> = Vec::new();
+
+        for query_part in query_parts.iter().filter(|part| !is_exclusion(part)) {
+            for c in query_part.chars() {
+                match self.postings.get(&c) {
+                    Some(ids) => lists.push(ids),
+                    None => return Some(Vec::new()),
                 }
-            } else if !mapped.contains(query_part) {
-                pass = false;
-                break;
             }
         }
 
-        if pass {
-            result.push(item.clone());
+        // Intersect starting from the shortest list to keep the work small
+        lists.sort_by_key(|ids| ids.len());
+        let (first, rest) = lists.split_first()?;
+        let mut candidates = first.to_vec();
+        for ids in rest {
+            candidates.retain(|id| ids.binary_search(id).is_ok());
         }
+        Some(candidates)
+    }
+}
+
+/// Filter and rank a list by query, using an index built from the same items.
+///
+/// Returns the same result as `filter_human_scored`.
+pub fn filter_indexed_scored<T, F>(
+    items: &[T],
+    index: &FilterIndex,
+    query: &str,
+    min_score: i64,
+    mapper: F,
+) -> Vec<(T, i64)>
+where
+    T: Clone,
+    F: Fn(&T) -> String,
+{
+    let query_parts = parse_query(query);
+    if items.is_empty() || query_parts.is_empty() {
+        return filter_human_scored(items, query, min_score, mapper);
     }
 
-    result
+    match index.candidates(&query_parts) {
+        Some(candidates) => rank(
+            candidates
+                .into_iter()
+                .filter_map(|id| score_item(&mapper(&items[id]), &query_parts).map(|score| (items[id].clone(), score)))
+                .collect(),
+            &query_parts,
+            min_score,
+        ),
+        None => filter_human_scored(items, query, min_score, mapper),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn repo(name: &str, language: Option<&str>, topics: &[&str]) -> RepoData {
+        RepoData {
+            language: language.map(|l| l.to_string()),
+            topics: topics.iter().map(|t| t.to_string()).collect(),
+            ..RepoData::named(name)
+        }
+    }
+
+    fn mixed_repos() -> Vec<RepoData> {
+        vec![
+            repo("rust-cli", Some("Rust"), &["cli", "tools"]),
+            repo("py-scraper", Some("Python"), &["scraping"]),
+            repo("notes", None, &["docs"]),
+            repo("web-app", Some("TypeScript"), &["web", "frontend", "archived-ideas"]),
+        ]
+    }
+
+    fn remaining(languages: &[&str], topics: &[&str]) -> Vec<String> {
+        let languages: Vec<String> = languages.iter().map(|l| l.to_string()).collect();
+        let topics: Vec<String> = topics.iter().map(|t| t.to_string()).collect();
+        mixed_repos()
+            .into_iter()
+            .filter(|r| !is_excluded(r, &languages, &topics))
+            .map(|r| r.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_is_empty_query() {
+        assert!(is_empty_query(""));
+        assert!(is_empty_query("   "));
+        assert!(is_empty_query("\t"));
+        assert!(!is_empty_query(" rust "));
+        assert!(!is_empty_query("-"));
+    }
+
+    #[test]
+    fn test_exclude_by_language() {
+        assert_eq!(remaining(&["python"], &[]), vec!["rust-cli", "notes", "web-app"]);
+        assert_eq!(remaining(&["Rust", "TypeScript"], &[]), vec!["py-scraper", "notes"]);
+    }
+
+    #[test]
+    fn test_exclude_by_topic() {
+        // Any matching topic excludes a repository with multiple topics
+        assert_eq!(remaining(&[], &["frontend"]), vec!["rust-cli", "py-scraper", "notes"]);
+        assert_eq!(remaining(&[], &["TOOLS", "docs"]), vec!["py-scraper", "web-app"]);
+    }
+
+    #[test]
+    fn test_exclude_by_language_and_topic() {
+        assert_eq!(remaining(&["rust"], &["scraping"]), vec!["notes", "web-app"]);
+    }
+
+    #[test]
+    fn test_no_exclusions() {
+        assert_eq!(remaining(&[], &[]), vec!["rust-cli", "py-scraper", "notes", "web-app"]);
+    }
+
     #[test]
     fn test_empty_list() {
         let items: Vec<String> = vec![];
@@ -122,4 +690,305 @@ mod tests {
             "medical-medium-text-files (git@github.com:Dima-369/medical-medium-text-files.git)"
         ]);
     }
+
+    /// Small deterministic pseudo random generator, so the test needs no extra crates
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+
+        fn word(&mut self, alphabet: &[char], max_len: usize) -> String {
+            let len = 1 + self.next(max_len);
+            (0..len).map(|_| alphabet[self.next(alphabet.len())]).collect()
+        }
+    }
+
+    #[test]
+    fn test_indexed_filter_matches_brute_force() {
+        // A small alphabet makes partial trigram overlaps common
+        let alphabet = ['a', 'b', 'c', 'd', 'E', '-', ' '];
+        let mut rng = Lcg(42);
+
+        let items: Vec<String> = (0..500).map(|_| rng.word(&alphabet, 12)).collect();
+        let index = FilterIndex::new(&items, |s| s.clone());
+
+        for _ in 0..500 {
+            let parts = 1 + rng.next(3);
+            let query = (0..parts)
+                .map(|_| rng.word(&alphabet, 5))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            assert_eq!(
+                filter_indexed_scored(&items, &index, &query, 0, |s| s.clone()),
+                filter_human_scored(&items, &query, 0, |s| s.clone()),
+                "query: {:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_subsequence() {
+        assert!(fuzzy_subsequence("github-repo-searcher", "gsrch"));
+        assert!(fuzzy_subsequence("github-repo-searcher", "github-repo-searcher"));
+        assert!(fuzzy_subsequence("anything", ""));
+        assert!(!fuzzy_subsequence("", "a"));
+
+        // The characters have to appear in order
+        assert!(!fuzzy_subsequence("github-repo-searcher", "hcrsg"));
+        assert!(!fuzzy_subsequence("abc", "acb"));
+
+        // Every occurrence of a repeated character needs its own match
+        assert!(fuzzy_subsequence("banana", "nna"));
+        assert!(!fuzzy_subsequence("banana", "nnn"));
+    }
+
+    #[test]
+    fn test_fuzzy_include_terms() {
+        let items = vec!["github-repo-searcher", "gitlab-search", "rust-cli"];
+        assert_eq!(filter_human(&items, "gsrch", |s| s.to_string()), vec!["github-repo-searcher", "gitlab-search"]);
+        assert_eq!(filter_human(&items, "GHRS", |s| s.to_string()), vec!["github-repo-searcher"]);
+
+        // Interleaved characters out of order do not match
+        assert!(filter_human(&items, "hcrsg", |s| s.to_string()).is_empty());
+
+        // Exclusions still need the exact text, "-gsrch" would hide nothing
+        assert_eq!(filter_human(&items, "gsrch -gitlab", |s| s.to_string()), vec!["github-repo-searcher"]);
+        assert_eq!(filter_human(&items, "gsrch -gsrch", |s| s.to_string()).len(), 2);
+
+        let index = FilterIndex::new(&items, |s| s.to_string());
+        let matching = filter_indexed_scored(&items, &index, "gsrch", 0, |s| s.to_string());
+        assert_eq!(matching.len(), 2);
+    }
+
+    fn ranked(items: &[&str], query: &str) -> Vec<String> {
+        filter_human_scored(items, query, 0, |s| s.to_string())
+            .into_iter()
+            .map(|(item, _)| item.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_scored_ranking() {
+        // A contiguous run beats scattered characters
+        assert_eq!(ranked(&["wide-lab-tools", "my-web"], "web"), vec!["my-web", "wide-lab-tools"]);
+
+        // Starting a word beats matching inside one
+        assert_eq!(ranked(&["cobweb", "web-app"], "web"), vec!["web-app", "cobweb"]);
+
+        // Earlier matches beat later ones
+        assert_eq!(ranked(&["the-old-rust-notes", "rust-notes"], "rust"), vec!["rust-notes", "the-old-rust-notes"]);
+
+        // Equal scores keep their order, and an empty query keeps everything in order
+        assert_eq!(ranked(&["b-api", "a-api"], "api"), vec!["b-api", "a-api"]);
+        assert_eq!(ranked(&["b", "a"], ""), vec!["b", "a"]);
+        assert!(ranked(&[], "a").is_empty());
+
+        // Exclusions still hide items
+        assert_eq!(ranked(&["web-app", "web-demo"], "web -demo"), vec!["web-app"]);
+    }
+
+    #[test]
+    fn test_match_indices() {
+        assert_eq!(match_indices("rust-web-server", "web"), vec![5, 6, 7]);
+        assert_eq!(match_indices("Rust-Web", "RW"), vec![0, 5]);
+
+        // Every include term adds its characters, exclusions add none
+        assert_eq!(match_indices("rust-web", "web rust -go"), vec![0, 1, 2, 3, 5, 6, 7]);
+        assert!(match_indices("rust-web", "-rust").is_empty());
+        assert!(match_indices("rust-web", "xyz").is_empty());
+
+        // The best scoring match is highlighted, not the first one
+        assert_eq!(match_indices("cobweb-web", "web"), vec![7, 8, 9]);
+
+        // Positions count characters, so an emoji before the match does not shift it
+        assert_eq!(match_indices("🦀 crab (A crab) 🔧", "crab"), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_scored_terms_add_up() {
+        let scored = filter_human_scored(&["rust-tools-for-the-web", "rust-web"], "rust web", 0, |s| s.to_string());
+        let scores: Vec<i64> = scored.iter().map(|&(_, score)| score).collect();
+        assert_eq!(scored[0].0, "rust-web");
+        assert!(scores[0] > scores[1]);
+    }
+
+    #[test]
+    fn test_min_score_drops_weak_matches() {
+        let items = ["rust-web", "the-old-rust-notes", "wrapper-utils-set", "crumbs-of-static"];
+        let names = |query: &str, min_score: i64| -> Vec<&str> {
+            filter_human_scored(&items, query, min_score, |s| s.to_string())
+                .into_iter()
+                .map(|(item, _)| item)
+                .collect()
+        };
+        assert_eq!(names("rust", 0).len(), 4);
+
+        // Scattered characters are dropped, contiguous matches remain
+        assert_eq!(names("rust", 15), vec!["rust-web", "the-old-rust-notes"]);
+        assert!(names("rust", 1000).is_empty());
+
+        // Exclusions still hide items, and a query without include terms keeps everything
+        assert_eq!(names("rust -web", 0), vec!["the-old-rust-notes", "wrapper-utils-set", "crumbs-of-static"]);
+        assert_eq!(names("rust -web", 15), vec!["the-old-rust-notes"]);
+        assert_eq!(names("-web", 15).len(), 3);
+        assert_eq!(names("", 15).len(), 4);
+
+        let index = FilterIndex::new(&items, |s| s.to_string());
+        assert_eq!(filter_indexed_scored(&items, &index, "rust", 15, |s| s.to_string()).len(), 2);
+    }
+
+    #[test]
+    fn test_index_push_matches_rebuild() {
+        let items = vec!["apple pie", "banana split", "cherry pie"];
+        let mut index = FilterIndex::new(&items[..1], |s| s.to_string());
+        index.push(1, items[1]);
+        index.push(2, items[2]);
+
+        for query in ["pie", "an sp", "y -app", "zz"] {
+            assert_eq!(
+                filter_indexed_scored(&items, &index, query, 0, |s| s.to_string()),
+                filter_human_scored(&items, query, 0, |s| s.to_string()),
+                "query: {:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_indexed_filter_unknown_trigram() {
+        let items = vec!["apple pie", "banana split", "cherry pie"];
+        let index = FilterIndex::new(&items, |s| s.to_string());
+        assert!(filter_indexed_scored(&items, &index, "xyz", 0, |s| s.to_string()).is_empty());
+        let matching = filter_indexed_scored(&items, &index, "pie -che", 0, |s| s.to_string());
+        assert_eq!(matching.into_iter().map(|(item, _)| item).collect::<Vec<_>>(), vec!["apple pie"]);
+    }
+
+    fn timestamp(date: &str) -> Option<u64> {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        Some(date.and_hms_opt(12, 0, 0).unwrap().and_utc().timestamp() as u64)
+    }
+
+    #[test]
+    fn test_date_filter_comparisons() {
+        let after_2020 = DateFilter::parse(">2020").unwrap();
+        assert!(after_2020.matches(timestamp("2021-01-01")));
+        assert!(!after_2020.matches(timestamp("2020-12-31")));
+
+        let from_2020 = DateFilter::parse(">=2020").unwrap();
+        assert!(from_2020.matches(timestamp("2020-01-01")));
+        assert!(!from_2020.matches(timestamp("2019-12-31")));
+
+        let before_june = DateFilter::parse("<2019-06").unwrap();
+        assert!(before_june.matches(timestamp("2019-05-31")));
+        assert!(!before_june.matches(timestamp("2019-06-01")));
+
+        let through_june = DateFilter::parse("<=2019-06").unwrap();
+        assert!(through_june.matches(timestamp("2019-06-30")));
+        assert!(!through_june.matches(timestamp("2019-07-01")));
+
+        let on_day = DateFilter::parse("2021-03-14").unwrap();
+        assert!(on_day.matches(timestamp("2021-03-14")));
+        assert!(!on_day.matches(timestamp("2021-03-15")));
+
+        // Repositories without a creation date never match
+        assert!(!after_2020.matches(None));
+    }
+
+    #[test]
+    fn test_date_filter_invalid() {
+        assert_eq!(DateFilter::parse(">soon"), None);
+        assert_eq!(DateFilter::parse("2020-13"), None);
+        assert_eq!(DateFilter::parse("2020-02-30"), None);
+        assert_eq!(DateFilter::parse(""), None);
+    }
+
+    #[test]
+    fn test_split_created_terms() {
+        let (text, filters) = split_created_terms("rust Created:>2020 -old created:<2023");
+        assert_eq!(text, "rust -old");
+        assert_eq!(filters, vec![DateFilter::parse(">2020").unwrap(), DateFilter::parse("<2023").unwrap()]);
+
+        // Invalid dates stay part of the text query
+        let (text, filters) = split_created_terms("created:>later");
+        assert_eq!(text, "created:>later");
+        assert!(filters.is_empty());
+    }
+
+    fn fields(name: &str, description: &str) -> SearchFields {
+        SearchFields {
+            name: name.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_field_scoped_terms() {
+        let items = vec![("api-gateway", "Routes requests"), ("rust-cli", "Talks to the GitHub API"), ("notes", "")];
+        let names = |query: &str| -> Vec<&str> {
+            filter_structured(&items, query, |(name, description)| fields(name, description))
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+
+        // The description-only match is excluded when restricted to names
+        assert_eq!(names("name:api"), vec!["api-gateway"]);
+        assert_eq!(names("NAME:API"), vec!["api-gateway"]);
+        assert_eq!(names("desc:api"), vec!["rust-cli"]);
+
+        // Unprefixed terms still match anywhere
+        assert_eq!(names("api"), vec!["api-gateway", "rust-cli"]);
+        assert_eq!(names("api -desc:github"), vec!["api-gateway"]);
+        assert_eq!(names("-name:api"), vec!["rust-cli", "notes"]);
+
+        // A prefix without a term restricts nothing
+        assert_eq!(names("name:").len(), 3);
+        assert_eq!(names("-desc:").len(), 3);
+    }
+
+    #[test]
+    fn test_split_field_terms() {
+        assert_eq!(
+            split_field_terms("rust name:api -Desc:old created:2020 -go"),
+            ("rust created:2020 -go".to_string(), "name:api -Desc:old".to_string())
+        );
+        assert_eq!(split_field_terms("named:api"), ("named:api".to_string(), String::new()));
+    }
+
+    #[test]
+    fn test_archived_filter_cycle() {
+        let start = ArchivedFilter::default();
+        assert_eq!(start, ArchivedFilter::All);
+        assert_eq!(start.next(), ArchivedFilter::Active);
+        assert_eq!(start.next().next(), ArchivedFilter::Archived);
+        assert_eq!(start.next().next().next(), start);
+    }
+
+    #[test]
+    fn test_archived_filter_with_query() {
+        let mut repos = mixed_repos();
+        repos[0].is_archived = true;
+        repos[3].is_archived = true;
+
+        let names = |mode: ArchivedFilter, query: &str| -> Vec<String> {
+            let visible: Vec<&RepoData> = repos.iter().filter(|r| mode.matches(r.is_archived)).collect();
+            filter_human(&visible, query, |r| r.name.clone())
+                .into_iter()
+                .map(|r| r.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(ArchivedFilter::All, ""), vec!["rust-cli", "py-scraper", "notes", "web-app"]);
+        assert_eq!(names(ArchivedFilter::Active, ""), vec!["py-scraper", "notes"]);
+        assert_eq!(names(ArchivedFilter::Archived, ""), vec!["rust-cli", "web-app"]);
+
+        // The text query narrows each mode further
+        assert_eq!(names(ArchivedFilter::Archived, "-rust"), vec!["web-app"]);
+        assert!(names(ArchivedFilter::Active, "rust").is_empty());
+    }
 }
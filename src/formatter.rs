@@ -9,129 +9,818 @@
 //!
 //! - (fork) or (fork: description) - Fork of another repository
 //! - 🔒 - Private repository
+//! - 📝 - GitHub gist or GitLab snippet
+//! - ⭐ - Starred repository, at the end
+//!
+//! ## Category Indicators
+//!
+//! An emoji like 🌐 or 🧪 at the end, inferred from keywords (see the `category` module)
+//!
+//! With `--no-emoji` the markers are ASCII instead, like `[priv]` and `[web]`, for
+//! terminals which draw emoji at the wrong width or not at all.
 
+use crate::cache::RepoData;
 use serde::{Deserialize, Serialize};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Repository source (GitHub or GitLab)
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum RepoSource {
+    // Gists and snippets were sources of their own in older caches and baskets
+    #[serde(alias = "Gist")]
     GitHub,
+    #[serde(alias = "Snippet")]
     GitLab,
 }
 
-/// Formats a repository name with private status indicator and source
-pub fn format_repo_name(name: &str, _is_fork: bool, is_private: bool, source: RepoSource) -> String {
-    // Add source and private icons
-    let private_icon = if is_private { " 🔒" } else { "" };
-    let source_icon = match source {
-        RepoSource::GitHub => " [GH]",
-        RepoSource::GitLab => " [GL]",
+/// How repositories are marked in the list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Emoji markers like 🔒, or ASCII ones like `[priv]` with `--no-emoji`
+    pub emoji: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { emoji: true }
+    }
+}
+
+impl FormatOptions {
+    /// Returns the emoji or the ASCII form of a marker
+    fn marker(self, (emoji, ascii): (&'static str, &'static str)) -> &'static str {
+        if self.emoji { emoji } else { ascii }
+    }
+}
+
+/// Marker for private repositories, as emoji and as ASCII
+const PRIVATE_MARKER: (&str, &str) = (" 🔒", " [priv]");
+
+/// Marker for gists and snippets, shown before their source tag
+const GIST_MARKER: (&str, &str) = (" 📝", " [gist]");
+
+/// Badge for repositories starred rather than owned
+const STARRED_BADGE: (&str, &str) = (" ⭐", " [star]");
+
+/// Badge for archived repositories, which are read-only
+const ARCHIVED_BADGE: (&str, &str) = (" 📦", " [archived]");
+
+/// Badge for repositories collapsed with their mirror on the other platform by `--dedup`
+const MIRROR_BADGE: (&str, &str) = (" 🔁", " [mirror]");
+
+/// Badge for repositories which changed since the previous cache
+const NEW_BADGE: (&str, &str) = (" 🆕", " [new]");
+
+/// Icons of the languages GitHub and GitLab report, matched case insensitively
+const LANGUAGE_ICONS: &[(&str, &str)] = &[
+    ("rust", "🦀"),
+    ("python", "🐍"),
+    ("go", "🐹"),
+    ("java", "☕"),
+    ("kotlin", "🟪"),
+    ("javascript", "🟨"),
+    ("typescript", "🟦"),
+    ("ruby", "💎"),
+    ("php", "🐘"),
+    ("swift", "🐦"),
+    ("c", "🔧"),
+    ("c++", "➕"),
+    ("c#", "🎼"),
+    ("haskell", "🧮"),
+    ("clojure", "🌀"),
+    ("elixir", "💧"),
+    ("shell", "🐚"),
+    ("lua", "🌙"),
+    ("dart", "🎯"),
+    ("html", "📄"),
+    ("css", "🎨"),
+    ("nix", "🧊"),
+    ("emacs lisp", "🐃"),
+];
+
+/// Returns the icon of a repository's primary language, or None for languages without one
+pub fn language_icon(language: &str) -> Option<&'static str> {
+    LANGUAGE_ICONS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(language))
+        .map(|(_, icon)| *icon)
+}
+
+/// Appends the icon of a repository's primary language, nothing without a known language or emoji
+pub fn mark_language(formatted: &str, language: Option<&str>, options: FormatOptions) -> String {
+    match language.filter(|_| options.emoji).and_then(language_icon) {
+        Some(icon) => format!("{} {}", formatted, icon),
+        None => formatted.to_string(),
+    }
+}
+
+/// Appends the star count of a repository, repositories without stars get none
+pub fn mark_stars(formatted: &str, stars: Option<u64>, options: FormatOptions) -> String {
+    match stars.filter(|&stars| stars > 0) {
+        Some(stars) if options.emoji => format!("{} ★ {}", formatted, stars),
+        Some(stars) => format!("{} *{}", formatted, stars),
+        None => formatted.to_string(),
+    }
+}
+
+/// Appends the category marker of a repository, if it has one
+pub fn mark_category(formatted: &str, marker: Option<&str>) -> String {
+    match marker {
+        Some(marker) => format!("{} {}", formatted, marker),
+        None => formatted.to_string(),
+    }
+}
+
+/// Marks a formatted repository as starred
+pub fn mark_starred(formatted: &str, options: FormatOptions) -> String {
+    format!("{}{}", formatted, options.marker(STARRED_BADGE))
+}
+
+/// Marks a formatted repository as archived
+pub fn mark_archived(formatted: &str, options: FormatOptions) -> String {
+    format!("{}{}", formatted, options.marker(ARCHIVED_BADGE))
+}
+
+/// Marks a formatted repository as also being on the other platform
+pub fn mark_mirrored(formatted: &str, options: FormatOptions) -> String {
+    format!("{}{}", formatted, options.marker(MIRROR_BADGE))
+}
+
+/// Marks a formatted repository as new or changed since the last view
+pub fn mark_new(formatted: &str, options: FormatOptions) -> String {
+    format!("{}{}", formatted, options.marker(NEW_BADGE))
+}
+
+/// Returns the name prefix shared by all repositories, up to and including its last separator.
+///
+/// Cutting at a separator like `-` keeps whole words, so `medical-medium-text` and
+/// `medical-medium-tools` share `medical-medium-`. Returns an empty string for fewer
+/// than two names, or if the prefix would be a whole name.
+pub fn common_name_prefix<'a, I>(names: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut names = names.into_iter();
+    let Some(first) = names.next() else {
+        return String::new();
     };
 
-    format!("{}{}{}", name, private_icon, source_icon)
+    let mut prefix_len = first.len();
+    let mut shortest_len = first.len();
+    let mut count = 1;
+    for name in names {
+        count += 1;
+        shortest_len = shortest_len.min(name.len());
+        prefix_len = first
+            .char_indices()
+            .zip(name.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(prefix_len);
+    }
+
+    if count < 2 {
+        return String::new();
+    }
+
+    // Only cut after a separator, so no name is split mid-word
+    let prefix = &first[..prefix_len];
+    match prefix.rfind(['-', '_', '.', ' ']) {
+        Some(i) if i + 1 < shortest_len => prefix[..=i].to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Default marker for truncated and elided text
+pub const DEFAULT_ELLIPSIS: &str = "…";
+
+/// Widest ellipsis accepted by `--ellipsis`, so truncated items keep most of their text
+const MAX_ELLIPSIS_WIDTH: usize = 5;
+
+/// Validates an ellipsis, which has to be printable and 1 to 5 terminal cells wide
+pub fn parse_ellipsis(value: &str) -> Result<String, String> {
+    if value.chars().any(char::is_control) {
+        return Err("the ellipsis cannot contain control characters".to_string());
+    }
+
+    match value.width() {
+        1..=MAX_ELLIPSIS_WIDTH => Ok(value.to_string()),
+        width => Err(format!(
+            "the ellipsis has to be 1 to {} cells wide, not {}",
+            MAX_ELLIPSIS_WIDTH, width
+        )),
+    }
+}
+
+/// Keeps the longest part of the characters which fits into `max_width` cells
+fn take_width(chars: impl Iterator<Item = char>, max_width: usize) -> String {
+    let mut width = 0;
+    chars
+        .take_while(|c| {
+            width += c.width().unwrap_or(0);
+            width <= max_width
+        })
+        .collect()
 }
 
+/// Shortens text to at most `max_width` terminal cells, ending with the ellipsis if it was cut.
+///
+/// Also returns whether it was cut, which the length alone does not tell with a wide ellipsis.
+pub fn truncate_end(text: &str, max_width: usize, ellipsis: &str) -> (String, bool) {
+    if text.width() <= max_width {
+        return (text.to_string(), false);
+    }
 
+    let kept = take_width(text.chars(), max_width.saturating_sub(ellipsis.width()));
+    (format!("{}{}", kept, ellipsis), true)
+}
 
-/// Formats a complete repository display string with name and description
-pub fn format_repository(name: &str, description: &str, is_fork: bool, is_private: bool, source: RepoSource) -> String {
-    let formatted_name = format_repo_name(name, is_fork, is_private, source);
+/// Wraps the characters at the sorted `positions` in `on` and `off`, like color codes
+pub fn highlight_chars(text: &str, positions: &[usize], on: &str, off: &str) -> String {
+    let mut positions = positions.iter().peekable();
+    let mut highlighted = String::with_capacity(text.len());
 
-    if is_fork {
-        if description.is_empty() {
-            format!("{} (fork)", formatted_name)
+    for (i, c) in text.chars().enumerate() {
+        if positions.next_if_eq(&&i).is_some() {
+            highlighted.push_str(on);
+            highlighted.push(c);
+            highlighted.push_str(off);
         } else {
-            // Trim the description before formatting
-            let trimmed_description = description.trim();
-            format!("{} (fork: {})", formatted_name, trimmed_description)
+            highlighted.push(c);
         }
-    } else if description.is_empty() {
-        formatted_name
-    } else {
-        // Trim the description before formatting
-        let trimmed_description = description.trim();
-        format!("{} ({})", formatted_name, trimmed_description)
     }
+    highlighted
+}
+
+/// Shortens text to at most `max_width` terminal cells, keeping its end after the ellipsis
+pub fn truncate_start(text: &str, max_width: usize, ellipsis: &str) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+
+    let kept: String = take_width(text.chars().rev(), max_width.saturating_sub(ellipsis.width()))
+        .chars()
+        .rev()
+        .collect();
+    format!("{}{}", ellipsis, kept)
+}
+
+/// Returns the part of a query shown in `max_width` cells and the cursor column in it.
+///
+/// The query is shown from its start while the cursor fits, otherwise the text before
+/// the cursor is cut at its start so the cursor stays visible, with one cell left for it.
+pub fn query_window(query: &str, cursor: usize, max_width: usize, ellipsis: &str) -> (String, usize) {
+    let (before, after) = query.split_at(cursor);
+    if query.width() <= max_width || before.width() < max_width {
+        return (truncate_end(query, max_width, ellipsis).0, before.width());
+    }
+
+    let before = truncate_start(before, max_width.saturating_sub(1), ellipsis);
+    let column = before.width();
+    let (after, _) = truncate_end(after, max_width.saturating_sub(column), ellipsis);
+    (format!("{}{}", before, after), column)
+}
+
+/// Returns the width of the owner column in terminal cells, fitting the widest `owner/`
+pub fn owner_column_width<'a, I>(owners: I) -> usize
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    owners.into_iter().map(|owner| owner.width() + 1).max().unwrap_or(0)
+}
+
+/// Formats the `owner/ [GH] ` column prefix, padding the owner to the column width.
+///
+/// Padding goes by display width instead of characters, so owners with wide
+/// characters still line up.
+pub fn owner_source_prefix(owner: &str, source: RepoSource, owner_width: usize) -> String {
+    let owner = format!("{}/", owner);
+    let padding = owner_width.saturating_sub(owner.width());
+    let source_tag = match source {
+        RepoSource::GitHub => "[GH]",
+        RepoSource::GitLab => "[GL]",
+    };
+
+    format!("{}{} {} ", owner, " ".repeat(padding), source_tag)
+}
+
+/// Formats how many repositories of each platform match, like `GH 5/42 · GL 2/13`.
+///
+/// `counts` holds each platform with its matching and total number of repositories,
+/// platforms without any repositories are left out.
+pub fn source_match_counts(counts: &[(RepoSource, usize, usize)]) -> String {
+    counts
+        .iter()
+        .filter(|&&(_, _, total)| total > 0)
+        .map(|&(source, matching, total)| {
+            let label = match source {
+                RepoSource::GitHub => "GH",
+                RepoSource::GitLab => "GL",
+            };
+            format!("{} {}/{}", label, matching, total)
+        })
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// Shortens an age in seconds to its largest unit, like "5h", "3d", "2mo" or "1y"
+pub fn relative_age(seconds: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    match seconds {
+        s if s < MINUTE => "now".to_string(),
+        s if s < HOUR => format!("{}m", s / MINUTE),
+        s if s < DAY => format!("{}h", s / HOUR),
+        s if s < 30 * DAY => format!("{}d", s / DAY),
+        s if s < 365 * DAY => format!("{}mo", s / (30 * DAY)),
+        s => format!("{}y", s / (365 * DAY)),
+    }
+}
+
+/// Layout of a row in the list, used without `--format`
+pub const DEFAULT_TEMPLATE: &str = "{name} {private} {gist} [{source}] {details}";
+
+/// A repository field which a `{placeholder}` in a display template is replaced with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Owner,
+    Desc,
+    /// The description in parentheses, with "fork" before it for forks
+    Details,
+    Fork,
+    Private,
+    Gist,
+    Source,
+    Language,
+    Stars,
+    /// How long ago the repository was pushed to, like "3d"
+    Age,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("name", Field::Name),
+    ("owner", Field::Owner),
+    ("desc", Field::Desc),
+    ("details", Field::Details),
+    ("fork", Field::Fork),
+    ("private", Field::Private),
+    ("gist", Field::Gist),
+    ("source", Field::Source),
+    ("language", Field::Language),
+    ("stars", Field::Stars),
+    ("age", Field::Age),
+];
+
+impl Field {
+    /// Returns the text of the field, empty when a repository does not have it
+    fn value(self, repo: &RepoData, options: FormatOptions) -> String {
+        let description = repo.description.trim();
+        match self {
+            Field::Name => repo.name.clone(),
+            Field::Owner => repo.owner.clone(),
+            Field::Desc => description.to_string(),
+            Field::Details => match (repo.is_fork, description.is_empty()) {
+                (true, true) => "(fork)".to_string(),
+                (true, false) => format!("(fork: {})", description),
+                (false, true) => String::new(),
+                (false, false) => format!("({})", description),
+            },
+            Field::Fork if repo.is_fork => "fork".to_string(),
+            Field::Private if repo.is_private => options.marker(PRIVATE_MARKER).trim_start().to_string(),
+            Field::Gist if repo.is_gist() => options.marker(GIST_MARKER).trim_start().to_string(),
+            Field::Fork | Field::Private | Field::Gist => String::new(),
+            Field::Source => match repo.source {
+                RepoSource::GitHub => "GH".to_string(),
+                RepoSource::GitLab => "GL".to_string(),
+            },
+            Field::Language => repo.language.clone().unwrap_or_default(),
+            Field::Stars => repo.stars.map(|stars| stars.to_string()).unwrap_or_default(),
+            Field::Age => repo
+                .pushed_at
+                .map(|pushed_at| relative_age(crate::sort::now_secs().saturating_sub(pushed_at)))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// The layout of a row in the list, set with `--format` like `{name} {private} - {desc} [{source}]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayTemplate {
+    segments: Vec<Segment>,
+}
+
+impl Default for DisplayTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).expect("the default template is valid")
+    }
+}
+
+impl DisplayTemplate {
+    /// Returns whether this is the layout used without `--format`
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Parses a template, where unknown or unclosed placeholders are an error
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in '{}'", template))?;
+            let name = &rest[open + 1..open + close];
+            let field = FIELDS
+                .iter()
+                .find(|(field_name, _)| *field_name == name)
+                .map(|(_, field)| *field)
+                .ok_or_else(|| {
+                    let names: Vec<&str> = FIELDS.iter().map(|(name, _)| *name).collect();
+                    format!("unknown placeholder '{{{}}}', expected one of {}", name, names.join(", "))
+                })?;
+            segments.push(Segment::Field(field));
+            rest = &rest[open + close + 1..];
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Replaces the placeholders with the fields of a repository.
+    ///
+    /// A space before an empty field is dropped when a space or the end follows, so
+    /// `{name} {private} [{source}]` leaves no double space for public repositories.
+    pub fn render(&self, repo: &RepoData, options: FormatOptions) -> String {
+        let mut formatted = String::new();
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Literal(text) => formatted.push_str(text),
+                Segment::Field(field) => {
+                    let value = field.value(repo, options);
+                    if !value.is_empty() {
+                        formatted.push_str(&value);
+                        continue;
+                    }
+
+                    let space_follows = match self.segments.get(i + 1) {
+                        Some(Segment::Literal(text)) => text.starts_with(' '),
+                        Some(Segment::Field(_)) => false,
+                        None => true,
+                    };
+                    if space_follows && formatted.ends_with(' ') {
+                        formatted.pop();
+                    }
+                }
+            }
+        }
+
+        formatted.trim().to_string()
+    }
+}
+
+/// Formats a complete repository display string with the fields a template shows
+pub fn format_repository(repo: &RepoData, template: &DisplayTemplate, options: FormatOptions) -> String {
+    template.render(repo, options)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cache::RepoKind;
+
+    fn repo(name: &str, description: &str, is_fork: bool, is_private: bool, source: RepoSource) -> RepoData {
+        RepoData {
+            description: description.to_string(),
+            is_fork,
+            is_private,
+            source,
+            ..RepoData::named(name)
+        }
+    }
+
+    // The name with its markers, which the default template shows before the description
+    fn format_repo_name(name: &str, is_fork: bool, is_private: bool, source: RepoSource, options: FormatOptions) -> String {
+        let template = DisplayTemplate::parse("{name} {private} {gist} [{source}]").unwrap();
+        format_repository(&repo(name, "", is_fork, is_private, source), &template, options)
+    }
+
+    fn format_gist_name(name: &str, is_private: bool, source: RepoSource, options: FormatOptions) -> String {
+        let template = DisplayTemplate::parse("{name} {private} {gist} [{source}]").unwrap();
+        let gist = RepoData { kind: RepoKind::Gist, ..repo(name, "", false, is_private, source) };
+        format_repository(&gist, &template, options)
+    }
+
+    #[test]
+    fn test_mark_stars() {
+        assert_eq!(mark_stars("notes [GH]", Some(42), FormatOptions::default()), "notes [GH] ★ 42");
+        assert_eq!(mark_stars("notes [GH]", Some(0), FormatOptions::default()), "notes [GH]");
+        assert_eq!(mark_stars("notes [GL]", None, FormatOptions::default()), "notes [GL]");
+    }
+
+    #[test]
+    fn test_language_icons_are_double_width() {
+        // Narrow icons would misalign the columns after them
+        for (language, icon) in LANGUAGE_ICONS {
+            assert_eq!(icon.width(), 2, "icon of {}", language);
+        }
+    }
+
+    #[test]
+    fn test_mark_language() {
+        assert_eq!(mark_language("notes [GH]", Some("Rust"), FormatOptions::default()), "notes [GH] 🦀");
+        assert_eq!(mark_language("notes [GH]", Some("python"), FormatOptions::default()), "notes [GH] 🐍");
+
+        // GitLab projects may have no language, and unknown ones get no icon
+        assert_eq!(mark_language("notes [GL]", None, FormatOptions::default()), "notes [GL]");
+        assert_eq!(mark_language("notes [GH]", Some("COBOL"), FormatOptions::default()), "notes [GH]");
+    }
 
     #[test]
     fn test_format_repo_name() {
         // Regular repository (GitHub)
-        assert_eq!(format_repo_name("normal-repo", false, false, RepoSource::GitHub), "normal-repo [GH]");
+        assert_eq!(format_repo_name("normal-repo", false, false, RepoSource::GitHub, FormatOptions::default()), "normal-repo [GH]");
 
         // Regular repository (GitLab)
-        assert_eq!(format_repo_name("normal-repo", false, false, RepoSource::GitLab), "normal-repo [GL]");
+        assert_eq!(format_repo_name("normal-repo", false, false, RepoSource::GitLab, FormatOptions::default()), "normal-repo [GL]");
 
         // Forked repository - fork status is now handled in format_repository
-        assert_eq!(format_repo_name("forked-repo", true, false, RepoSource::GitHub), "forked-repo [GH]");
+        assert_eq!(format_repo_name("forked-repo", true, false, RepoSource::GitHub, FormatOptions::default()), "forked-repo [GH]");
 
         // Private repository
-        assert_eq!(format_repo_name("private-repo", false, true, RepoSource::GitHub), "private-repo 🔒 [GH]");
+        assert_eq!(format_repo_name("private-repo", false, true, RepoSource::GitHub, FormatOptions::default()), "private-repo 🔒 [GH]");
 
         // Both forked and private - fork status is now handled in format_repository
-        assert_eq!(format_repo_name("private-fork", true, true, RepoSource::GitLab), "private-fork 🔒 [GL]");
+        assert_eq!(format_repo_name("private-fork", true, true, RepoSource::GitLab, FormatOptions::default()), "private-fork 🔒 [GL]");
+
+        // Gists and snippets keep the tag of their platform
+        assert_eq!(format_gist_name("aa5a315d", false, RepoSource::GitHub, FormatOptions::default()), "aa5a315d 📝 [GH]");
+        assert_eq!(format_gist_name("2814", true, RepoSource::GitLab, FormatOptions::default()), "2814 🔒 📝 [GL]");
     }
 
+    #[test]
+    fn test_custom_template() {
+        let template = DisplayTemplate::parse("{name} {private} - {desc} [{source}]").unwrap();
+        let options = FormatOptions::default();
+
+        assert_eq!(
+            format_repository(&repo("notes", "My notes", false, true, RepoSource::GitHub), &template, options),
+            "notes 🔒 - My notes [GH]"
+        );
+        assert_eq!(
+            format_repository(&repo("notes", "My notes", false, false, RepoSource::GitLab), &template, options),
+            "notes - My notes [GL]"
+        );
 
+        let mut rust_cli = repo("rust-cli", "", true, false, RepoSource::GitHub);
+        rust_cli.language = Some("Rust".to_string());
+        rust_cli.stars = Some(42);
+        let template = DisplayTemplate::parse("{owner}/{name} {fork} {language} *{stars}").unwrap();
+        assert_eq!(format_repository(&rust_cli, &template, options), "user/rust-cli fork Rust *42");
+
+        // Only the default layout gets the badges appended
+        assert!(!template.is_default());
+        assert!(DisplayTemplate::parse(DEFAULT_TEMPLATE).unwrap().is_default());
+    }
+
+    #[test]
+    fn test_relative_age() {
+        let day = 24 * 60 * 60;
+        assert_eq!(relative_age(30), "now");
+        assert_eq!(relative_age(5 * 60 * 60), "5h");
+        assert_eq!(relative_age(3 * day + 60), "3d");
+        assert_eq!(relative_age(65 * day), "2mo");
+        assert_eq!(relative_age(400 * day), "1y");
+
+        // A repository without a push date leaves the placeholder empty
+        let template = DisplayTemplate::parse("{name} {age}").unwrap();
+        let notes = repo("notes", "", false, false, RepoSource::GitHub);
+        assert_eq!(format_repository(&notes, &template, FormatOptions::default()), "notes");
+    }
+
+    #[test]
+    fn test_parse_template_errors() {
+        assert!(DisplayTemplate::parse("{name} {title}").unwrap_err().contains("unknown placeholder '{title}'"));
+        assert!(DisplayTemplate::parse("{name").unwrap_err().contains("unclosed placeholder"));
+        assert_eq!(DisplayTemplate::parse("plain text").unwrap().render(&repo("a", "", false, false, RepoSource::GitHub), FormatOptions::default()), "plain text");
+    }
 
     #[test]
     fn test_format_repository() {
         // Repository with description (GitHub)
         assert_eq!(
-            format_repository("web-app", "Frontend application", false, false, RepoSource::GitHub),
+            format_repository(&repo("web-app", "Frontend application", false, false, RepoSource::GitHub), &DisplayTemplate::default(), FormatOptions::default()),
             "web-app [GH] (Frontend application)"
         );
 
         // Repository with description (GitLab)
         assert_eq!(
-            format_repository("web-app", "Frontend application", false, false, RepoSource::GitLab),
+            format_repository(&repo("web-app", "Frontend application", false, false, RepoSource::GitLab), &DisplayTemplate::default(), FormatOptions::default()),
             "web-app [GL] (Frontend application)"
         );
 
         // Repository with description and fork status
         assert_eq!(
-            format_repository("forked-api", "Backend service", true, false, RepoSource::GitHub),
+            format_repository(&repo("forked-api", "Backend service", true, false, RepoSource::GitHub), &DisplayTemplate::default(), FormatOptions::default()),
             "forked-api [GH] (fork: Backend service)"
         );
 
         // Repository with description and private status
         assert_eq!(
-            format_repository("mobile-app", "iOS client", false, true, RepoSource::GitHub),
+            format_repository(&repo("mobile-app", "iOS client", false, true, RepoSource::GitHub), &DisplayTemplate::default(), FormatOptions::default()),
             "mobile-app 🔒 [GH] (iOS client)"
         );
 
         // Repository with description, fork and private status
         assert_eq!(
-            format_repository("game-demo", "Unity project", true, true, RepoSource::GitLab),
+            format_repository(&repo("game-demo", "Unity project", true, true, RepoSource::GitLab), &DisplayTemplate::default(), FormatOptions::default()),
             "game-demo 🔒 [GL] (fork: Unity project)"
         );
 
         // Repository with no description
         assert_eq!(
-            format_repository("test-framework", "", false, false, RepoSource::GitHub),
+            format_repository(&repo("test-framework", "", false, false, RepoSource::GitHub), &DisplayTemplate::default(), FormatOptions::default()),
             "test-framework [GH]"
         );
 
         // Repository with no description but with fork and private status
         assert_eq!(
-            format_repository("private-fork", "", true, true, RepoSource::GitLab),
+            format_repository(&repo("private-fork", "", true, true, RepoSource::GitLab), &DisplayTemplate::default(), FormatOptions::default()),
             "private-fork 🔒 [GL] (fork)"
         );
 
         // Repository with description containing extra whitespace
         assert_eq!(
-            format_repository("whitespace-test", "  Description with extra spaces  ", false, false, RepoSource::GitHub),
+            format_repository(&repo("whitespace-test", "  Description with extra spaces  ", false, false, RepoSource::GitHub), &DisplayTemplate::default(), FormatOptions::default()),
             "whitespace-test [GH] (Description with extra spaces)"
         );
 
         // Forked repository with no description
         assert_eq!(
-            format_repository("just-fork", "", true, false, RepoSource::GitLab),
+            format_repository(&repo("just-fork", "", true, false, RepoSource::GitLab), &DisplayTemplate::default(), FormatOptions::default()),
             "just-fork [GL] (fork)"
         );
     }
+
+    #[test]
+    fn test_ascii_markers() {
+        let ascii = FormatOptions { emoji: false };
+
+        assert_eq!(format_repo_name("private-repo", false, true, RepoSource::GitHub, ascii), "private-repo [priv] [GH]");
+        assert_eq!(format_gist_name("2814", true, RepoSource::GitLab, ascii), "2814 [priv] [gist] [GL]");
+        assert_eq!(
+            format_repository(&repo("private-fork", "", true, true, RepoSource::GitLab), &DisplayTemplate::default(), ascii),
+            "private-fork [priv] [GL] (fork)"
+        );
+
+        assert_eq!(mark_starred("notes [GH]", ascii), "notes [GH] [star]");
+        assert_eq!(mark_archived("notes [GH]", ascii), "notes [GH] [archived]");
+        assert_eq!(mark_mirrored("notes [GH]", ascii), "notes [GH] [mirror]");
+        assert_eq!(mark_new("notes [GH]", ascii), "notes [GH] [new]");
+        assert_eq!(mark_stars("notes [GH]", Some(42), ascii), "notes [GH] *42");
+
+        // Language icons have no ASCII form, so they are left out
+        assert_eq!(mark_language("notes [GH]", Some("Rust"), ascii), "notes [GH]");
+    }
+
+    #[test]
+    fn test_owner_source_columns_align() {
+        let repos = [
+            ("dima-369", RepoSource::GitHub),
+            ("a", RepoSource::GitLab),
+            ("日本語", RepoSource::GitHub),
+        ];
+        let width = owner_column_width(repos.iter().map(|(owner, _)| *owner));
+        assert_eq!(width, "dima-369/".len());
+
+        let prefixes: Vec<String> = repos
+            .iter()
+            .map(|(owner, source)| owner_source_prefix(owner, *source, width))
+            .collect();
+
+        assert_eq!(prefixes[0], "dima-369/ [GH] ");
+        assert_eq!(prefixes[1], "a/        [GL] ");
+        // Each wide character takes two cells, so fewer spaces are needed
+        assert_eq!(prefixes[2], "日本語/   [GH] ");
+        assert!(prefixes.iter().all(|prefix| prefix.width() == prefixes[0].width()));
+
+        assert_eq!(owner_column_width(Vec::<&str>::new()), 0);
+    }
+
+    #[test]
+    fn test_truncation_leaves_room_for_ellipsis() {
+        // The result fills the available width exactly, whatever the ellipsis is
+        for ellipsis in ["…", "...", ">"] {
+            let (truncated, was_cut) = truncate_end("rust-web-server", 10, ellipsis);
+            assert!(was_cut);
+            assert!(truncated.ends_with(ellipsis));
+            assert_eq!(truncated.width(), 10);
+
+            let truncated = truncate_start("rust-web-server", 10, ellipsis);
+            assert!(truncated.starts_with(ellipsis));
+            assert!(truncated.ends_with("server"));
+            assert_eq!(truncated.width(), 10);
+        }
+        assert_eq!(truncate_end("rust-web-server", 10, "...").0, "rust-we...");
+        assert_eq!(truncate_start("rust-web-server", 10, "..."), "...-server");
+
+        // Wide characters are never split, which can leave a cell free
+        assert_eq!(truncate_end("日本語テキスト", 7, "…").0, "日本語…");
+        assert_eq!(truncate_end("日本語テキスト", 6, "…").0, "日本…");
+
+        // Cut text can keep its length in bytes, a multi-byte ellipsis replacing single-byte characters
+        assert_eq!(truncate_end("rust-web", 6, "…"), ("rust-…".to_string(), true));
+
+        // Text which fits is left alone
+        assert_eq!(truncate_end("rust", 4, "..."), ("rust".to_string(), false));
+        assert_eq!(truncate_start("rust", 10, "..."), "rust");
+    }
+
+    #[test]
+    fn test_query_window() {
+        // A query which fits is shown as it is
+        assert_eq!(query_window("rust", 2, 10, "…"), ("rust".to_string(), 2));
+        assert_eq!(query_window("日本", 3, 10, "…"), ("日本".to_string(), 2));
+
+        // The start is shown while the cursor fits
+        assert_eq!(query_window("rust-web-server", 2, 8, "…"), ("rust-we…".to_string(), 2));
+
+        // Otherwise the text before the cursor is cut, keeping a cell for the cursor
+        assert_eq!(query_window("rust-web-server", 15, 8, "…"), ("…server".to_string(), 7));
+        assert_eq!(query_window("rust-web-server", 10, 8, "…"), ("…-web-s…".to_string(), 7));
+
+        // A terminal too narrow for the ellipsis does not underflow
+        assert_eq!(query_window("rust", 4, 0, "…").1, 1);
+    }
+
+    #[test]
+    fn test_highlight_chars() {
+        assert_eq!(highlight_chars("rust-web", &[0, 5], "[", "]"), "[r]ust-[w]eb");
+        assert_eq!(highlight_chars("🦀 crab", &[2, 3], "[", "]"), "🦀 [c][r]ab");
+
+        // Positions past the end are ignored
+        assert_eq!(highlight_chars("go", &[1, 7], "[", "]"), "g[o]");
+        assert_eq!(highlight_chars("go", &[], "[", "]"), "go");
+    }
+
+    #[test]
+    fn test_source_match_counts() {
+        let counts = [(RepoSource::GitHub, 5, 42), (RepoSource::GitLab, 2, 13)];
+        assert_eq!(source_match_counts(&counts), "GH 5/42 · GL 2/13");
+
+        // Platforms without repositories are left out, but ones without matches are not
+        let counts = [(RepoSource::GitHub, 0, 42), (RepoSource::GitLab, 0, 0)];
+        assert_eq!(source_match_counts(&counts), "GH 0/42");
+        assert_eq!(source_match_counts(&[]), "");
+    }
+
+    #[test]
+    fn test_parse_ellipsis() {
+        assert_eq!(parse_ellipsis("...").unwrap(), "...");
+        assert_eq!(parse_ellipsis(DEFAULT_ELLIPSIS).unwrap(), "…");
+        assert!(parse_ellipsis("").is_err());
+        assert!(parse_ellipsis("[....]").is_err());
+        assert!(parse_ellipsis("\t").is_err());
+    }
+
+    #[test]
+    fn test_common_name_prefix() {
+        // Shared prefix, cut back to the last separator
+        assert_eq!(
+            common_name_prefix(["medical-medium-text-files", "medical-medium-demon-podcast-notes", "medical-medium-tools"]),
+            "medical-medium-"
+        );
+
+        // No shared prefix
+        assert_eq!(common_name_prefix(["rust-web-server", "go-microservices"]), "");
+
+        // Shared characters without a separator are not a prefix
+        assert_eq!(common_name_prefix(["medicalmedium-instagram", "medical-medium-text"]), "");
+
+        // A single repository has nothing to elide
+        assert_eq!(common_name_prefix(["medical-medium-text"]), "");
+        assert_eq!(common_name_prefix(Vec::<&str>::new()), "");
+
+        // Never elide a whole name
+        assert_eq!(common_name_prefix(["api-", "api-client"]), "");
+        assert_eq!(common_name_prefix(["api-client", "api-"]), "");
+        assert_eq!(common_name_prefix(["ab-c", "ab-d"]), "ab-");
+        assert_eq!(common_name_prefix(["api-v2", "api-v2"]), "api-");
+    }
 }
@@ -1,7 +1,11 @@
-use std::io::{self, stdin, stdout, Write};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, stdout, Write};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
 use std::process;
-use std::thread;
-use std::time::Duration;
+use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant};
 use termion::clear;
 use termion::color;
 use termion::cursor;
@@ -10,21 +14,197 @@ use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 use termion::screen::IntoAlternateScreen;
 use termion::style;
-use termion as terminal;
+use tokio::sync::mpsc;
+use unicode_width::UnicodeWidthStr;
 
+use crate::aliases::{self, Aliases};
+use crate::basket::{self, Basket};
+use crate::cache::RepoData;
+use crate::clipboard;
 use crate::filter;
+use crate::formatter::{self, RepoSource};
+use crate::git::{self, LocalState};
+use crate::grouping::{self, GroupBy, Section};
+use crate::masked_input::MaskedInput;
+use crate::multiplexer;
+use crate::quick_filter::{self, QuickFilter};
+use crate::recording::{Recorder, Recording, Replay};
+use crate::sort;
+use crate::state;
+use crate::terminal::PressTracker;
+
+/// Status shown once the background refresh finished
+pub const LOADING_COMPLETE_STATUS: &str = "Repository loading complete";
+
+/// An update from the background refresh, applied while the finder runs
+pub enum FinderUpdate {
+    /// The repositories fetched so far, merged into the listed ones
+    Repos(Vec<RepoData>),
+    /// The repositories of a fetch which started over, replacing the listed ones
+    FreshRepos(Vec<RepoData>),
+    Status(String),
+    Error(String),
+    /// A source rejected its token, so a new one is asked for
+    AuthFailed(RepoSource),
+    /// The refresh finished, which stops the spinner, with the status to show
+    LoadingComplete(String),
+    /// Clears the status and error messages
+    ClearMessages,
+}
+
+/// Typing pauses this long before the list is filtered again, so fast typing filters once
+const FILTER_DEBOUNCE: Duration = Duration::from_millis(40);
+
+/// Frames of the spinner shown while repositories are loading, one per render tick
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How often a new token can be entered for a source before giving up
+const MAX_TOKEN_ATTEMPTS: usize = 3;
+
+/// How long a confirmation like "Copied SSH URL" stays in the status line
+const TRANSIENT_STATUS_DURATION: Duration = Duration::from_secs(2);
+
+/// What to do with the item picked in the fuzzy finder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinderAction {
+    /// Enter: open the repository in the browser
+    Open,
+    /// Ctrl+G: open the repository in a git TUI
+    GitTui,
+    /// Ctrl+F: open the file browser of the repository at its default branch
+    Tree,
+    /// Ctrl+E: run the clone command of every basket repository in its own multiplexer window
+    BasketWindows,
+    /// Enter with `--multi`: copy the SSH URLs of the marked repositories
+    CopyUrls,
+}
+
+/// What the finder asks of the background refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshRequest {
+    /// F5: fetch everything again without the cache, unless a refresh is running
+    Refresh,
+    /// Ctrl+R: cancel the running refresh and start it over
+    Restart,
+}
+
+/// What a key pressed in the finder leads to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyOutcome {
+    Continue,
+    /// The selected repository was picked for an action
+    Select(FinderAction),
+    Quit,
+}
+
+/// Deletes the word before the cursor like readline's Ctrl+W, returning the new query and cursor.
+///
+/// Spaces right before the cursor are deleted with the word before them, words are
+/// separated by whitespace only.
+fn delete_word_before(query: &str, cursor: usize) -> (String, usize) {
+    let before = query[..cursor].trim_end();
+    let start = before.rfind(char::is_whitespace).map_or(0, |i| i + before[i..].chars().next().unwrap().len_utf8());
+    (format!("{}{}", &query[..start], &query[cursor..]), start)
+}
+
+/// The local state of a repository by its URL, sent when a background check finishes
+type LocalStateUpdate = (String, LocalState);
+
+/// How long filtering took for one query, logged with `--verbose`
+#[derive(Debug)]
+struct FilterTiming {
+    elapsed: Duration,
+    /// Matching items before and after filtering
+    before: usize,
+    after: usize,
+}
 
 // Custom UI for displaying and filtering repositories
 pub struct FuzzyFinder {
+    repos: Vec<RepoData>,
+    // Formatted repositories, in the same order as `repos`
     items: Vec<String>,
-    filtered_items: Vec<String>,
+    format: Box<dyn Fn(&RepoData) -> String>,
+    index: filter::FilterIndex,
+    // Indices into `items` which match the query
+    filtered_items: Vec<usize>,
     query: String,
+    // Byte offset of the cursor in the query, always on a character boundary
     cursor_pos: usize,
     selected_index: usize,
     max_display: usize,
+    // Height of the terminal the list was fitted into, unknown until the finder runs
+    height: Option<u16>,
     scroll_offset: usize,
     status_message: Option<String>,
+    // When a transient status message like "Copied SSH URL" is cleared again
+    status_expires_at: Option<Instant>,
     error_message: Option<String>,
+    // Repositories are still streaming in from the background refresh, shown with a spinner
+    loading: bool,
+    spinner_frame: usize,
+    // Saves the query when leaving the finder, so the next run starts with it
+    remember_query: bool,
+    // Query edits wait this long for more typing before filtering, zero filters right away
+    filter_debounce: Duration,
+    // When the query changed without being filtered yet
+    filter_pending_since: Option<Instant>,
+    strip_common_prefix: bool,
+    // Name prefix shared by all filtered repositories, elided in the list
+    common_prefix: String,
+    archived_filter: filter::ArchivedFilter,
+    // Only repositories of this platform are shown, cycled with Ctrl+S
+    source_filter: Option<RepoSource>,
+    // Matches scoring below this are hidden, 0 shows all of them
+    min_score: i64,
+    // Splits the filtered items into sections with headers, set with `--group-by`
+    group_by: GroupBy,
+    // Moving past either end of the list continues at the other end, set with `--cycle`
+    wrap: bool,
+    // Presets toggled with Alt+1 to Alt+9, and the indices of the active ones
+    quick_filters: Vec<QuickFilter>,
+    active_quick_filters: BTreeSet<usize>,
+    confirm_quit_when_busy: bool,
+    quit_presses: PressTracker,
+    // Repositories and status messages from the background refresh
+    updates: Option<mpsc::Receiver<FinderUpdate>>,
+    // Retries fetching a source with a newly entered token
+    retry_token: Option<Box<dyn FnMut(RepoSource, String)>>,
+    // Asks main to refresh or to start the running refresh over
+    refresh_requests: Option<mpsc::Sender<RefreshRequest>>,
+    // The source whose new token is being entered instead of the query
+    token_prompt: Option<(RepoSource, MaskedInput)>,
+    token_attempts: HashMap<RepoSource, usize>,
+    aliases: Aliases,
+    show_aliases: bool,
+    // The URL of the repository whose alias is being edited instead of the query, and the new alias.
+    // A refresh can move the repository meanwhile, so it is looked up by URL when saving
+    alias_prompt: Option<(String, String)>,
+    basket: Basket,
+    // Only the basket is shown, so repositories removed from it are hidden
+    basket_only: bool,
+    // Ctrl+E opens the basket in multiplexer windows, set with `--multiplexer`
+    basket_windows: bool,
+    // Ctrl+E was pressed once for a large basket and waits to be pressed again
+    confirm_basket_windows: bool,
+    // Tab marks repositories and Enter copies their URLs, set with `--multi`
+    multi_select: bool,
+    // URLs of the marked repositories, which stay marked when the list is refreshed
+    marked: HashSet<String>,
+    show_owner_source: bool,
+    // Marks truncated items and queries and the elided common prefix
+    ellipsis: String,
+    // The clone directory checked for local clones with `--check-local`
+    local_clone_dir: Option<PathBuf>,
+    // Local state by repository URL, Absent while a check is still running
+    local_states: HashMap<String, LocalState>,
+    local_state_updates: Option<(std_mpsc::Sender<LocalStateUpdate>, std_mpsc::Receiver<LocalStateUpdate>)>,
+    // Matching and total repositories of GitHub and GitLab, recounted on each filter
+    source_counts: [(RepoSource, usize, usize); 2],
+    // Where the pressed keys are saved with `--record`, and the keys so far
+    recorder: Option<(PathBuf, Recorder)>,
+    // Keys fed into the finder before reading the terminal with `--replay`
+    replay: Option<Replay>,
 }
 
 impl FuzzyFinder {
@@ -42,359 +222,2200 @@ impl FuzzyFinder {
         process::exit(0);
     }
 
-    pub fn new(items: Vec<String>) -> Self {
-        let filtered_items = items.clone();
+    pub fn new<F>(repos: Vec<RepoData>, format: F) -> Self
+    where
+        F: Fn(&RepoData) -> String + 'static,
+    {
+        let items: Vec<String> = repos.iter().map(&format).collect();
+        let filtered_items = (0..items.len()).collect();
+        let index = filter::FilterIndex::new(&items, |s| s.clone());
         let max_display = 10; // Number of items to display at once
 
-        Self {
+        let mut finder = Self {
+            repos,
             items,
+            format: Box::new(format),
+            index,
             filtered_items,
             query: String::new(),
             cursor_pos: 0,
             selected_index: 0,
             max_display,
+            height: None,
             scroll_offset: 0,
             status_message: None,
+            status_expires_at: None,
             error_message: None,
-        }
+            loading: false,
+            spinner_frame: 0,
+            remember_query: false,
+            filter_debounce: Duration::ZERO,
+            filter_pending_since: None,
+            strip_common_prefix: false,
+            common_prefix: String::new(),
+            archived_filter: filter::ArchivedFilter::default(),
+            source_filter: None,
+            min_score: 0,
+            group_by: GroupBy::None,
+            wrap: false,
+            quick_filters: Vec::new(),
+            active_quick_filters: BTreeSet::new(),
+            confirm_quit_when_busy: false,
+            quit_presses: PressTracker::default(),
+            updates: None,
+            retry_token: None,
+            refresh_requests: None,
+            token_prompt: None,
+            token_attempts: HashMap::new(),
+            aliases: Aliases::default(),
+            show_aliases: false,
+            alias_prompt: None,
+            basket: Basket::default(),
+            basket_only: false,
+            basket_windows: false,
+            confirm_basket_windows: false,
+            multi_select: false,
+            marked: HashSet::new(),
+            show_owner_source: false,
+            ellipsis: formatter::DEFAULT_ELLIPSIS.to_string(),
+            local_clone_dir: None,
+            local_states: HashMap::new(),
+            local_state_updates: None,
+            source_counts: [(RepoSource::GitHub, 0, 0), (RepoSource::GitLab, 0, 0)],
+            recorder: None,
+            replay: None,
+        };
+        finder.count_sources();
+        finder
     }
 
-    /// Updates the items list and refreshes the display
-    pub fn update_items(&mut self, new_items: Vec<String>) {
-        self.items = new_items;
-        self.update_filter();
+    /// Records every pressed key to a file, saved after each selection and on quit
+    pub fn set_recording(&mut self, path: PathBuf) {
+        self.recorder = Some((path, Recorder::start()));
     }
 
-    /// Sets a status message to be displayed in the UI
-    pub fn set_status_message(&mut self, message: Option<String>) {
-        self.status_message = message;
+    /// Presses the keys of a recording at their recorded pace before reading the terminal
+    pub fn set_replay(&mut self, recording: Recording) {
+        self.replay = Some(Replay::new(recording));
     }
 
-    /// Sets an error message to be displayed in the UI
-    pub fn set_error_message(&mut self, message: Option<String>) {
-        self.error_message = message;
+    /// Records a key with `--record`, except those typed into the token prompt, which would
+    /// save the token in plain text
+    fn record_key(&mut self, key: Key) {
+        if self.token_prompt.is_some() {
+            return;
+        }
+        if let Some((_, recorder)) = &mut self.recorder {
+            recorder.record(key);
+        }
     }
 
-    fn update_filter(&mut self) {
-        // Use the filter_human function to filter items based on query
-        self.filtered_items = filter::filter_human(&self.items, &self.query, |s| s.clone());
+    fn save_recording(&mut self) {
+        if let Some((path, recorder)) = &self.recorder {
+            if let Err(e) = recorder.recording().save_to(path) {
+                self.error_message = Some(format!("Failed to save recording: {}", e));
+            }
+        }
+    }
 
-        // Reset selection if it's out of bounds
-        if self.selected_index >= self.filtered_items.len() {
-            self.selected_index = if self.filtered_items.is_empty() {
-                0
-            } else {
-                self.filtered_items.len() - 1
-            };
+    /// Keeps the query for the next run, like the basket a failure to save is not fatal
+    fn save_query(&mut self) {
+        if !self.remember_query {
+            return;
+        }
+        if let Err(e) = state::save_last_query(&self.query) {
+            self.error_message = Some(format!("Failed to save the query: {}", e));
         }
+    }
 
-        // Reset scroll offset if needed
-        if self.selected_index < self.scroll_offset {
-            self.scroll_offset = self.selected_index;
-        } else if self.selected_index >= self.scroll_offset + self.max_display {
-            self.scroll_offset = self.selected_index - self.max_display + 1;
+    /// Returns the next replayed key once it is due, dropping the replay after its last key
+    fn next_replayed_key(&mut self) -> Option<Key> {
+        let replay = self.replay.as_mut()?;
+        let key = replay.next_key();
+        if replay.is_finished() {
+            self.replay = None;
         }
+        key
     }
 
-    fn move_cursor_up(&mut self) {
-        if !self.filtered_items.is_empty() && self.selected_index > 0 {
-            self.selected_index -= 1;
+    /// Marks repositories with a local clone in the clone directory, and if it is dirty.
+    ///
+    /// The clones are checked in the background, so the badges appear as checks finish.
+    pub fn set_local_clone_dir(&mut self, clone_dir: PathBuf) {
+        self.local_clone_dir = Some(clone_dir);
+        self.local_state_updates = Some(std_mpsc::channel());
+        self.check_local_clones();
+    }
 
-            // Adjust scroll offset if needed
-            if self.selected_index < self.scroll_offset {
-                self.scroll_offset = self.selected_index;
+    /// Starts checking the repositories which were not checked yet
+    fn check_local_clones(&mut self) {
+        let (Some(clone_dir), Some((tx, _))) = (&self.local_clone_dir, &self.local_state_updates) else {
+            return;
+        };
+
+        let mut unchecked = Vec::new();
+        for repo in &self.repos {
+            if !self.local_states.contains_key(&repo.url) {
+                self.local_states.insert(repo.url.clone(), LocalState::Absent);
+                unchecked.push((repo.url.clone(), repo.name.clone()));
             }
         }
+        if unchecked.is_empty() {
+            return;
+        }
+
+        let clone_dir = clone_dir.clone();
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            for (url, name) in unchecked {
+                let state = git::local_state(&git::clone_target(&clone_dir, &name));
+                if state != LocalState::Absent && tx.send((url, state)).is_err() {
+                    return;
+                }
+            }
+        });
     }
 
-    fn move_cursor_down(&mut self) {
-        if !self.filtered_items.is_empty() && self.selected_index < self.filtered_items.len() - 1 {
-            self.selected_index += 1;
+    /// Checks the local clone of a repository again, like after an action cloned or changed it
+    pub fn recheck_local_clone(&mut self, repo: &RepoData) {
+        self.local_states.remove(&repo.url);
+        self.check_local_clones();
+    }
 
-            // Adjust scroll offset if needed
-            if self.selected_index >= self.scroll_offset + self.max_display {
-                self.scroll_offset = self.selected_index - self.max_display + 1;
-            }
+    /// Sets the marker for truncated text, for terminals without the … glyph
+    pub fn set_ellipsis(&mut self, ellipsis: String) {
+        self.ellipsis = ellipsis;
+    }
+
+    /// Starts with a query, like one typed before the finder opened
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.cursor_pos = self.query.len();
+        self.update_filter();
+    }
+
+    /// Saves the query whenever the finder is left, to restore it on the next launch
+    pub fn set_remember_query(&mut self, remember_query: bool) {
+        self.remember_query = remember_query;
+    }
+
+    /// Elides the name prefix shared by all filtered repositories, showing it once above the list
+    pub fn set_strip_common_prefix(&mut self, strip_common_prefix: bool) {
+        self.strip_common_prefix = strip_common_prefix;
+        self.update_filter();
+    }
+
+    /// Sets which repositories are shown by whether they are archived, cycled with Ctrl+X
+    pub fn set_archived_filter(&mut self, archived_filter: filter::ArchivedFilter) {
+        self.archived_filter = archived_filter;
+        self.update_filter();
+    }
+
+    /// Hides weak matches scoring below `min_score`, 0 shows all matches
+    pub fn set_min_score(&mut self, min_score: i64) {
+        self.min_score = min_score;
+        self.update_filter();
+    }
+
+    /// Shows the filtered repositories in labeled sections, like public and private ones
+    pub fn set_group_by(&mut self, group_by: GroupBy) {
+        self.group_by = group_by;
+        self.update_filter();
+    }
+
+    /// Moves the selection from the last item to the first and back, like fzf's `--cycle`
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Sets the presets toggled with Alt+1 to Alt+9, which start inactive
+    pub fn set_quick_filters(&mut self, quick_filters: Vec<QuickFilter>) {
+        self.quick_filters = quick_filters;
+        self.active_quick_filters.clear();
+        self.update_filter();
+    }
+
+    /// Turns the preset at `index` on or off, if there is one
+    fn toggle_quick_filter(&mut self, index: usize) {
+        if index >= self.quick_filters.len() {
+            return;
+        }
+
+        if !self.active_quick_filters.remove(&index) {
+            self.active_quick_filters.insert(index);
         }
+        self.update_filter();
     }
 
-    fn render<W: Write>(&self, screen: &mut W) -> io::Result<()> {
-        // Get terminal size
-        let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+    fn active_quick_filters(&self) -> impl Iterator<Item = &QuickFilter> {
+        self.active_quick_filters.iter().map(|&index| &self.quick_filters[index])
+    }
 
-        // Clear screen
-        write!(screen, "{}{}", clear::All, cursor::Goto(1, 1))?;
+    /// Shows each repository's owner and source in aligned columns before it, toggled with Ctrl+O
+    pub fn set_show_owner_source(&mut self, show_owner_source: bool) {
+        self.show_owner_source = show_owner_source;
+    }
 
-        // Calculate available space for items (accounting for prompt and status lines)
-        let available_lines = height as usize - 3; // Prompt line (with input) + status line + separator line
+    /// Asks for a second Esc or Ctrl+C before quitting while repositories are being fetched
+    pub fn set_confirm_quit_when_busy(&mut self, confirm_quit_when_busy: bool) {
+        self.confirm_quit_when_busy = confirm_quit_when_busy;
+    }
 
-        // Adjust max_display based on available space
-        let display_count = std::cmp::min(available_lines, self.filtered_items.len());
-        let end_idx = std::cmp::min(
-            self.scroll_offset + display_count,
-            self.filtered_items.len(),
-        );
+    /// Receives repositories and status messages while the finder is running
+    pub fn set_update_receiver(&mut self, updates: mpsc::Receiver<FinderUpdate>) {
+        self.updates = Some(updates);
+    }
 
-        // Display items
-        for i in self.scroll_offset..end_idx {
-            let item = &self.filtered_items[i];
+    /// Prompts for a new token when a source rejects one, and passes it to `retry`
+    pub fn set_token_retry<F>(&mut self, retry: F)
+    where
+        F: FnMut(RepoSource, String) + 'static,
+    {
+        self.retry_token = Some(Box::new(retry));
+    }
 
-            // Calculate available width for text (accounting for the prefix)
-            let prefix_len = 2; // Both "> " and "  " are 2 characters
-            let available_width = width as usize - prefix_len - 5; // Extra buffer for emojis and safety
+    /// Sends F5 and Ctrl+R presses to whoever runs the background refresh
+    pub fn set_refresh_requests(&mut self, requests: mpsc::Sender<RefreshRequest>) {
+        self.refresh_requests = Some(requests);
+    }
 
-            // Truncate item text if it's too long
-            let display_text = if item.chars().count() > available_width {
-                // Truncate and add ellipsis, being careful with multibyte characters like emojis
-                let mut truncated = String::new();
-                let mut char_count = 0;
+    /// Asks for a refresh, which only a restart may start while another one is running.
+    ///
+    /// The error of an earlier refresh is dropped, as the new one may succeed.
+    fn request_refresh(&mut self, request: RefreshRequest) {
+        let Some(requests) = &self.refresh_requests else {
+            return;
+        };
+        if request == RefreshRequest::Refresh && self.loading {
+            self.set_transient_status_message("Already refreshing, Ctrl+R starts over".to_string(), Instant::now());
+            return;
+        }
+        // A full channel already holds a request which starts a refresh
+        let _ = requests.try_send(request);
 
-                for c in item.chars() {
-                    if char_count >= available_width - 1 {
-                        break;
-                    }
-                    truncated.push(c);
-                    char_count += 1;
-                }
+        self.loading = true;
+        self.set_error_message(None);
+        let status = match request {
+            RefreshRequest::Refresh => "Refreshing",
+            RefreshRequest::Restart => "Restarting the refresh",
+        };
+        self.set_status_message(Some(format!("{}{}", status, self.ellipsis)));
+    }
 
-                format!("{truncated}…")
-            } else {
-                item.clone()
-            };
+    /// Applies all pending updates from the background refresh and the local clone checks
+    fn apply_updates(&mut self) {
+        self.expire_status_message(Instant::now());
 
-            // Highlight selected item
-            if i == self.selected_index {
-                write!(
-                    screen,
-                    "{}{}> {}{}",
-                    color::Fg(color::Green),
-                    style::Bold,
-                    display_text,
-                    style::Reset
-                )?;
-            } else {
-                write!(screen, "  {}", display_text)?;
+        if let Some((_, rx)) = &self.local_state_updates {
+            self.local_states.extend(rx.try_iter());
+        }
+
+        let mut pending = Vec::new();
+        if let Some(updates) = &mut self.updates {
+            while let Ok(update) = updates.try_recv() {
+                pending.push(update);
             }
+        }
 
-            write!(screen, "\r\n")?;
+        for update in pending {
+            match update {
+                FinderUpdate::Repos(repos) => self.merge_items(repos),
+                FinderUpdate::FreshRepos(repos) => self.update_items(repos),
+                FinderUpdate::Status(status) => self.set_status_message(Some(status)),
+                FinderUpdate::Error(error) => self.set_error_message(Some(error)),
+                FinderUpdate::AuthFailed(source) => self.request_token(source),
+                FinderUpdate::LoadingComplete(status) => {
+                    self.loading = false;
+                    self.set_status_message(Some(status));
+                }
+                FinderUpdate::ClearMessages => {
+                    self.set_status_message(None);
+                    self.set_error_message(None);
+                }
+            }
         }
+    }
 
-        // Reserve space for status messages (2 lines)
-        let status_area_height: u16 = 2;
+    /// Prompts for a new token for a source, unless it rejected too many already
+    fn request_token(&mut self, source: RepoSource) {
+        if self.retry_token.is_none() {
+            return;
+        }
 
-        // Fill any remaining lines with empty space
-        let display_items_count = end_idx - self.scroll_offset;
-        let required_lines = 4 + status_area_height as usize + display_items_count;
-        let empty_lines = if height as usize > required_lines {
-            height as usize - required_lines
-        } else {
-            0 // No empty lines if we don't have enough space
+        let attempts = self.token_attempts.entry(source).or_insert(0);
+        if *attempts >= MAX_TOKEN_ATTEMPTS {
+            self.error_message = Some(format!("{:?} rejected {} new tokens, giving up", source, MAX_TOKEN_ATTEMPTS));
+            return;
+        }
+
+        *attempts += 1;
+        self.token_prompt = Some((source, MaskedInput::default()));
+    }
+
+    /// Handles a key while a new token is being entered
+    fn handle_token_prompt_key(&mut self, key: Key) {
+        let Some((source, input)) = &mut self.token_prompt else {
+            return;
         };
 
-        for _ in 0..empty_lines {
-            write!(screen, "\r\n")?;
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                let source = *source;
+                let token = std::mem::take(input).into_value();
+                self.token_prompt = None;
+
+                if token.is_empty() {
+                    return;
+                }
+                if let Some(retry) = &mut self.retry_token {
+                    retry(source, token);
+                    self.loading = true;
+                    self.error_message = None;
+                    self.status_message = Some(format!("Retrying {:?} with the new token...", source));
+                }
+            }
+            Key::Char(c) => input.push(c),
+            Key::Backspace => input.backspace(),
+            // Skip entering a token, keeping the finder usable with the other source
+            Key::Esc | Key::Ctrl('c') => self.token_prompt = None,
+            _ => {}
         }
+    }
 
-        // Calculate the position for the status area (safely)
-        let status_pos = if height > 3 + status_area_height {
-            height - 3 - status_area_height
-        } else {
-            1 // Fallback to top of screen if terminal is too small
+    // Waits up to the timeout for input on the terminal, so updates can be rendered while idle
+    fn wait_for_input(tty: &File, timeout: Duration) -> bool {
+        let mut poll_fd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
         };
 
-        // Position cursor for the status area
-        write!(screen, "{}", cursor::Goto(1, status_pos))?;
+        // SAFETY: poll only writes to the single pollfd it is given
+        unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int) > 0 }
+    }
+
+    /// Makes repositories searchable by their aliases, optionally showing them in the list
+    pub fn set_aliases(&mut self, aliases: Aliases, show_aliases: bool) {
+        self.aliases = aliases;
+        self.show_aliases = show_aliases;
+        self.rebuild_index();
+        self.update_filter();
+    }
+
+    /// Sets the basket which Ctrl+B adds repositories to, and whether only it is shown
+    pub fn set_basket(&mut self, basket: Basket, basket_only: bool) {
+        self.basket = basket;
+        self.basket_only = basket_only;
+    }
+
+    /// Lets Ctrl+E open every basket repository in a multiplexer window
+    pub fn set_basket_windows(&mut self, basket_windows: bool) {
+        self.basket_windows = basket_windows;
+    }
+
+    /// Lets Tab mark several repositories, whose URLs Enter copies together
+    pub fn set_multi_select(&mut self, multi_select: bool) {
+        self.multi_select = multi_select;
+    }
 
-        // Clear the status area (2 lines)
-        for _ in 0..status_area_height {
-            write!(screen, "{}{}", terminal::clear::CurrentLine, "\r\n")?;
+    /// Marks the selected repository or unmarks it again
+    fn toggle_marked(&mut self) {
+        let Some((name, url)) = self.selected_repo().map(|repo| (repo.name.clone(), repo.url.clone())) else {
+            return;
+        };
+        let marked = !self.marked.remove(&url);
+        if marked {
+            self.marked.insert(url);
         }
 
-        // Move back to the start of the status area
-        write!(screen, "{}", cursor::Goto(1, status_pos))?;
+        let action = if marked { "Marked" } else { "Unmarked" };
+        self.status_message = Some(format!("{} {} ({} marked)", action, name, self.marked.len()));
+    }
 
-        // Display error message if any (in red)
-        if let Some(error) = &self.error_message {
-            write!(
-                screen,
-                "{}>Error: {}{}",
-                color::Fg(color::Red),
-                error,
-                style::Reset
-            )?;
+    /// Returns how many repositories are marked, like "3 marked (1 hidden)" when the query
+    /// or filters hide some of them, as those are copied too
+    fn marked_text(&self) -> Option<String> {
+        let is_marked = |id: &usize| self.marked.contains(&self.repos[*id].url);
+        let total = (0..self.repos.len()).filter(is_marked).count();
+        let hidden = total - self.filtered_items.iter().filter(|id| is_marked(id)).count();
+        match (total, hidden) {
+            (0, _) => None,
+            (total, 0) => Some(format!("{} marked", total)),
+            (total, hidden) => Some(format!("{} marked ({} hidden)", total, hidden)),
         }
-        // Otherwise display status message if any (in green)
-        else if let Some(status) = &self.status_message {
-            write!(
-                screen,
-                "{}>{}{}",
-                color::Fg(color::Green),
-                status,
-                style::Reset
-            )?;
+    }
+
+    /// Returns the marked repositories in list order and unmarks them.
+    ///
+    /// Without marks this is the selected repository, so Enter works like without `--multi`.
+    pub fn take_marked_repositories(&mut self) -> Vec<RepoData> {
+        let marked = std::mem::take(&mut self.marked);
+        if marked.is_empty() {
+            return self.selected_repo().cloned().into_iter().collect();
         }
-        write!(screen, "\r\n")?;
+        self.repos.iter().filter(|repo| marked.contains(&repo.url)).cloned().collect()
+    }
 
-        // Create the status text with count
-        let count_text = format!("{}/{}", self.filtered_items.len(), self.items.len());
+    /// Returns the repositories in the basket, which Ctrl+E opens windows for
+    pub fn basket_repositories(&self) -> &[RepoData] {
+        &self.basket.repositories
+    }
 
-        // Display status line at the bottom (format: "12/12 ───────────────")
-        write!(
-            screen,
-            "{}{} {}{}",
-            color::Fg(color::Yellow),
-            count_text,
-            color::Fg(color::Blue),
-            "─".repeat(width as usize - count_text.len() - 1)
-        )?;
-        write!(screen, "{}", style::Reset)?;
+    /// Handles Ctrl+E, returning whether the basket windows should be opened.
+    ///
+    /// Large baskets have to be confirmed by pressing Ctrl+E again right away.
+    fn request_basket_windows(&mut self, confirming: bool) -> bool {
+        let count = self.basket.repositories.len();
+        if !self.basket_windows {
+            self.status_message = Some("Set --multiplexer to open the basket in windows".to_string());
+            false
+        } else if count == 0 {
+            self.status_message = Some("The basket is empty, add repositories with Ctrl+B".to_string());
+            false
+        } else if count > multiplexer::CONFIRM_ABOVE && !confirming {
+            self.confirm_basket_windows = true;
+            self.status_message = Some(format!("Open {} windows? Press Ctrl+E again to confirm", count));
+            false
+        } else {
+            true
+        }
+    }
 
-        // Display prompt at the bottom with input text on the same line
-        write!(screen, "\r\n{}>{} ", color::Fg(color::Blue), style::Reset)?;
-
-        // Display the input text on the same line as the prompt
-        if !self.query.is_empty() {
-            // Truncate query if it's too long for the terminal width
-            // Account for the prompt (2 characters: '>' and space)
-            let available_width = width as usize - 2;
-            let display_query = if self.query.len() > available_width {
-                // Show the last part of the query that fits in the terminal
-                let start_pos = self.query.len() - available_width + 1;
-                format!("…{}", &self.query[start_pos..])
-            } else {
-                self.query.clone()
+    /// Adds the selected repository to the basket or removes it, saving the basket
+    fn toggle_in_basket(&mut self) {
+        let repo = self.repos[self.filtered_items[self.selected_index]].clone();
+        let added = self.basket.toggle(&repo);
+
+        if let Err(e) = basket::save_basket(&self.basket) {
+            self.error_message = Some(format!("Failed to save basket: {}", e));
+            return;
+        }
+
+        let action = if added { "Added to" } else { "Removed from" };
+        self.status_message = Some(format!(
+            "{} basket: {} ({} in basket)",
+            action,
+            repo.name,
+            self.basket.repositories.len()
+        ));
+
+        if self.basket_only && !added {
+            self.update_items(self.basket.repositories.clone());
+        }
+    }
+
+    /// Updates the repositories and refreshes the display.
+    ///
+    /// The selected repository stays selected on the same row of the list if it still
+    /// matches, even when new repositories are sorted in before it.
+    pub fn update_items(&mut self, repos: Vec<RepoData>) {
+        let selected = self.selection();
+
+        self.items = repos.iter().map(&self.format).collect();
+        self.repos = repos;
+        self.rebuild_index();
+        self.update_filter();
+        self.check_local_clones();
+        self.restore_selection(selected);
+    }
+
+    /// Merges repositories into the list by URL, keeping the selection.
+    ///
+    /// The list takes the order of `repos`, so new repositories appear where they are
+    /// sorted in. Listed repositories missing from `repos` are kept after the one listed
+    /// before them. When the new repositories only extend the list and no listed one
+    /// changed, only the new ones are indexed while a refresh streams in.
+    pub fn merge_items(&mut self, repos: Vec<RepoData>) {
+        let selected = self.selection();
+
+        let incoming: HashSet<String> = repos.iter().map(|repo| repo.url.clone()).collect();
+        let mut merged = repos;
+        for (id, repo) in self.repos.iter().enumerate().filter(|(_, repo)| !incoming.contains(&repo.url)) {
+            let position = match id {
+                0 => 0,
+                _ => merged
+                    .iter()
+                    .position(|listed| listed.url == self.repos[id - 1].url)
+                    .map_or(merged.len(), |position| position + 1),
             };
-            write!(screen, "{}", display_query)?;
+            merged.insert(position, repo.clone());
         }
 
-        // Position cursor at the right position in the input line
-        let available_width = width as usize - 2; // Account for '>' and space
-        if self.query.len() > available_width {
-            // If text is truncated, position cursor at the end of visible text
-            write!(screen, "{}", cursor::Goto(width, height))?;
+        let items: Vec<String> = merged.iter().map(&self.format).collect();
+        let listed = self.repos.len();
+        let extended = merged.len() >= listed
+            && merged.iter().zip(&self.repos).all(|(repo, listed)| repo.url == listed.url)
+            && items.iter().zip(&self.items).all(|(item, listed)| item == listed);
+        self.items = items;
+        self.repos = merged;
+
+        // Moved or changed items would have to be taken out of the index, so only then rebuild it
+        if extended {
+            for id in listed..self.items.len() {
+                let text = self.search_text(id);
+                self.index.push(id, &text);
+            }
         } else {
-            // Otherwise, position cursor at the current position (after the prompt)
-            write!(
-                screen,
-                "{}",
-                cursor::Goto(self.cursor_pos as u16 + 3, height)
-            )?;
+            self.rebuild_index();
         }
 
-        // Ensure all output is flushed to the screen
-        screen.flush()?;
-        Ok(())
+        self.update_filter();
+        self.check_local_clones();
+        self.restore_selection(selected);
     }
 
-    /// Run the fuzzy finder with support for background updates
-    pub fn run(&mut self) -> Option<String> {
-        // Set up terminal
-        let mut screen = stdout()
-            .into_raw_mode()
-            .unwrap()
-            .into_alternate_screen()
-            .unwrap();
+    /// Returns the URL of the selected repository with the row of the list it is on
+    fn selection(&self) -> Option<(String, usize)> {
+        self.selected_repo()
+            .map(|repo| (repo.url.clone(), self.selected_index - self.scroll_offset))
+    }
 
-        // Show cursor and perform initial render
-        write!(screen, "{}", cursor::Show).unwrap();
-        screen.flush().unwrap();
-        self.render(&mut screen).unwrap();
+    /// Selects a repository again on the same row, if it still matches
+    fn restore_selection(&mut self, selected: Option<(String, usize)>) {
+        let Some((url, row)) = selected else {
+            return;
+        };
+        if let Some(position) = self.filtered_items.iter().position(|&id| self.repos[id].url == url) {
+            self.selected_index = position;
+            self.scroll_offset = position.saturating_sub(row);
+            self.scroll_to_selection();
+        }
+    }
 
-        // Process input
-        let stdin = stdin();
-        let mut keys = stdin.keys();
+    // Returns the text an item is matched against, which includes its alias
+    fn search_text(&self, id: usize) -> String {
+        aliases::search_text(&self.items[id], self.aliases.get(&self.repos[id]))
+    }
 
-        // For non-blocking input
-        let mut last_render = std::time::Instant::now();
-        let render_interval = Duration::from_millis(100); // Refresh UI every 100ms
+    fn rebuild_index(&mut self) {
+        let texts: Vec<String> = (0..self.items.len()).map(|id| self.search_text(id)).collect();
+        self.index = filter::FilterIndex::new(&texts, |s| s.clone());
+    }
 
-        loop {
-            // Check if it's time to re-render (for status updates)
-            let now = std::time::Instant::now();
-            if now.duration_since(last_render) >= render_interval {
-                self.render(&mut screen).unwrap();
-                last_render = now;
+    /// Handles a key while the alias of a repository is being edited
+    fn handle_alias_prompt_key(&mut self, key: Key) {
+        let Some((_, alias)) = &mut self.alias_prompt else {
+            return;
+        };
+
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                let Some((url, alias)) = self.alias_prompt.take() else {
+                    return;
+                };
+                let Some(repo) = self.repos.iter().find(|repo| repo.url == url) else {
+                    return;
+                };
+
+                self.aliases.set(repo, &alias);
+                if let Err(e) = aliases::save_aliases(&self.aliases) {
+                    self.error_message = Some(format!("Failed to save aliases: {}", e));
+                }
+                self.rebuild_index();
+                self.update_filter();
+            }
+            Key::Char(c) => alias.push(c),
+            Key::Backspace => {
+                alias.pop();
             }
+            Key::Esc | Key::Ctrl('c') => self.alias_prompt = None,
+            _ => {}
+        }
+    }
 
-            // Process key input (non-blocking)
-            if let Some(Ok(key)) = keys.next() {
-                match key {
-                    Key::Char('\n') | Key::Char('\r') => {
-                        // Return selected item but don't exit the program
-                        if !self.filtered_items.is_empty() {
-                            // Store the selected item
-                            let selected = self.filtered_items[self.selected_index].clone();
+    /// Returns the listed repository with a URL, if it is still listed
+    fn repo_by_url(&self, url: &str) -> Option<&RepoData> {
+        self.repos.iter().find(|repo| repo.url == url)
+    }
 
-                            // Properly restore terminal state before returning
-                            Self::cleanup_terminal(&mut screen);
-                            let _ = screen; // Mark screen as used without trying to drop the reference
+    /// Returns the repository under the cursor, which stays selected after `run` returns
+    pub fn selected_repo(&self) -> Option<&RepoData> {
+        self.filtered_items.get(self.selected_index).map(|&id| &self.repos[id])
+    }
 
-                            // Return the selected item to be processed
-                            return Some(selected);
-                        }
-                    }
-                    Key::Char(c) => {
-                        // Add character to query at cursor position
-                        self.query.insert(self.cursor_pos, c);
-                        self.cursor_pos += 1;
-                        self.update_filter();
-                    }
-                    Key::Backspace => {
-                        // Remove character before cursor position
-                        if !self.query.is_empty() && self.cursor_pos > 0 {
-                            self.query.remove(self.cursor_pos - 1);
-                            self.cursor_pos -= 1;
-                            self.update_filter();
-                        }
-                    }
-                    Key::Up => {
-                        self.move_cursor_up();
-                    }
-                    Key::Down => {
-                        self.move_cursor_down();
-                    }
-                    Key::Left => {
-                        // Move cursor left if possible
-                        if self.cursor_pos > 0 {
-                            self.cursor_pos -= 1;
-                        }
-                    }
-                    Key::Right => {
-                        // Move cursor right if possible
-                        if self.cursor_pos < self.query.len() {
-                            self.cursor_pos += 1;
-                        }
-                    }
-                    Key::Delete => {
-                        // Remove character at cursor position
-                        if !self.query.is_empty() && self.cursor_pos < self.query.len() {
-                            self.query.remove(self.cursor_pos);
-                            self.update_filter();
-                        }
-                    }
-                    Key::Home => {
-                        // Move cursor to the beginning of the query
-                        self.cursor_pos = 0;
-                    }
-                    Key::End => {
-                        // Move cursor to the end of the query
-                        self.cursor_pos = self.query.len();
-                    }
-                    Key::Ctrl('c') => {
-                        Self::exit_program(&mut screen, "\nExiting...");
-                    }
-                    Key::Esc => {
-                        Self::exit_program(&mut screen, "\nExiting...");
-                    }
-                    _ => {}
-                }
+    /// Sets a status message to be displayed in the UI
+    pub fn set_status_message(&mut self, message: Option<String>) {
+        self.status_message = message;
+        self.status_expires_at = None;
+    }
 
-                // Re-render after each key press
-                self.render(&mut screen).unwrap();
-            }
+    /// Shows a status message which is cleared again after a couple of seconds
+    fn set_transient_status_message(&mut self, message: String, now: Instant) {
+        self.status_message = Some(message);
+        self.status_expires_at = Some(now + TRANSIENT_STATUS_DURATION);
+    }
+
+    /// Clears a transient status message once it expired
+    fn expire_status_message(&mut self, now: Instant) {
+        if self.status_expires_at.is_some_and(|expires_at| now >= expires_at) {
+            self.set_status_message(None);
+        }
+    }
+
+    /// Copies the SSH URL of the selected repository to the clipboard, keeping the finder open
+    fn copy_selected_url(&mut self) {
+        let Some(url) = self.selected_repo().map(|repo| repo.url.clone()) else {
+            return;
+        };
+        match clipboard::copy_to_clipboard(&url) {
+            Ok(_) => self.set_transient_status_message("Copied SSH URL".to_string(), Instant::now()),
+            Err(e) => self.set_error_message(Some(format!("Copying the SSH URL failed: {}", e))),
+        }
+    }
+
+    /// Returns the message shown instead of an empty list, telling no matches apart from no repositories yet
+    fn empty_list_message(&self) -> Option<String> {
+        if !self.filtered_items.is_empty() {
+            None
+        } else if !self.items.is_empty() {
+            Some("No matches".to_string())
+        } else if self.loading {
+            Some(format!("Loading{}", self.ellipsis))
+        } else {
+            Some("No repositories".to_string())
+        }
+    }
+
+    /// Shows the spinner until the background refresh reports that loading is complete
+    pub fn set_loading(&mut self, loading: bool) {
+        self.loading = loading;
+    }
+
+    /// Returns the status shown above the count, led by a spinner while repositories are loading
+    fn status_text(&self) -> Option<String> {
+        let frame = SPINNER_FRAMES[self.spinner_frame % SPINNER_FRAMES.len()];
+        match (&self.status_message, self.loading) {
+            (Some(status), true) => Some(format!("{} {}", frame, status)),
+            (None, true) => Some(format!("{} Loading repositories...", frame)),
+            (status, false) => status.clone(),
+        }
+    }
+
+    /// Sets an error message to be displayed in the UI
+    pub fn set_error_message(&mut self, message: Option<String>) {
+        self.error_message = message;
+    }
+
+    /// Returns the message shown above the count line and whether it is an error, cut to the width.
+    ///
+    /// An error is shown instead of the status until it is cleared.
+    fn message_line(&self, width: usize) -> Option<(bool, String)> {
+        let (is_error, text) = match &self.error_message {
+            Some(error) => (true, format!("✖ {}", error)),
+            None => (false, format!(">{}", self.status_text()?)),
+        };
+        // Like the items, leave the last column free so the terminal does not wrap
+        Some((is_error, formatter::truncate_end(&text, width.saturating_sub(1), &self.ellipsis).0))
+    }
+
+    /// Returns the ids of the items matching a query without date terms
+    fn matching_ids(&self, text_query: &str) -> Vec<usize> {
+        let ids: Vec<usize> = (0..self.items.len()).collect();
+
+        // An empty query matches everything, so skip copying all ids through the filter
+        if filter::is_empty_query(text_query) {
+            return ids;
+        }
+
+        // Use the index to only check items which can match the query, best matches first
+        filter::filter_indexed_scored(&ids, &self.index, text_query, self.min_score, |&id| self.search_text(id))
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Filters the items by the query and the archived filter
+    fn filter_items(&mut self) {
+        // Active text presets add their terms to the typed query
+        let query = std::iter::once(self.query.as_str())
+            .chain(self.active_quick_filters().filter_map(QuickFilter::query))
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        // Date and field terms are checked against the repositories, the rest against the formatted text
+        let (text_query, created_filters) = filter::split_created_terms(&query);
+        let (text_query, field_query) = filter::split_field_terms(&text_query);
+
+        let now = sort::now_secs();
+        let ids: Vec<usize> = self
+            .matching_ids(&text_query)
+            .into_iter()
+            .filter(|&id| self.archived_filter.matches(self.repos[id].is_archived))
+            .filter(|&id| self.source_filter.is_none_or(|source| self.repos[id].source == source))
+            .filter(|&id| self.active_quick_filters().all(|f| f.matches(&self.repos[id], now)))
+            .filter(|&id| created_filters.iter().all(|f| f.matches(self.repos[id].created_at)))
+            .collect();
+
+        self.filtered_items = filter::filter_structured(&ids, &field_query, |&id| filter::SearchFields {
+            name: self.repos[id].name.clone(),
+            description: self.repos[id].description.clone(),
+        });
+
+        // The best matches stay first within each section
+        let group_by = self.group_by;
+        grouping::partition(&mut self.filtered_items, |&id| group_by.section(&self.repos[id]));
+    }
+
+    /// Returns the sections of the filtered items with their counts, empty without grouping
+    fn sections(&self) -> Vec<(Section, usize)> {
+        grouping::sections(&self.filtered_items, |&id| self.group_by.section(&self.repos[id]))
+    }
+
+    /// Returns the section whose header is shown above the filtered item at `index`.
+    ///
+    /// The first displayed item always gets one, so the section is known while scrolling.
+    fn header_before(&self, index: usize) -> Option<Section> {
+        let section = self.group_by.section(&self.repos[self.filtered_items[index]]);
+        let previous = index
+            .checked_sub(1)
+            .filter(|&previous| previous >= self.scroll_offset)
+            .and_then(|previous| self.group_by.section(&self.repos[self.filtered_items[previous]]));
+
+        section.filter(|&section| previous != Some(section))
+    }
+
+    /// Filters the items like `filter_items`, measuring how long it took
+    fn filter_items_timed(&mut self) -> FilterTiming {
+        let before = self.filtered_items.len();
+        let start = Instant::now();
+        self.filter_items();
+
+        FilterTiming {
+            elapsed: start.elapsed(),
+            before,
+            after: self.filtered_items.len(),
+        }
+    }
+
+    /// Filters for an edited query, or waits for more typing with a debounce
+    fn query_changed(&mut self) {
+        if self.filter_debounce.is_zero() {
+            self.update_filter();
+        } else {
+            self.filter_pending_since = Some(Instant::now());
+        }
+    }
+
+    /// Filters for the edited query once typing paused for the debounce, returning if it did
+    fn filter_if_idle(&mut self, now: Instant) -> bool {
+        match self.filter_pending_since {
+            Some(since) if now.duration_since(since) >= self.filter_debounce => {
+                self.update_filter();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update_filter(&mut self) {
+        self.filter_pending_since = None;
+
+        // Only read the clock when the timing is logged, so typing costs nothing extra otherwise
+        if log::log_enabled!(log::Level::Debug) {
+            let timing = self.filter_items_timed();
+            log::debug!(
+                "query {:?}: {} -> {} items in {:?}",
+                self.query,
+                timing.before,
+                timing.after,
+                timing.elapsed
+            );
+        } else {
+            self.filter_items();
+        }
+
+        self.count_sources();
+
+        // The common prefix changes as the filter narrows. It is cut from the start of the rows,
+        // so it only applies if every row starts with its name, unlike rows with a marker first
+        let rows_start_with_names = self
+            .filtered_items
+            .iter()
+            .all(|&id| self.items[id].starts_with(self.repos[id].name.as_str()));
+        self.common_prefix = if self.strip_common_prefix && rows_start_with_names {
+            formatter::common_name_prefix(self.filtered_items.iter().map(|&id| self.repos[id].name.as_str()))
+        } else {
+            String::new()
+        };
+
+        // The common prefix line takes a row from the list
+        self.fit_max_display();
+        self.clamp_selection();
+    }
+
+    /// Keeps the selection within the matches and visible after they changed.
+    ///
+    /// A list which got shorter is scrolled back so it leaves no empty rows below its end.
+    fn clamp_selection(&mut self) {
+        let count = self.filtered_items.len();
+        self.selected_index = self.selected_index.min(count.saturating_sub(1));
+        self.scroll_offset = self.scroll_offset.min(count.saturating_sub(self.max_display));
+
+        self.scroll_to_selection();
+    }
+
+    /// Returns how many rows the items from the scroll offset through `index` take, with their section headers
+    fn rows_through(&self, index: usize) -> usize {
+        (self.scroll_offset..=index)
+            .map(|i| 1 + usize::from(self.header_before(i).is_some()))
+            .sum()
+    }
+
+    /// Scrolls the selected item into view, counting the rows of the section headers above it
+    fn scroll_to_selection(&mut self) {
+        if self.selected_index < self.scroll_offset {
+            self.scroll_offset = self.selected_index;
+        } else if self.selected_index >= self.scroll_offset + self.max_display {
+            self.scroll_offset = self.selected_index + 1 - self.max_display;
+        }
+
+        while self.scroll_offset < self.selected_index && self.rows_through(self.selected_index) > self.max_display {
+            self.scroll_offset += 1;
+        }
+    }
+
+    /// Cycles between showing all repositories, only GitHub ones and only GitLab ones
+    fn cycle_source_filter(&mut self) {
+        self.source_filter = match self.source_filter {
+            None => Some(RepoSource::GitHub),
+            Some(RepoSource::GitHub) => Some(RepoSource::GitLab),
+            Some(_) => None,
+        };
+        self.update_filter();
+    }
+
+    /// Counts the matching and total repositories of each platform
+    fn count_sources(&mut self) {
+        for (source, matching, total) in &mut self.source_counts {
+            let source = *source;
+            *total = self.repos.iter().filter(|repo| repo.source == source).count();
+            *matching = self
+                .filtered_items
+                .iter()
+                .filter(|&&id| self.repos[id].source == source)
+                .count();
+        }
+    }
+
+    // Returns the byte offset of the character before the query cursor
+    fn previous_char_boundary(&self) -> usize {
+        self.query[..self.cursor_pos]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    // Returns the byte offset after the character at the query cursor
+    fn next_char_boundary(&self) -> usize {
+        self.query[self.cursor_pos..]
+            .chars()
+            .next()
+            .map_or(self.cursor_pos, |c| self.cursor_pos + c.len_utf8())
+    }
+
+    fn move_cursor_up(&mut self) {
+        if self.wrap && !self.filtered_items.is_empty() && self.selected_index == 0 {
+            // Scroll to the end so the last item is at the bottom of the list
+            self.select_index(self.filtered_items.len() - 1);
+        } else if !self.filtered_items.is_empty() && self.selected_index > 0 {
+            self.selected_index -= 1;
+
+            // Adjust scroll offset if needed
+            if self.selected_index < self.scroll_offset {
+                self.scroll_offset = self.selected_index;
+            }
+        }
+    }
+
+    /// Fits the list into a terminal of the given height, keeping the selection on screen
+    fn resize(&mut self, height: u16) {
+        self.height = Some(height);
+        self.fit_max_display();
+
+        // A shrink can push the selection below the last visible row
+        self.select_index(self.selected_index);
+    }
+
+    /// Fits the number of displayed items into the terminal height, if it is known
+    fn fit_max_display(&mut self) {
+        if let Some(height) = self.height {
+            // The prompt, status and separator lines, and the common prefix above the items
+            let reserved = 3 + usize::from(!self.common_prefix.is_empty());
+            self.max_display = (height as usize).saturating_sub(reserved).max(1);
+        }
+    }
+
+    /// Selects the filtered item at `index`, clamped to the list, scrolling it into view
+    fn select_index(&mut self, index: usize) {
+        if self.filtered_items.is_empty() {
+            return;
+        }
+
+        self.selected_index = index.min(self.filtered_items.len() - 1);
+        self.scroll_to_selection();
+    }
+
+    fn move_cursor_down(&mut self) {
+        if self.wrap && !self.filtered_items.is_empty() && self.selected_index == self.filtered_items.len() - 1 {
+            self.select_index(0);
+        } else if !self.filtered_items.is_empty() && self.selected_index < self.filtered_items.len() - 1 {
+            self.selected_index += 1;
+            self.scroll_to_selection();
+        }
+    }
+
+    /// Returns the character positions in a displayed item matched by the text query.
+    ///
+    /// The query matches the search text, so its positions are moved past the owner column
+    /// and the elided common prefix, and onto the displayed alias.
+    fn displayed_match_positions(&self, id: usize, text_query: &str, owner_chars: usize, elided: bool) -> Vec<usize> {
+        let item_chars = self.items[id].chars().count();
+        let (hidden, ellipsis) = if elided {
+            (self.common_prefix.chars().count(), self.ellipsis.chars().count())
+        } else {
+            (0, 0)
+        };
+        // The displayed alias follows the item after " ~", while the search text has a space
+        let alias_start = owner_chars + ellipsis + item_chars - hidden + 2;
+
+        filter::match_indices(&self.search_text(id), text_query)
+            .into_iter()
+            .filter_map(|pos| {
+                if pos < item_chars {
+                    (pos >= hidden).then(|| owner_chars + ellipsis + pos - hidden)
+                } else {
+                    (self.show_aliases && pos > item_chars).then(|| alias_start + pos - item_chars - 1)
+                }
+            })
+            .collect()
+    }
+
+    fn render<W: Write>(&self, screen: &mut W) -> io::Result<()> {
+        // Get terminal size
+        let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+
+        // Clear screen
+        write!(screen, "{}{}", clear::All, cursor::Goto(1, 1))?;
+
+        // Calculate available space for items (accounting for prompt and status lines)
+        let mut available_lines = (height as usize).saturating_sub(3); // Prompt line (with input) + status line + separator line
+
+        // Show the elided common prefix once above the items
+        if !self.common_prefix.is_empty() {
+            write!(
+                screen,
+                "{}  {}{} (common prefix){}\r\n",
+                color::Fg(color::Yellow),
+                self.common_prefix,
+                self.ellipsis,
+                style::Reset
+            )?;
+            available_lines = available_lines.saturating_sub(1);
+        }
+
+        // Adjust max_display based on available space, section headers take a line each
+        let mut end_idx = self.scroll_offset;
+        let mut used_lines = 0;
+        while end_idx < self.filtered_items.len() {
+            let lines = 1 + usize::from(self.header_before(end_idx).is_some());
+            if used_lines + lines > available_lines {
+                break;
+            }
+            used_lines += lines;
+            end_idx += 1;
+        }
+        let sections = self.sections();
+
+        // Every repository counts for the owner column, so it keeps its width while scrolling
+        let owner_width = if self.show_owner_source {
+            formatter::owner_column_width(self.repos.iter().map(|repo| repo.owner.as_str()))
+        } else {
+            0
+        };
+
+        // Only the text terms of the query are highlighted, date and field terms match elsewhere
+        let (text_query, _) = filter::split_created_terms(&self.query);
+        let (text_query, _) = filter::split_field_terms(&text_query);
+
+        // Display items
+        for i in self.scroll_offset..end_idx {
+            if let Some(section) = self.header_before(i) {
+                let count = sections.iter().find(|(s, _)| *s == section).map_or(0, |(_, count)| *count);
+                write!(
+                    screen,
+                    "{}── {} ({}) ──{}\r\n",
+                    color::Fg(color::Magenta),
+                    section.label(),
+                    count,
+                    style::Reset
+                )?;
+            }
+
+            let id = self.filtered_items[i];
+            let item = &self.items[id];
+            let (item, elided) = match item.strip_prefix(self.common_prefix.as_str()) {
+                Some(rest) if !self.common_prefix.is_empty() => (format!("{}{}", self.ellipsis, rest), true),
+                _ => (item.clone(), false),
+            };
+
+            // The alias is only displayed, a selection is always the plain formatted item
+            let item = match self.aliases.get(&self.repos[id]) {
+                Some(alias) if self.show_aliases => format!("{} ~{}", item, alias),
+                _ => item,
+            };
+
+            let item = match self.local_states.get(&self.repos[id].url).and_then(|state| state.badge()) {
+                Some(badge) => format!("{} {}", item, badge),
+                None => item,
+            };
+
+            let owner_prefix = if self.show_owner_source {
+                let repo = &self.repos[id];
+                formatter::owner_source_prefix(&repo.owner, repo.source, owner_width)
+            } else {
+                String::new()
+            };
+            let item = format!("{}{}", owner_prefix, item);
+
+            // Calculate available width for text (accounting for the prefix)
+            let prefix_len = 2; // Both "> " and "  " are 2 characters
+            let available_width = (width as usize).saturating_sub(prefix_len + 5); // Extra buffer for emojis and safety
+
+            // Truncate item text if it's too long, by display width since emojis take two cells
+            let (display_text, was_cut) = formatter::truncate_end(&item, available_width, &self.ellipsis);
+            let kept_chars = if was_cut {
+                display_text.chars().count().saturating_sub(self.ellipsis.chars().count())
+            } else {
+                display_text.chars().count()
+            };
+
+            let positions: Vec<usize> = self
+                .displayed_match_positions(id, &text_query, owner_prefix.chars().count(), elided)
+                .into_iter()
+                .filter(|&pos| pos < kept_chars)
+                .collect();
+
+            // Marked repositories have a star after the selection arrow
+            let mark = if self.marked.contains(&self.repos[id].url) { '*' } else { ' ' };
+
+            // Highlight selected item
+            if i == self.selected_index {
+                let on = format!("{}", color::Fg(color::Cyan));
+                let off = format!("{}", color::Fg(color::Green));
+                write!(
+                    screen,
+                    "{}{}>{}{}{}",
+                    color::Fg(color::Green),
+                    style::Bold,
+                    mark,
+                    formatter::highlight_chars(&display_text, &positions, &on, &off),
+                    style::Reset
+                )?;
+            } else {
+                let on = format!("{}", color::Fg(color::Cyan));
+                let off = format!("{}", color::Fg(color::Reset));
+                write!(screen, " {}{}", mark, formatter::highlight_chars(&display_text, &positions, &on, &off))?;
+            }
+
+            write!(screen, "\r\n")?;
+        }
+
+        // Say why the list is empty, centered in the item area
+        if let Some(message) = self.empty_list_message() {
+            let top = 1 + u16::from(!self.common_prefix.is_empty());
+            let row = top + (available_lines / 2) as u16;
+            let column = 1 + (width as usize).saturating_sub(message.width()) as u16 / 2;
+            write!(
+                screen,
+                "{}{}{}{}{}",
+                cursor::Goto(column, row),
+                style::Faint,
+                message,
+                style::Reset,
+                cursor::Goto(1, top)
+            )?;
+        }
+
+        // The bottom three rows are the message line, the count line and the prompt, so the
+        // items get the height minus three. The message line stays reserved while it is
+        // empty, so the list does not jump when messages come and go.
+        let message_row = height.saturating_sub(2).max(1);
+        write!(screen, "{}", cursor::Goto(1, message_row))?;
+        match self.message_line(width as usize) {
+            Some((true, error)) => write!(screen, "{}{}{}", color::Fg(color::Red), error, style::Reset)?,
+            Some((false, status)) => write!(screen, "{}{}{}", color::Fg(color::Yellow), status, style::Reset)?,
+            None => {}
+        }
+        write!(screen, "{}", cursor::Goto(1, message_row + 1))?;
+
+        // Create the status text with count, and the archived mode unless everything is shown
+        let count_text = match self.archived_filter {
+            filter::ArchivedFilter::All => format!("{}/{}", self.filtered_items.len(), self.items.len()),
+            mode => format!("{}/{} ({})", self.filtered_items.len(), self.items.len(), mode.label()),
+        };
+
+        // And the platform shown with Ctrl+S, unless it is both
+        let count_text = match self.source_filter {
+            Some(source) => format!("{} [{:?}]", count_text, source),
+            None => count_text,
+        };
+
+        // And the marked repositories, which are copied even when hidden
+        let count_text = match self.marked_text() {
+            Some(marked) => format!("{} · {}", count_text, marked),
+            None => count_text,
+        };
+
+        // Break the count down by platform when both have repositories and it fits
+        let both_platforms = self.source_counts.iter().all(|&(_, _, total)| total > 0);
+        let per_source = formatter::source_match_counts(&self.source_counts);
+        let count_text = if both_platforms && count_text.width() + per_source.width() + 5 < width as usize {
+            format!("{} · {}", count_text, per_source)
+        } else {
+            count_text
+        };
+
+        // Show the presets after the count when they fit, with the active ones highlighted
+        let labels = quick_filter::bar_labels(&self.quick_filters);
+        let bar_width: usize = labels.iter().map(|label| label.width() + 2).sum();
+        let show_bar = !labels.is_empty() && count_text.width() + bar_width + 5 < width as usize;
+
+        // Display status line at the bottom (format: "12/12 ───────────────")
+        write!(screen, "{}{}", color::Fg(color::Yellow), count_text)?;
+        let mut line_width = count_text.width();
+        if show_bar {
+            for (i, label) in labels.iter().enumerate() {
+                if self.active_quick_filters.contains(&i) {
+                    write!(screen, "  {}{}{}{}", color::Fg(color::Cyan), style::Bold, label, style::Reset)?;
+                } else {
+                    write!(screen, "  {}{}", color::Fg(color::LightBlack), label)?;
+                }
+            }
+            line_width += bar_width;
+        }
+        write!(
+            screen,
+            " {}{}",
+            color::Fg(color::Blue),
+            "─".repeat((width as usize).saturating_sub(line_width + 1))
+        )?;
+        write!(screen, "{}", style::Reset)?;
+
+        // Display prompt at the bottom with input text on the same line
+        write!(screen, "{}{}>{} ", cursor::Goto(1, height), color::Fg(color::Blue), style::Reset)?;
+
+        // Ask for a new token instead of the query, never showing what is typed
+        if let Some((source, input)) = &self.token_prompt {
+            write!(
+                screen,
+                "{}{:?} rejected the token, enter a new one (Esc to skip): {}{}",
+                color::Fg(color::Red),
+                source,
+                style::Reset,
+                input.masked()
+            )?;
+            screen.flush()?;
+            return Ok(());
+        }
+
+        // Edit an alias instead of the query
+        if let Some((url, alias)) = &self.alias_prompt {
+            write!(
+                screen,
+                "{}Alias for {} (empty to remove): {}{}",
+                color::Fg(color::Yellow),
+                self.repo_by_url(url).map_or(url.as_str(), |repo| repo.name.as_str()),
+                style::Reset,
+                alias
+            )?;
+            screen.flush()?;
+            return Ok(());
+        }
+
+        // Display the part of the query around the cursor on the same line as the prompt
+        // Account for the prompt (2 characters: '>' and space)
+        let available_width = (width as usize).saturating_sub(2);
+        let (display_query, cursor_column) =
+            formatter::query_window(&self.query, self.cursor_pos, available_width, &self.ellipsis);
+        write!(screen, "{}", display_query)?;
+
+        // Position cursor at the current position (after the prompt), by display width
+        write!(
+            screen,
+            "{}",
+            cursor::Goto(cursor_column as u16 + 3, height)
+        )?;
+
+        // Ensure all output is flushed to the screen
+        screen.flush()?;
+        Ok(())
+    }
+
+    /// Run the fuzzy finder with support for background updates
+    pub fn run(&mut self) -> Option<(FinderAction, Option<RepoData>)> {
+        // Set up terminal
+        let mut screen = stdout()
+            .into_raw_mode()
+            .unwrap()
+            .into_alternate_screen()
+            .unwrap();
+
+        // Show cursor and perform initial render
+        write!(screen, "{}", cursor::Show).unwrap();
+        screen.flush().unwrap();
+        self.apply_updates();
+        self.render(&mut screen).unwrap();
+
+        // Read keys from the terminal without buffering, so waiting for input can time out
+        let tty = termion::get_tty().unwrap();
+        let mut keys = tty.try_clone().unwrap().keys();
+
+        // Coalesce fast typing into one filter pass once it pauses
+        self.filter_debounce = FILTER_DEBOUNCE;
+
+        // Size the list to the terminal, and again whenever it is resized
+        let mut size = termion::terminal_size().unwrap_or((80, 24));
+        self.resize(size.1);
+
+        // For non-blocking input
+        let mut last_render = std::time::Instant::now();
+        let render_interval = Duration::from_millis(100); // Refresh UI every 100ms
+
+        loop {
+            // Check if it's time to re-render (for status updates)
+            let now = std::time::Instant::now();
+            if now.duration_since(last_render) >= render_interval {
+                self.spinner_frame = self.spinner_frame.wrapping_add(1);
+                self.apply_updates();
+                self.render(&mut screen).unwrap();
+                last_render = now;
+            }
+
+            // Replayed keys come first, then wait briefly for input, which also prevents CPU hogging
+            let key = match self.next_replayed_key() {
+                Some(key) => key,
+                None => {
+                    if !Self::wait_for_input(&tty, Duration::from_millis(10)) {
+                        // Filter on the first idle tick after typing
+                        let mut changed = self.filter_if_idle(std::time::Instant::now());
+
+                        // A resize while idle sends no key, so compare the size on every tick
+                        let new_size = termion::terminal_size().unwrap_or(size);
+                        if new_size != size {
+                            size = new_size;
+                            self.resize(size.1);
+                            changed = true;
+                        }
+
+                        if changed {
+                            self.render(&mut screen).unwrap();
+                        }
+                        continue;
+                    }
+                    match keys.next() {
+                        Some(Ok(key)) => key,
+                        _ => continue,
+                    }
+                }
+            };
+
+            self.record_key(key);
+
+            match self.handle_key(key) {
+                KeyOutcome::Select(action) => {
+                    // Store the selected repository, there is none when opening the basket without matches
+                    let selected = self.selected_repo().cloned();
+                    self.save_recording();
+                    self.save_query();
+
+                    // Properly restore terminal state before returning, dropping the screen leaves
+                    // raw mode so the caller can read whole lines, like the action menu does
+                    Self::cleanup_terminal(&mut screen);
+                    drop(screen);
+
+                    // Return the selected repository to be processed
+                    return Some((action, selected));
+                }
+                KeyOutcome::Quit => {
+                    self.save_recording();
+                    self.save_query();
+                    Self::exit_program(&mut screen, "\nExiting...");
+                }
+                KeyOutcome::Continue => {}
+            }
+
+            // Re-render after each key press
+            self.render(&mut screen).unwrap();
+        }
+    }
+
+    /// Handles a key pressed in the finder or one of its prompts
+    fn handle_key(&mut self, key: Key) -> KeyOutcome {
+        // Anything but typing acts on the matches, so the final query must be filtered first
+        let edits_query = matches!(
+            key,
+            Key::Backspace | Key::Delete | Key::Ctrl('w') | Key::Ctrl('u')
+        ) || matches!(key, Key::Char(c) if c != '\n' && c != '\r' && !(c == '\t' && self.multi_select));
+        if !edits_query && self.filter_pending_since.is_some() {
+            self.update_filter();
+        }
+
+        if self.token_prompt.is_some() {
+            self.handle_token_prompt_key(key);
+            return KeyOutcome::Continue;
+        }
+
+        if self.alias_prompt.is_some() {
+            self.handle_alias_prompt_key(key);
+            return KeyOutcome::Continue;
+        }
+
+        // Any other key cancels confirming the basket windows
+        let confirming = std::mem::take(&mut self.confirm_basket_windows);
+
+        match key {
+            // Return selected item but don't exit the program
+            Key::Char('\n') | Key::Char('\r') | Key::Ctrl('g') | Key::Ctrl('f') if !self.filtered_items.is_empty() => {
+                let action = match key {
+                    Key::Ctrl('g') => FinderAction::GitTui,
+                    Key::Ctrl('f') => FinderAction::Tree,
+                    _ if self.multi_select => FinderAction::CopyUrls,
+                    _ => FinderAction::Open,
+                };
+                return KeyOutcome::Select(action);
+            }
+            // Copy the SSH URL of the selected repository without leaving the finder
+            Key::Ctrl('y') => self.copy_selected_url(),
+            // Nothing to select, don't add the newline to the query
+            Key::Char('\n') | Key::Char('\r') => {}
+            // Open every repository in the basket in its own window, or say why not
+            Key::Ctrl('e') if self.request_basket_windows(confirming) => {
+                return KeyOutcome::Select(FinderAction::BasketWindows);
+            }
+            // Add the selected repository to the basket or remove it
+            Key::Ctrl('b') if !self.filtered_items.is_empty() => {
+                self.toggle_in_basket();
+            }
+            // Mark the selected repository for copying its URL with the others
+            Key::Char('\t') if self.multi_select => self.toggle_marked(),
+            // Edit the alias of the selected repository
+            Key::Ctrl('t') if !self.filtered_items.is_empty() => {
+                let repo = &self.repos[self.filtered_items[self.selected_index]];
+                let alias = self.aliases.get(repo).unwrap_or_default().to_string();
+                self.alias_prompt = Some((repo.url.clone(), alias));
+            }
+            // Toggle the owner and source columns
+            Key::Ctrl('o') => {
+                self.show_owner_source = !self.show_owner_source;
+            }
+            // Cycle between all, active only and archived only repositories
+            Key::Ctrl('x') => {
+                self.archived_filter = self.archived_filter.next();
+                self.update_filter();
+            }
+            Key::Ctrl('s') => self.cycle_source_filter(),
+            Key::F(5) => self.request_refresh(RefreshRequest::Refresh),
+            Key::Ctrl('r') => self.request_refresh(RefreshRequest::Restart),
+            Key::Alt(c @ '1'..='9') => {
+                self.toggle_quick_filter(c as usize - '1' as usize);
+            }
+            Key::Char(c) => {
+                // Add character to query at cursor position
+                self.query.insert(self.cursor_pos, c);
+                self.cursor_pos += c.len_utf8();
+                self.query_changed();
+            }
+            // Remove character before cursor position
+            Key::Backspace if !self.query.is_empty() && self.cursor_pos > 0 => {
+                self.cursor_pos = self.previous_char_boundary();
+                self.query.remove(self.cursor_pos);
+                self.query_changed();
+            }
+            Key::Up => {
+                self.move_cursor_up();
+            }
+            Key::Down => {
+                self.move_cursor_down();
+            }
+            // Move cursor left if possible
+            Key::Left if self.cursor_pos > 0 => {
+                self.cursor_pos = self.previous_char_boundary();
+            }
+            // Move cursor right if possible
+            Key::Right if self.cursor_pos < self.query.len() => {
+                self.cursor_pos = self.next_char_boundary();
+            }
+            // Delete the word before the cursor
+            Key::Ctrl('w') if self.cursor_pos > 0 => {
+                (self.query, self.cursor_pos) = delete_word_before(&self.query, self.cursor_pos);
+                self.query_changed();
+            }
+            // Clear the whole query
+            Key::Ctrl('u') if !self.query.is_empty() => {
+                self.query.clear();
+                self.cursor_pos = 0;
+                self.query_changed();
+            }
+            // Remove character at cursor position
+            Key::Delete if !self.query.is_empty() && self.cursor_pos < self.query.len() => {
+                self.query.remove(self.cursor_pos);
+                self.query_changed();
+            }
+            // Jump a page of items up or down
+            Key::PageUp => {
+                self.select_index(self.selected_index.saturating_sub(self.max_display));
+            }
+            Key::PageDown => {
+                self.select_index(self.selected_index + self.max_display);
+            }
+            // Jump to the first or last item
+            Key::Home => {
+                self.select_index(0);
+            }
+            Key::End => {
+                self.select_index(usize::MAX);
+            }
+            Key::CtrlHome | Key::Ctrl('a') => {
+                // Move cursor to the beginning of the query
+                self.cursor_pos = 0;
+            }
+            Key::CtrlEnd => {
+                // Move cursor to the end of the query
+                self.cursor_pos = self.query.len();
+            }
+            Key::Ctrl('c') | Key::Esc => {
+                // While an operation is running, quitting needs a second press
+                let busy = self.confirm_quit_when_busy && crate::terminal::is_busy();
+                if !busy || self.quit_presses.press(crate::terminal::now_ms()) {
+                    return KeyOutcome::Quit;
+                }
+                self.status_message = Some("Operation in progress, press again to quit".to_string());
+            }
+            _ => {}
+        }
+        KeyOutcome::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    #[test]
+    fn test_basket_loads_into_items() {
+        let mut basket = Basket::default();
+        basket.add(&RepoData::named("research-a"));
+        basket.add(&RepoData::named("research-b"));
+
+        let mut finder = FuzzyFinder::new(basket.repositories.clone(), |r| r.name.clone());
+        finder.set_basket(basket, true);
+
+        assert_eq!(finder.items, vec!["research-a", "research-b"]);
+        assert_eq!(finder.filtered_items, vec![0, 1]);
+        assert!(finder.basket.contains(&finder.repos[1]));
+    }
+
+    #[test]
+    fn test_empty_query_keeps_all_items() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("go-microservices"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+
+        finder.set_query("   ");
+        assert_eq!(finder.filtered_items, vec![0, 1, 2]);
+
+        // Navigation works on the unfiltered list like on a filtered one
+        finder.move_cursor_down();
+        finder.move_cursor_down();
+        finder.move_cursor_down();
+        assert_eq!(finder.selected_index, 2);
+
+        // Clearing a query brings back every item
+        finder.set_query("go");
+        assert_eq!(finder.filtered_items, vec![1]);
+        assert_eq!(finder.selected_index, 0);
+        finder.set_query("");
+        assert_eq!(finder.filtered_items, vec![0, 1, 2]);
+    }
+
+    /// Compares the empty query against copying all ids through the filter, run with
+    /// `cargo test --release bench_empty_query -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_empty_query() {
+        let repos: Vec<RepoData> = (0..50_000).map(|i| RepoData::named(&format!("project-{}", i))).collect();
+        let finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        let rounds = 500;
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            let ids: Vec<usize> = (0..finder.items.len()).collect();
+            black_box(filter::filter_indexed_scored(&ids, &finder.index, "", 0, |&id| finder.search_text(id)));
+        }
+        let before = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            black_box(finder.matching_ids(""));
+        }
+        let after = start.elapsed();
+
+        println!("empty query over 50000 items: {:?} before, {:?} after per round", before / rounds, after / rounds);
+        assert_eq!(finder.matching_ids("").len(), 50_000);
+    }
+
+    #[test]
+    fn test_filter_timing_counts_items() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("go-microservices"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+
+        finder.query = "rust".to_string();
+        let timing = finder.filter_items_timed();
+        assert_eq!((timing.before, timing.after), (3, 2));
+
+        finder.query = "rust cli".to_string();
+        let timing = finder.filter_items_timed();
+        assert_eq!((timing.before, timing.after), (2, 1));
+        assert!(timing.elapsed < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_source_counts_follow_filter() {
+        let gitlab_repo = RepoData { source: RepoSource::GitLab, ..RepoData::named("rust-gitlab") };
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("go-microservices"), gitlab_repo];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        assert_eq!(formatter::source_match_counts(&finder.source_counts), "GH 2/2 · GL 1/1");
+
+        finder.set_query("go");
+        assert_eq!(formatter::source_match_counts(&finder.source_counts), "GH 1/2 · GL 0/1");
+    }
+
+    #[test]
+    fn test_visibility_sections_follow_filter() {
+        let private = |name: &str| RepoData { is_private: true, ..RepoData::named(name) };
+        let repos = vec![private("rust-api"), RepoData::named("rust-web"), private("notes"), RepoData::named("go-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        finder.set_group_by(GroupBy::Visibility);
+
+        assert_eq!(finder.filtered_items, vec![1, 3, 0, 2]);
+        assert_eq!(finder.sections(), vec![(Section::Public, 2), (Section::Private, 2)]);
+        assert_eq!(finder.header_before(0), Some(Section::Public));
+        assert_eq!(finder.header_before(1), None);
+        assert_eq!(finder.header_before(2), Some(Section::Private));
+
+        // Headers are not items, so moving down goes straight into the next section
+        finder.move_cursor_down();
+        finder.move_cursor_down();
+        assert_eq!(finder.selected_repo().unwrap().name, "rust-api");
+
+        // Section headers take rows too, so the list scrolls before the selection reaches the last row
+        finder.max_display = 3;
+        finder.select_index(0);
+        finder.move_cursor_down();
+        assert_eq!((finder.selected_index, finder.scroll_offset), (1, 0));
+        finder.move_cursor_down();
+        assert_eq!((finder.selected_index, finder.scroll_offset), (2, 2));
+        assert_eq!(finder.rows_through(2), 2);
+        finder.select_index(0);
+        finder.select_index(3);
+        assert_eq!(finder.scroll_offset, 2);
+
+        // Sections without matches are hidden
+        finder.set_query("notes");
+        assert_eq!(finder.sections(), vec![(Section::Private, 1)]);
+    }
+
+    #[test]
+    fn test_match_positions_follow_display() {
+        let mut aliases = Aliases::default();
+        aliases.set(&RepoData::named("rust-web"), "webby");
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("rust-web")], |r| r.name.clone());
+        finder.set_aliases(aliases, true);
+
+        // Displayed as "rust-web ~webby"
+        assert_eq!(finder.displayed_match_positions(0, "web", 0, false), vec![5, 6, 7]);
+        assert_eq!(finder.displayed_match_positions(0, "webby", 0, false), vec![10, 11, 12, 13, 14]);
+
+        // Displayed as "user/ [GH] rust-web ~webby", the owner column shifts everything
+        assert_eq!(finder.displayed_match_positions(0, "rw", 11, false), vec![11, 16]);
+
+        // Displayed as "…web ~webby" with "rust-" elided, whose matches are hidden
+        finder.common_prefix = "rust-".to_string();
+        assert_eq!(finder.displayed_match_positions(0, "rw", 0, true), vec![1]);
+        assert_eq!(finder.displayed_match_positions(0, "webby", 0, true), vec![6, 7, 8, 9, 10]);
+
+        // Hidden aliases are still searched, but not highlighted
+        finder.show_aliases = false;
+        assert!(finder.displayed_match_positions(0, "webby", 0, false).is_empty());
+    }
+
+    #[test]
+    fn test_cycle_source_filter() {
+        let gitlab = |name: &str| RepoData { source: RepoSource::GitLab, ..RepoData::named(name) };
+        let repos = vec![RepoData::named("rust-web"), gitlab("rust-api"), gitlab("notes")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        finder.set_query("rust");
+
+        // GitHub, then GitLab, then both again, always with the query on top
+        finder.handle_key(Key::Ctrl('s'));
+        assert_eq!(finder.filtered_items, vec![0]);
+        finder.handle_key(Key::Ctrl('s'));
+        assert_eq!(finder.filtered_items, vec![1]);
+        finder.handle_key(Key::Ctrl('s'));
+        assert_eq!(finder.source_filter, None);
+        assert_eq!(finder.filtered_items.len(), 2);
+    }
+
+    #[test]
+    fn test_tab_marks_repositories_for_copying() {
+        let repos = vec![RepoData::named("rust-web"), RepoData::named("notes"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+
+        // Without --multi Tab is typed into the query like before
+        finder.handle_key(Key::Char('\t'));
+        assert_eq!(finder.query, "\t");
+        finder.handle_key(Key::Ctrl('u'));
+        finder.set_multi_select(true);
+
+        // Marks are kept in list order, whatever order they were made in
+        finder.select_index(2);
+        finder.handle_key(Key::Char('\t'));
+        finder.select_index(0);
+        finder.handle_key(Key::Char('\t'));
+        assert_eq!(finder.query, "");
+        assert_eq!(finder.handle_key(Key::Char('\n')), KeyOutcome::Select(FinderAction::CopyUrls));
+
+        // Marks hidden by the query are still copied, so the count line tells about them
+        assert_eq!(finder.marked_text().as_deref(), Some("2 marked"));
+        finder.set_query("web");
+        assert_eq!(finder.marked_text().as_deref(), Some("2 marked (1 hidden)"));
+
+        let names: Vec<String> = finder.take_marked_repositories().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["rust-web", "rust-cli"]);
+        assert_eq!(finder.marked_text(), None);
+
+        // Taking unmarks them, so without marks Enter falls back to the selected repository
+        let names: Vec<String> = finder.take_marked_repositories().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["rust-web"]);
+    }
+
+    #[test]
+    fn test_large_basket_windows_need_confirmation() {
+        let mut basket = Basket::default();
+        for i in 0..=multiplexer::CONFIRM_ABOVE {
+            basket.add(&RepoData::named(&format!("repo-{}", i)));
+        }
+        let mut finder = FuzzyFinder::new(Vec::new(), |r| r.name.clone());
+        finder.set_basket(basket, false);
+
+        // Nothing opens without a multiplexer
+        assert_eq!(finder.handle_key(Key::Ctrl('e')), KeyOutcome::Continue);
+        finder.set_basket_windows(true);
+
+        assert_eq!(finder.handle_key(Key::Ctrl('e')), KeyOutcome::Continue);
+        assert!(finder.status_message.as_deref().unwrap().starts_with("Open 6 windows?"));
+        assert_eq!(finder.handle_key(Key::Ctrl('e')), KeyOutcome::Select(FinderAction::BasketWindows));
+
+        // Another key in between cancels the confirmation
+        assert_eq!(finder.handle_key(Key::Ctrl('e')), KeyOutcome::Continue);
+        finder.handle_key(Key::Down);
+        assert_eq!(finder.handle_key(Key::Ctrl('e')), KeyOutcome::Continue);
+    }
+
+    #[test]
+    fn test_transient_status_message_expires() {
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        let now = Instant::now();
+
+        finder.set_transient_status_message("Copied SSH URL".to_string(), now);
+        finder.expire_status_message(now + Duration::from_secs(1));
+        assert_eq!(finder.status_message.as_deref(), Some("Copied SSH URL"));
+        finder.expire_status_message(now + TRANSIENT_STATUS_DURATION);
+        assert_eq!(finder.status_message, None);
+
+        // Status messages from the background refresh stay until replaced
+        finder.set_transient_status_message("Copied SSH URL".to_string(), now);
+        finder.set_status_message(Some("Fetching GitLab repositories...".to_string()));
+        finder.expire_status_message(now + TRANSIENT_STATUS_DURATION);
+        assert_eq!(finder.status_message.as_deref(), Some("Fetching GitLab repositories..."));
+    }
+
+    #[test]
+    fn test_errors_and_rejected_tokens_from_updates() {
+        let (tx, rx) = mpsc::channel(8);
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        finder.set_update_receiver(rx);
+        finder.set_token_retry(|_, _| {});
+
+        tx.try_send(FinderUpdate::Error("GitHub error: connection reset".to_string())).unwrap();
+        finder.apply_updates();
+        assert_eq!(finder.message_line(80), Some((true, "✖ GitHub error: connection reset".to_string())));
+
+        tx.try_send(FinderUpdate::AuthFailed(RepoSource::GitLab)).unwrap();
+        finder.apply_updates();
+        assert!(matches!(finder.token_prompt, Some((RepoSource::GitLab, _))));
+
+        tx.try_send(FinderUpdate::ClearMessages).unwrap();
+        finder.apply_updates();
+        assert_eq!(finder.message_line(80), None);
+    }
+
+    #[test]
+    fn test_message_line_prefers_errors() {
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        assert_eq!(finder.message_line(80), None);
+
+        finder.set_status_message(Some("Fetching GitLab repositories".to_string()));
+        assert_eq!(finder.message_line(80), Some((false, ">Fetching GitLab repositories".to_string())));
+
+        finder.set_error_message(Some("GitLab API error".to_string()));
+        assert_eq!(finder.message_line(80), Some((true, "✖ GitLab API error".to_string())));
+
+        // Long messages are cut to the terminal width instead of wrapping into the count line
+        let (_, line) = finder.message_line(10).unwrap();
+        assert_eq!(line.width(), 9);
+        assert!(line.ends_with(formatter::DEFAULT_ELLIPSIS));
+    }
+
+    #[test]
+    fn test_token_prompt_keys_are_not_recorded() {
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        finder.set_recording(PathBuf::from("unused.json"));
+        finder.set_token_retry(|_, _| {});
+
+        finder.record_key(Key::Char('n'));
+        finder.request_token(RepoSource::GitHub);
+        for c in "ghp_secret\n".chars() {
+            finder.record_key(Key::Char(c));
+            finder.handle_key(Key::Char(c));
+        }
+        finder.record_key(Key::Char('o'));
+
+        let (_, recorder) = finder.recorder.as_ref().unwrap();
+        let keys: Vec<&str> = recorder.recording().keys.iter().map(|event| event.key.as_str()).collect();
+        assert_eq!(keys, vec!["n", "o"]);
+    }
+
+    #[test]
+    fn test_ctrl_r_restarts_refresh() {
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        // Without a background refresh, like with dummy data, there is nothing to restart
+        finder.handle_key(Key::Ctrl('r'));
+        assert!(!finder.loading);
+
+        let (tx, mut rx) = mpsc::channel(1);
+        finder.set_refresh_requests(tx);
+        finder.set_loading(true);
+        finder.set_error_message(Some("GitHub error: connection reset".to_string()));
+
+        finder.handle_key(Key::Ctrl('r'));
+        assert_eq!(rx.try_recv(), Ok(RefreshRequest::Restart));
+        assert!(finder.loading);
+        assert_eq!(finder.error_message, None);
+        assert_eq!(finder.status_message.as_deref(), Some("Restarting the refresh…"));
+    }
+
+    #[test]
+    fn test_f5_refreshes_once_at_a_time() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        finder.set_refresh_requests(tx);
+
+        finder.handle_key(Key::F(5));
+        assert_eq!(rx.try_recv(), Ok(RefreshRequest::Refresh));
+        assert_eq!(finder.status_message.as_deref(), Some("Refreshing…"));
+
+        // Pressing it again before the refresh completes does not start another one
+        finder.handle_key(Key::F(5));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(finder.status_message.as_deref(), Some("Already refreshing, Ctrl+R starts over"));
+    }
+
+    #[test]
+    fn test_spinner_stops_when_loading_completes() {
+        let (tx, rx) = mpsc::channel(8);
+        let mut finder = FuzzyFinder::new(Vec::new(), |r| r.name.clone());
+        finder.set_update_receiver(rx);
+        finder.set_loading(true);
+        assert_eq!(finder.status_text().as_deref(), Some("⠋ Loading repositories..."));
+
+        // Each render tick shows the next frame before the status
+        tx.try_send(FinderUpdate::Repos(vec![RepoData::named("notes")])).unwrap();
+        tx.try_send(FinderUpdate::Status("Fetching GitLab repositories...".to_string())).unwrap();
+        finder.apply_updates();
+        finder.spinner_frame += 1;
+        assert_eq!(finder.status_text().as_deref(), Some("⠙ Fetching GitLab repositories..."));
+
+        tx.try_send(FinderUpdate::LoadingComplete(format!("{}: 2 new", LOADING_COMPLETE_STATUS))).unwrap();
+        finder.apply_updates();
+        assert_eq!(finder.status_text().as_deref(), Some("Repository loading complete: 2 new"));
+    }
+
+    #[test]
+    fn test_replayed_keys_select_repository() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("go-web"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+
+        // Keys as saved with --record, the quit is never reached
+        let recording: Recording = serde_json::from_str(
+            r#"{"keys": [
+                {"at_ms": 0, "key": "r"}, {"at_ms": 0, "key": "u"}, {"at_ms": 0, "key": "s"},
+                {"at_ms": 0, "key": "t"}, {"at_ms": 0, "key": "Down"}, {"at_ms": 0, "key": "Enter"},
+                {"at_ms": 0, "key": "Esc"}
+            ]}"#,
+        )
+        .unwrap();
+        finder.set_replay(recording);
+
+        let outcome = loop {
+            let key = finder.next_replayed_key().expect("the replay selects before running out of keys");
+            match finder.handle_key(key) {
+                KeyOutcome::Continue => {}
+                outcome => break outcome,
+            }
+        };
+
+        assert_eq!(outcome, KeyOutcome::Select(FinderAction::Open));
+        assert_eq!(finder.query, "rust");
+        assert_eq!(finder.selected_repo().unwrap().name, "rust-cli");
+        assert_eq!(finder.next_replayed_key(), Some(Key::Esc));
+        assert!(finder.replay.is_none());
+    }
+
+    #[test]
+    fn test_selection_kept_when_items_merged_in() {
+        let repos = vec![RepoData::named("alpha"), RepoData::named("delta"), RepoData::named("gamma")];
+        let mut finder = FuzzyFinder::new(repos.clone(), |r| r.name.clone());
+        finder.move_cursor_down();
+        assert_eq!(finder.selected_repo().unwrap().name, "delta");
+
+        // A batch sorted in before the selected repository moves it down the list
+        let batch = vec![RepoData::named("beta"), RepoData::named("zeta"), RepoData::named("charlie")];
+        let merged = sort::merge_sorted(repos, batch, |a, b| a.name.cmp(&b.name));
+        finder.update_items(merged);
+
+        let names: Vec<&str> = finder.filtered_items.iter().map(|&id| finder.repos[id].name.as_str()).collect();
+        assert_eq!(names, vec!["alpha", "beta", "charlie", "delta", "gamma", "zeta"]);
+        assert_eq!(finder.selected_repo().unwrap().name, "delta");
+        assert_eq!(finder.selected_index - finder.scroll_offset, 1);
+    }
+
+    #[test]
+    fn test_update_items_keeps_query_and_scroll() {
+        let mut repos: Vec<RepoData> = (0..30).map(|i| RepoData::named(&format!("rust-{:02}", i))).collect();
+        let mut finder = FuzzyFinder::new(repos.clone(), |r| r.name.clone());
+        finder.set_query("rust");
+        finder.select_index(25);
+        assert_eq!((finder.selected_index, finder.scroll_offset), (25, 16));
+
+        // New repositories arrive while scrolled down, and the query still applies to them
+        repos.insert(0, RepoData::named("notes"));
+        repos.insert(0, RepoData::named("rust-new"));
+        finder.update_items(repos.clone());
+        assert_eq!(finder.query, "rust");
+        assert_eq!(finder.filtered_items.len(), 31);
+        assert_eq!(finder.selected_repo().unwrap().name, "rust-25");
+        assert_eq!(finder.selected_index - finder.scroll_offset, 9);
+
+        // Without the selected repository its row is kept, without empty rows below the end
+        repos.truncate(20);
+        finder.update_items(repos);
+        assert_eq!(finder.filtered_items.len(), 19);
+        assert_eq!((finder.selected_index, finder.scroll_offset), (18, 9));
+    }
+
+    #[test]
+    fn test_merge_items_mid_search() {
+        let repos = vec![RepoData::named("rust-web"), RepoData::named("notes"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos.clone(), |r| r.name.clone());
+        finder.set_query("rust");
+        finder.move_cursor_down();
+        assert_eq!(finder.selected_repo().unwrap().name, "rust-cli");
+
+        // The refresh repeats the listed repositories with new ones among them
+        let mut refreshed = vec![RepoData::named("rust-api"), RepoData::named("dotfiles")];
+        refreshed.extend(repos);
+        finder.merge_items(refreshed);
+
+        let names: Vec<&str> = finder.repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["rust-api", "dotfiles", "rust-web", "notes", "rust-cli"]);
+        assert_eq!(finder.query, "rust");
+        assert_eq!(finder.filtered_items.len(), 3);
+        assert_eq!(finder.selected_repo().unwrap().name, "rust-cli");
+
+        // New repositories are found like the ones indexed from the start
+        finder.set_query("dotf");
+        assert_eq!(finder.filtered_items, vec![1]);
+
+        // Repositories missing from an update keep their place, and changed items are searchable
+        let mut renamed = RepoData::named("notes");
+        renamed.name = "journal".to_string();
+        finder.merge_items(vec![renamed]);
+        let names: Vec<&str> = finder.repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["rust-api", "dotfiles", "rust-web", "journal", "rust-cli"]);
+        finder.set_query("journal");
+        assert_eq!(finder.filtered_items, vec![3]);
+
+        // Repositories appended by a refresh are indexed too
+        let mut appended = finder.repos.clone();
+        appended.push(RepoData::named("dotnet"));
+        finder.merge_items(appended);
+        finder.set_query("dotn");
+        assert_eq!(finder.filtered_items, vec![5]);
+    }
+
+    #[test]
+    fn test_initial_query_filters_items() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("go-microservices"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        finder.set_query("rust");
+
+        assert_eq!(finder.filtered_items, vec![0, 2]);
+        assert_eq!(finder.cursor_pos, 4);
+    }
+
+    #[test]
+    fn test_page_and_home_end_navigation() {
+        let mut repos: Vec<RepoData> = (0..20).map(|i| RepoData::named(&format!("repo-{:02}", i))).collect();
+        repos.extend((0..5).map(|i| RepoData::named(&format!("other-{}", i))));
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        let selected = |finder: &FuzzyFinder| (finder.selected_index, finder.scroll_offset);
+
+        finder.handle_key(Key::PageDown);
+        assert_eq!(selected(&finder), (10, 1));
+        finder.handle_key(Key::PageDown);
+        finder.handle_key(Key::PageDown);
+        assert_eq!(selected(&finder), (24, 15));
+        finder.handle_key(Key::PageUp);
+        assert_eq!(selected(&finder), (14, 14));
+
+        finder.handle_key(Key::Home);
+        assert_eq!(selected(&finder), (0, 0));
+        finder.handle_key(Key::End);
+        assert_eq!(selected(&finder), (24, 15));
+
+        // A list shorter than a page clamps to its ends
+        finder.set_query("other");
+        finder.handle_key(Key::Home);
+        finder.handle_key(Key::PageDown);
+        assert_eq!(selected(&finder), (4, 0));
+        finder.handle_key(Key::PageUp);
+        assert_eq!(selected(&finder), (0, 0));
+
+        // An empty list stays as it is
+        finder.set_query("nothing");
+        for key in [Key::PageDown, Key::End, Key::PageUp, Key::Home] {
+            finder.handle_key(key);
+            assert_eq!(selected(&finder), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_quick_filters_toggle_back() {
+        let fork = RepoData { is_fork: true, ..RepoData::named("rust-fork") };
+        let private = RepoData { is_private: true, ..RepoData::named("rust-private") };
+        let repos = vec![RepoData::named("rust-web"), fork, private, RepoData::named("go-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        finder.set_quick_filters(vec![
+            QuickFilter::parse("forks").unwrap(),
+            QuickFilter::parse("go=go").unwrap(),
+        ]);
+        finder.set_query("rust");
+        let before = finder.filtered_items.clone();
+
+        // A predicate narrows the typed query without changing it
+        finder.handle_key(Key::Alt('1'));
+        assert_eq!(finder.filtered_items, vec![1]);
+        assert_eq!(finder.query, "rust");
+
+        // A text preset adds its terms, here matching nothing together with the query
+        finder.handle_key(Key::Alt('2'));
+        assert!(finder.filtered_items.is_empty());
+
+        // Toggling both off returns to the matches before, keys without a preset do nothing
+        finder.handle_key(Key::Alt('2'));
+        finder.handle_key(Key::Alt('1'));
+        finder.handle_key(Key::Alt('9'));
+        assert_eq!(finder.filtered_items, before);
+        assert!(finder.active_quick_filters.is_empty());
+    }
+
+    #[test]
+    fn test_wrap_around_navigation() {
+        let repos: Vec<RepoData> = (0..15).map(|i| RepoData::named(&format!("repo-{:02}", i))).collect();
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        let selected = |finder: &FuzzyFinder| (finder.selected_index, finder.scroll_offset);
+
+        // Without wrapping the selection stops at the ends
+        finder.move_cursor_up();
+        assert_eq!(selected(&finder), (0, 0));
+
+        // Up on the first item scrolls to the last one, Down there back to the first
+        finder.set_wrap(true);
+        finder.move_cursor_up();
+        assert_eq!(selected(&finder), (14, 5));
+        finder.move_cursor_down();
+        assert_eq!(selected(&finder), (0, 0));
+
+        // A single item stays selected
+        finder.set_query("repo-07");
+        finder.move_cursor_down();
+        finder.move_cursor_up();
+        assert_eq!(selected(&finder), (0, 0));
+    }
+
+    #[test]
+    fn test_delete_word_before() {
+        assert_eq!(delete_word_before("rust web", 8), ("rust ".to_string(), 5));
+        assert_eq!(delete_word_before("rust web  ", 10), ("rust ".to_string(), 5));
+        assert_eq!(delete_word_before("rust", 4), (String::new(), 0));
+        assert_eq!(delete_word_before("   ", 3), (String::new(), 0));
+
+        // Only the part before the cursor is deleted
+        assert_eq!(delete_word_before("rust web", 4), (" web".to_string(), 0));
+        assert_eq!(delete_word_before("go -old web", 7), ("go  web".to_string(), 3));
+        assert_eq!(delete_word_before("café crème", 12), ("café ".to_string(), 6));
+    }
+
+    #[test]
+    fn test_ctrl_w_and_ctrl_u_edit_query() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        finder.set_query("rust web");
+        assert_eq!(finder.filtered_items, vec![0]);
+
+        finder.handle_key(Key::Ctrl('w'));
+        assert_eq!((finder.query.as_str(), finder.cursor_pos), ("rust ", 5));
+        assert_eq!(finder.filtered_items, vec![0, 1]);
+
+        finder.handle_key(Key::Char('x'));
+        finder.handle_key(Key::Ctrl('u'));
+        assert_eq!((finder.query.as_str(), finder.cursor_pos), ("", 0));
+        assert_eq!(finder.filtered_items, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_empty_list_message() {
+        let mut finder = FuzzyFinder::new(Vec::new(), |r| r.name.clone());
+        assert_eq!(finder.empty_list_message().as_deref(), Some("No repositories"));
+        finder.set_loading(true);
+        assert_eq!(finder.empty_list_message().as_deref(), Some("Loading…"));
+
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("notes")], |r| r.name.clone());
+        assert_eq!(finder.empty_list_message(), None);
+        finder.set_query("rust");
+        assert_eq!(finder.empty_list_message().as_deref(), Some("No matches"));
+    }
+
+    #[test]
+    fn test_local_clones_are_rechecked_by_url() {
+        let notes = RepoData::named("notes");
+        let mut finder = FuzzyFinder::new(vec![notes.clone()], |r| r.name.clone());
+        finder.set_local_clone_dir(std::env::temp_dir().join("repo-searcher-test-no-clones"));
+        assert_eq!(finder.local_states.get(&notes.url), Some(&LocalState::Absent));
+
+        // The state an action may have changed is checked again instead of kept
+        finder.local_states.insert(notes.url.clone(), LocalState::Clean);
+        finder.recheck_local_clone(&notes);
+        assert_eq!(finder.local_states.get(&notes.url), Some(&LocalState::Absent));
+    }
+
+    #[test]
+    fn test_shrinking_keeps_selection_visible() {
+        let repos: Vec<RepoData> = (0..30).map(|i| RepoData::named(&format!("repo-{:02}", i))).collect();
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+
+        finder.resize(40);
+        assert_eq!(finder.max_display, 37);
+        finder.select_index(20);
+        assert_eq!(finder.scroll_offset, 0);
+
+        // Only 7 items fit now, so the list scrolls to keep the selection as the last one
+        finder.resize(10);
+        assert_eq!(finder.max_display, 7);
+        assert_eq!((finder.selected_index, finder.scroll_offset), (20, 14));
+
+        // Tiny terminals still show the selected item
+        finder.resize(2);
+        assert_eq!((finder.max_display, finder.scroll_offset), (1, 20));
+
+        // The common prefix shown once a filter narrows the list takes a row
+        finder.resize(10);
+        finder.set_strip_common_prefix(true);
+        finder.set_query("repo-2");
+        assert_eq!(finder.common_prefix, "repo-");
+        assert_eq!(finder.max_display, 6);
+    }
+
+    #[test]
+    fn test_common_prefix_needs_rows_starting_with_names() {
+        let repos = vec![RepoData::named("rust-web"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos.clone(), |r| r.name.clone());
+        finder.set_strip_common_prefix(true);
+        finder.set_query("");
+        assert_eq!(finder.common_prefix, "rust-");
+
+        // A row with a marker before the name would have nothing stripped
+        let mut finder = FuzzyFinder::new(repos, |r| format!("📦 {}", r.name));
+        finder.set_strip_common_prefix(true);
+        finder.set_query("");
+        assert_eq!(finder.common_prefix, "");
+    }
+
+    #[test]
+    fn test_typing_is_filtered_once_idle() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("rust-cli"), RepoData::named("go-web")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        finder.filter_debounce = FILTER_DEBOUNCE;
+
+        for c in "rust".chars() {
+            finder.handle_key(Key::Char(c));
+        }
+        assert_eq!(finder.filtered_items, vec![0, 1, 2]);
+
+        // Typing continues within the debounce, then the final query is filtered
+        let typed_at = finder.filter_pending_since.unwrap();
+        assert!(!finder.filter_if_idle(typed_at + Duration::from_millis(10)));
+        assert!(finder.filter_if_idle(typed_at + FILTER_DEBOUNCE));
+        assert_eq!(finder.filtered_items, vec![0, 1]);
+
+        // Other keys filter a pending query before acting on the matches
+        for c in " cli".chars() {
+            finder.handle_key(Key::Char(c));
+        }
+        finder.handle_key(Key::Down);
+        assert_eq!(finder.filtered_items, vec![1]);
+        assert_eq!(finder.selected_repo().unwrap().name, "rust-cli");
+    }
+
+    #[test]
+    fn test_editing_query_mid_string() {
+        let repos = vec![RepoData::named("rust-web-server"), RepoData::named("rust-cli")];
+        let mut finder = FuzzyFinder::new(repos, |r| r.name.clone());
+        let mut press = |keys: &[Key]| {
+            for &key in keys {
+                finder.handle_key(key);
+            }
+        };
+
+        // Fix a typo in the middle of "rsut web"
+        press(&[Key::Char('r'), Key::Char('s'), Key::Char('u'), Key::Char('t'), Key::Char(' ')]);
+        press(&[Key::Char('w'), Key::Char('e'), Key::Char('b')]);
+        press(&[Key::CtrlHome, Key::Right, Key::Right, Key::Backspace, Key::Right, Key::Char('s')]);
+        assert_eq!(finder.query, "rust web");
+        assert_eq!(finder.cursor_pos, 3);
+        assert_eq!(finder.filtered_items, vec![0]);
 
-            // Small sleep to prevent CPU hogging
-            thread::sleep(Duration::from_millis(10));
+        // Multibyte characters move the cursor by whole characters
+        let mut finder = FuzzyFinder::new(vec![RepoData::named("café")], |r| r.name.clone());
+        for key in [Key::Char('c'), Key::Char('é'), Key::Left, Key::Left, Key::Right, Key::Char('a'), Key::Delete] {
+            finder.handle_key(key);
         }
+        assert_eq!(finder.query, "ca");
+        finder.handle_key(Key::Backspace);
+        finder.handle_key(Key::CtrlEnd);
+        assert_eq!((finder.query.as_str(), finder.cursor_pos), ("c", 1));
     }
 }
@@ -0,0 +1,238 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Returns the directory a repository is cloned into
+pub fn clone_target(clone_dir: &Path, repo_name: &str) -> PathBuf {
+    clone_dir.join(repo_name)
+}
+
+/// Whether a repository has a local clone, and if it has uncommitted changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalState {
+    Absent,
+    Clean,
+    Dirty,
+}
+
+impl LocalState {
+    /// Returns the badge shown after a repository in the fuzzy finder
+    pub fn badge(self) -> Option<&'static str> {
+        match self {
+            LocalState::Absent => None,
+            LocalState::Clean => Some("📂"),
+            LocalState::Dirty => Some("📂*"),
+        }
+    }
+}
+
+/// Checks if a directory is a git clone and if `git status --porcelain` reports changes
+///
+/// Directories without their own `.git` count as absent, so a clone directory inside
+/// another repository does not report that repository's status.
+pub fn local_state(repo_dir: &Path) -> LocalState {
+    if !repo_dir.join(".git").exists() {
+        return LocalState::Absent;
+    }
+
+    let output = process::Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(repo_dir)
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() && output.stdout.is_empty() => LocalState::Clean,
+        Ok(output) if output.status.success() => LocalState::Dirty,
+        // Not a usable repository, like a broken clone
+        _ => LocalState::Absent,
+    }
+}
+
+/// Clones a repository with git, showing git's progress output
+pub fn clone_repo(url: &str, target: &Path) -> Result<(), String> {
+    println!("Cloning {} into {}", url, target.display());
+
+    let status = process::Command::new("git")
+        .arg("clone")
+        .arg(url)
+        .arg(target)
+        .status()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("git clone exited with {}", status))
+    }
+}
+
+/// Clones a repository into a directory which must not exist yet.
+///
+/// An existing directory is reported up front, rather than by git's error scrolling past.
+pub fn clone_into(url: &str, target: &Path) -> Result<(), String> {
+    if target.exists() {
+        return Err(format!("{} already exists, not cloning into it", target.display()));
+    }
+    clone_repo(url, target)
+}
+
+/// Builds the command to launch a git TUI like lazygit inside a repository
+///
+/// The TUI command may contain arguments, like "gitui --watcher".
+pub fn git_tui_command(git_tui: &str, repo_dir: &Path) -> Option<process::Command> {
+    let mut parts = git_tui.split_whitespace();
+    let mut command = process::Command::new(parts.next()?);
+    command.args(parts).current_dir(repo_dir);
+    Some(command)
+}
+
+/// Runs a git TUI inside a repository and waits for it to exit
+pub fn run_git_tui(git_tui: &str, repo_dir: &Path) -> Result<(), String> {
+    let mut command = git_tui_command(git_tui, repo_dir).ok_or("No git TUI command configured")?;
+
+    let status = command.status().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => format!("{} is not installed", git_tui),
+        _ => format!("Failed to run {}: {}", git_tui, e),
+    })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", git_tui, status))
+    }
+}
+
+/// Opens a repository in a git TUI, cloning it first if there is no local clone yet
+///
+/// Cloning and spawning are passed in, so the gating can be tested without git.
+pub fn open_in_git_tui<C, S>(repo_dir: &Path, clone: C, spawn: S) -> Result<(), String>
+where
+    C: FnOnce(&Path) -> Result<(), String>,
+    S: FnOnce(&Path) -> Result<(), String>,
+{
+    if !repo_dir.exists() {
+        clone(repo_dir).map_err(|e| format!("Could not clone {}: {}", repo_dir.display(), e))?;
+    }
+
+    spawn(repo_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_git_tui_command() {
+        let command = git_tui_command("gitui --watcher", Path::new("/tmp/repo")).unwrap();
+
+        assert_eq!(command.get_program(), "gitui");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), vec!["--watcher"]);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/tmp/repo")));
+
+        assert!(git_tui_command("   ", Path::new("/tmp/repo")).is_none());
+    }
+
+    #[test]
+    fn test_clone_target() {
+        assert_eq!(clone_target(Path::new("/src"), "my-repo"), PathBuf::from("/src/my-repo"));
+    }
+
+    #[test]
+    fn test_clone_into_existing_directory_fails() {
+        let target = std::env::temp_dir().join("repo-searcher-test-clone-into");
+        std::fs::create_dir_all(&target).unwrap();
+
+        let error = clone_into("git@github.com:user/notes.git", &target).unwrap_err();
+        assert!(error.ends_with("already exists, not cloning into it"));
+    }
+
+    // Creates an empty git repository in a fresh temporary directory
+    fn init_repo(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let status = process::Command::new("git")
+            .args(["init", "--quiet"])
+            .current_dir(&dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        dir
+    }
+
+    #[test]
+    fn test_local_state() {
+        let missing = std::env::temp_dir().join("repo-searcher-test-local-missing");
+        assert_eq!(local_state(&missing), LocalState::Absent);
+
+        // A plain directory is not a clone
+        let plain = std::env::temp_dir().join("repo-searcher-test-local-plain");
+        std::fs::create_dir_all(&plain).unwrap();
+        assert_eq!(local_state(&plain), LocalState::Absent);
+
+        let clean = init_repo("repo-searcher-test-local-clean");
+        assert_eq!(local_state(&clean), LocalState::Clean);
+
+        let dirty = init_repo("repo-searcher-test-local-dirty");
+        std::fs::write(dirty.join("notes.txt"), "uncommitted").unwrap();
+        assert_eq!(local_state(&dirty), LocalState::Dirty);
+
+        for dir in [plain, clean, dirty] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_open_clones_missing_repository_first() {
+        let missing = std::env::temp_dir().join("repo-searcher-test-missing-clone");
+        let cloned = Cell::new(false);
+
+        let result = open_in_git_tui(
+            &missing,
+            |_| {
+                cloned.set(true);
+                Ok(())
+            },
+            |_| {
+                assert!(cloned.get(), "spawned before cloning");
+                Ok(())
+            },
+        );
+
+        assert_eq!(result, Ok(()));
+        assert!(cloned.get());
+    }
+
+    #[test]
+    fn test_open_skips_clone_for_existing_repository() {
+        let existing = std::env::temp_dir();
+        let spawned = Cell::new(false);
+
+        let result = open_in_git_tui(
+            &existing,
+            |_| panic!("existing repository was cloned again"),
+            |_| {
+                spawned.set(true);
+                Ok(())
+            },
+        );
+
+        assert_eq!(result, Ok(()));
+        assert!(spawned.get());
+    }
+
+    #[test]
+    fn test_open_does_not_spawn_after_failed_clone() {
+        let missing = std::env::temp_dir().join("repo-searcher-test-failed-clone");
+
+        let result = open_in_git_tui(
+            &missing,
+            |_| Err("permission denied".to_string()),
+            |_| panic!("spawned after failed clone"),
+        );
+
+        assert!(result.unwrap_err().ends_with("permission denied"));
+    }
+}
@@ -1,132 +1,703 @@
-use octocrab::Octocrab;
+use chrono::{DateTime, Local, Utc};
 use octocrab::models::Repository as OctocrabRepo;
-use std::io::Write;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
 
-pub type Repository = (String, String, String, String, bool, bool); // (name, ssh_url, description, owner, is_fork, is_private)
+use crate::retry;
+use crate::terminal;
+use crate::user_agent;
+
+/// A repository as fetched from GitHub or GitLab, before it is converted for the cache
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Repository {
+    pub name: String,
+    pub ssh_url: String,
+    pub description: String,
+    pub owner: String,
+    pub is_fork: bool,
+    pub is_private: bool,
+    pub language: Option<String>,
+    pub topics: Vec<String>,
+    // Timestamps are seconds since the epoch
+    pub updated_at: Option<u64>,
+    pub created_at: Option<u64>,
+    pub is_archived: bool,
+    pub stars: Option<u64>,
+    pub open_issues: Option<u64>,
+    pub default_branch: Option<String>,
+    pub pushed_at: Option<u64>,
+}
+
+/// Host of the public GitHub, used without `--github-host`
+pub const DEFAULT_HOST: &str = "github.com";
+
+static HOST: OnceLock<String> = OnceLock::new();
+
+/// Parses a GitHub Enterprise host like `github.example.com`, also accepting its URL
+pub fn parse_host(value: &str) -> Result<String, String> {
+    let host = value.trim();
+    let host = host.strip_prefix("https://").or_else(|| host.strip_prefix("http://")).unwrap_or(host);
+    let host = host.trim_end_matches('/');
+    let host = host.strip_suffix("/api/v3").unwrap_or(host);
+
+    if host.is_empty() || host.contains(['/', ' ']) {
+        return Err(format!("expected a host like github.example.com, got '{}'", value));
+    }
+    Ok(host.to_string())
+}
+
+/// Sets the host all GitHub requests and URLs use, only the first call has an effect
+pub fn set_host(host: String) {
+    let _ = HOST.set(host);
+}
+
+/// Returns the configured host, or github.com if none was set
+pub fn host() -> &'static str {
+    HOST.get().map_or(DEFAULT_HOST, String::as_str)
+}
+
+/// Returns the API base URL of a GitHub Enterprise host, None for github.com which octocrab knows
+pub fn api_base_uri(host: &str) -> Option<String> {
+    (host != DEFAULT_HOST).then(|| format!("https://{}/api/v3", host))
+}
+
+/// Returns the web page of a repository on a host
+pub fn repo_web_url(host: &str, owner: &str, name: &str) -> String {
+    format!("https://{}/{}/{}", host, owner, name)
+}
+
+/// Returns the web page of a gist, which GitHub Enterprise serves under `/gist`
+pub fn gist_web_url(host: &str, owner: &str, id: &str) -> String {
+    match host {
+        DEFAULT_HOST => format!("https://gist.github.com/{}/{}", owner, id),
+        _ => format!("https://{}/gist/{}/{}", host, owner, id),
+    }
+}
 
 // Helper function to convert GitHub API repository to our Repository type
 fn convert_repo(repo: OctocrabRepo, username: &str) -> Repository {
-    (
-        repo.name,
-        repo.ssh_url.unwrap_or_default(),
-        repo.description.unwrap_or_default(),
-        username.to_string(),
-        repo.fork.unwrap_or(false),
-        repo.private.unwrap_or(false)
-    )
+    Repository {
+        name: repo.name,
+        ssh_url: repo.ssh_url.unwrap_or_default(),
+        description: repo.description.unwrap_or_default(),
+        owner: username.to_string(),
+        is_fork: repo.fork.unwrap_or(false),
+        is_private: repo.private.unwrap_or(false),
+        language: repo.language.and_then(|language| language.as_str().map(|l| l.to_string())),
+        topics: repo.topics.unwrap_or_default(),
+        updated_at: repo.updated_at.map(|updated_at| updated_at.timestamp() as u64),
+        created_at: repo.created_at.map(|created_at| created_at.timestamp() as u64),
+        is_archived: repo.archived.unwrap_or(false),
+        stars: repo.stargazers_count.map(u64::from),
+        open_issues: repo.open_issues_count.map(u64::from),
+        default_branch: repo.default_branch,
+        pushed_at: repo.pushed_at.map(|pushed_at| pushed_at.timestamp() as u64),
+    }
+}
+
+// Gist API response, only with the fields needed here
+#[derive(Debug, Deserialize)]
+struct GitHubGist {
+    id: String,
+    description: Option<String>,
+    public: bool,
+    files: BTreeMap<String, serde_json::Value>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// Returns the SSH clone URL of a gist on a host, which is cloned by its id
+fn gist_clone_url(host: &str, id: &str) -> String {
+    match host {
+        DEFAULT_HOST => format!("git@gist.github.com:{}.git", id),
+        _ => format!("git@{}:gist/{}.git", host, id),
+    }
+}
+
+// Helper function to convert a gist to our Repository type, named by its id
+fn convert_gist(gist: GitHubGist, username: &str) -> Repository {
+    let file_names: Vec<String> = gist.files.into_keys().collect();
+
+    // Many gists have no description, so their file names are shown instead
+    let description = gist
+        .description
+        .filter(|description| !description.trim().is_empty())
+        .unwrap_or_else(|| file_names.join(", "));
+
+    // File names are no topics, so they are only shown in the description
+    Repository {
+        ssh_url: gist_clone_url(host(), &gist.id),
+        name: gist.id,
+        description,
+        owner: username.to_string(),
+        is_private: !gist.public,
+        updated_at: gist.updated_at.map(|updated_at| updated_at.timestamp() as u64),
+        created_at: gist.created_at.map(|created_at| created_at.timestamp() as u64),
+        // A gist has no pushes of its own, every push updates it
+        pushed_at: gist.updated_at.map(|updated_at| updated_at.timestamp() as u64),
+        ..Repository::default()
+    }
 }
 
-// Helper function to update progress display
+// Helper function to update progress display, on stderr to keep it out of --print and --output json
 fn update_progress(page_count: usize, repos_count: usize) {
-    print!("\r                                                  "); // Clear the line
-    print!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
-    std::io::stdout().flush().unwrap();
+    eprint!("\r                                                  "); // Clear the line
+    eprint!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
+}
+
+/// Keeps the repositories updated after `since`, from a page sorted by update time.
+///
+/// Returns the kept repositories and whether an older repository was reached, after
+/// which no later page can contain updates.
+fn take_updated_since(repos: Vec<Repository>, since: u64) -> (Vec<Repository>, bool) {
+    let total = repos.len();
+    let updated: Vec<Repository> = repos
+        .into_iter()
+        .take_while(|repo| repo.updated_at.is_some_and(|updated_at| updated_at > since))
+        .collect();
+    let reached_older = updated.len() < total;
+    (updated, reached_older)
+}
+
+/// Response header telling when the token of the request expires, for tokens with an expiry
+const TOKEN_EXPIRATION_HEADER: &str = "github-authentication-token-expiration";
+
+/// Parses the token expiration header, like `2021-09-13 06:47:25 UTC` or `2021-09-13 06:47:25 -0700`
+pub fn parse_token_expiration(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    let value = match value.strip_suffix(" UTC") {
+        Some(date_time) => format!("{} +0000", date_time),
+        None => value.to_string(),
+    };
+
+    DateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S %z")
+        .ok()
+        .map(|date_time| date_time.with_timezone(&Utc))
+}
+
+/// Why fetching the repositories failed
+#[derive(Debug)]
+pub enum FetchError {
+    /// The token used up its requests, which are available again at the given time
+    RateLimited(DateTime<Utc>),
+    /// The refresh was cancelled between pages
+    Cancelled,
+    /// GitHub answered 401, so the token is wrong, revoked or expired
+    InvalidToken,
+    Api(octocrab::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::RateLimited(resets_at) => write!(
+                f,
+                "GitHub rate limit exceeded, resets at {}",
+                resets_at.with_timezone(&Local).format("%H:%M")
+            ),
+            FetchError::Cancelled => write!(f, "Fetching GitHub repositories was cancelled"),
+            FetchError::InvalidToken => write!(f, "Invalid GitHub token"),
+            FetchError::Api(e) => write!(f, "GitHub error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<octocrab::Error> for FetchError {
+    fn from(error: octocrab::Error) -> Self {
+        FetchError::Api(error)
+    }
+}
+
+/// Returns when the rate limit resets if a response was rejected for exceeding it.
+///
+/// GitHub answers with 403, or 429 for secondary limits, and no remaining requests.
+pub fn rate_limit_reset(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap) -> Option<DateTime<Utc>> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let rejected = matches!(status.as_u16(), 403 | 429);
+    if !rejected || header("x-ratelimit-remaining")?.trim() != "0" {
+        return None;
+    }
+    let reset = header("x-ratelimit-reset")?.trim().parse().ok()?;
+    DateTime::from_timestamp(reset, 0)
+}
+
+/// Returns when the token of a response expires, if it does
+fn token_expiration(headers: &reqwest::header::HeaderMap) -> Option<DateTime<Utc>> {
+    headers
+        .get(TOKEN_EXPIRATION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_token_expiration)
+}
+
+/// Sends a GET request with retries on transient errors, reporting an exceeded rate limit with when it resets.
+///
+/// GitHub tells when the token expires on every authenticated response, so that is returned too.
+async fn get<T: octocrab::FromResponse>(octocrab: &octocrab::Octocrab, uri: &str) -> Result<(T, Option<DateTime<Utc>>), FetchError> {
+    let response = retry::with_backoff(is_transient_error, || async {
+        let response = octocrab._get(uri).await?;
+        if response.status().is_server_error() {
+            return octocrab::map_github_error(response).await;
+        }
+        Ok(response)
+    })
+    .await?;
+
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(FetchError::InvalidToken);
+    }
+    if let Some(resets_at) = rate_limit_reset(response.status(), response.headers()) {
+        return Err(FetchError::RateLimited(resets_at));
+    }
+    let response = octocrab::map_github_error(response).await?;
+    let expires_at = token_expiration(response.headers());
+    Ok((T::from_response(response).await?, expires_at))
+}
+
+/// Fetches the pages after a first one by following their next links, one by one.
+///
+/// Every page is passed to `add_page` as it arrives. Setting `cancel` stops before the next page.
+async fn follow_next_links<T: DeserializeOwned>(
+    octocrab: &octocrab::Octocrab,
+    mut next_link: Option<String>,
+    cancel: &AtomicBool,
+    mut add_page: impl FnMut(Vec<T>),
+) -> Result<(), FetchError> {
+    while let Some(next) = next_link {
+        // Add a small sleep to allow Ctrl+C to be processed
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        if terminal::should_stop(cancel) {
+            return Err(FetchError::Cancelled);
+        }
+
+        let (page, _): (octocrab::Page<T>, _) = get(octocrab, &next).await?;
+        next_link = page.next.as_ref().map(ToString::to_string);
+        add_page(page.items);
+    }
+    Ok(())
+}
+
+/// Fetches every page of a listing, starting with the one of `uri`
+async fn get_all_pages<T: DeserializeOwned>(octocrab: &octocrab::Octocrab, uri: &str, cancel: &AtomicBool) -> Result<Vec<T>, FetchError> {
+    let (first_page, _): (octocrab::Page<T>, _) = get(octocrab, uri).await?;
+    let next_link = first_page.next.as_ref().map(ToString::to_string);
+    let mut items = first_page.items;
+    follow_next_links(octocrab, next_link, cancel, |page| items.extend(page)).await?;
+    Ok(items)
 }
 
-pub async fn fetch_repos(token: &str) -> octocrab::Result<(String, Vec<Repository>)> {
-    print!("Fetching user information... ");
-    std::io::stdout().flush().unwrap();
+/// Fetches the repositories of the authenticated user, and when the token expires if it does.
+///
+/// With `updated_since`, only repositories updated after that timestamp are fetched,
+/// stopping at the first page reaching older ones. Setting `cancel` stops before the next page.
+pub async fn fetch_repos(
+    token: &str,
+    updated_since: Option<u64>,
+    known_username: Option<&str>,
+    cancel: &Arc<AtomicBool>,
+) -> Result<(String, Vec<Repository>, Option<DateTime<Utc>>), FetchError> {
+    let octocrab = user_agent::github_client(token)?;
+
+    // A username known from the cache skips the user call, the first page then tells the token expiry
+    let (username, user_expires_at) = match known_username {
+        Some(username) => (username.to_string(), None),
+        None => {
+            eprint!("Fetching user information... ");
 
-    let octocrab = Octocrab::builder().personal_token(token.to_string()).build()?;
+            // Get authenticated user information, whose response tells when the token expires.
+            // This also checks the token before any repositories are requested
+            let (user, expires_at): (octocrab::models::Author, _) = get(&octocrab, "/user").await?;
 
-    // Get authenticated user information
-    let user = octocrab.current().user().await?;
-    let username = user.login;
+            eprintln!("✓"); // Show checkmark on its own line
+            (user.login, expires_at)
+        }
+    };
 
-    println!("✓"); // Show checkmark on its own line
-    print!("Fetching repositories for {}... ", username);
-    std::io::stdout().flush().unwrap();
+    eprint!("Fetching repositories for {}... ", username);
 
-    let mut page = octocrab
-        .current()
-        .list_repos_for_authenticated_user()
-        .per_page(100) // Maximum allowed per page
-        .send()
-        .await?;
+    // Most recently updated first, so fetching can stop at the first older repository
+    let route = match updated_since {
+        Some(_) => "/user/repos?per_page=100&sort=updated&direction=desc",
+        None => "/user/repos?per_page=100", // Maximum allowed per page
+    };
+    let (mut page, page_expires_at): (octocrab::Page<OctocrabRepo>, _) = get(&octocrab, route).await?;
+    let expires_at = user_expires_at.or(page_expires_at);
 
     let mut all_repos = Vec::new();
     let mut page_count = 1;
 
-    // Add repos from the first page
-    all_repos.extend(
-        page.items
+    // Converts a page, returning false once no further page can have updates
+    let add_page = |items: Vec<OctocrabRepo>, all_repos: &mut Vec<Repository>| {
+        let repos: Vec<Repository> = items
             .into_iter()
             .map(|repo| convert_repo(repo, &username))
-    );
+            .collect();
+
+        match updated_since {
+            Some(since) => {
+                let (updated, reached_older) = take_updated_since(repos, since);
+                all_repos.extend(updated);
+                !reached_older
+            }
+            None => {
+                all_repos.extend(repos);
+                true
+            }
+        }
+    };
+
+    // Add repos from the first page
+    let mut more_pages = add_page(std::mem::take(&mut page.items), &mut all_repos);
 
     update_progress(page_count, all_repos.len());
 
     // Fetch all remaining pages
-    while let Some(next_page) = octocrab.get_page(&page.next).await? {
+    while more_pages {
+        let Some(next) = page.next.as_ref().map(ToString::to_string) else {
+            break;
+        };
+
         // Add a small sleep to allow Ctrl+C to be processed
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        if terminal::should_stop(cancel) {
+            return Err(FetchError::Cancelled);
+        }
 
         page_count += 1;
-        page = next_page;
+        (page, _) = get(&octocrab, &next).await?;
+
+        more_pages = add_page(std::mem::take(&mut page.items), &mut all_repos);
+        update_progress(page_count, all_repos.len());
+    }
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} repositories from {} pages", all_repos.len(), page_count);
+    Ok((username, all_repos, expires_at))
+}
+
+/// Fetches the repositories of an organization the user can access, owned by the organization.
+///
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_org_repos(token: &str, org: &str, cancel: &AtomicBool) -> Result<Vec<Repository>, FetchError> {
+    let octocrab = user_agent::github_client(token)?;
 
-        all_repos.extend(
-            page.items
-                .into_iter()
-                .map(|repo| convert_repo(repo, &username))
-        );
+    eprint!("Fetching repositories for organization {}... ", org);
+
+    let route = format!("/orgs/{}/repos?per_page=100", org); // Maximum allowed per page
+    let (page, _): (octocrab::Page<OctocrabRepo>, _) = get(&octocrab, &route).await?;
+    let next_link = page.next.as_ref().map(ToString::to_string);
+
+    let mut all_repos: Vec<Repository> = page.items
+        .into_iter()
+        .map(|repo| convert_repo(repo, org))
+        .collect();
+    let mut page_count = 1;
+    update_progress(page_count, all_repos.len());
+
+    follow_next_links(&octocrab, next_link, cancel, |items: Vec<OctocrabRepo>| {
+        page_count += 1;
+        all_repos.extend(items.into_iter().map(|repo| convert_repo(repo, org)));
         update_progress(page_count, all_repos.len());
+    })
+    .await?;
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} repositories of {} from {} pages", all_repos.len(), org, page_count);
+    Ok(all_repos)
+}
+
+/// Fetches the gists of the authenticated user.
+///
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_gists(token: &str, username: &str, cancel: &AtomicBool) -> Result<Vec<Repository>, FetchError> {
+    let octocrab = user_agent::github_client(token)?;
+
+    // Maximum allowed per page
+    let gists: Vec<GitHubGist> = get_all_pages(&octocrab, "/gists?per_page=100", cancel).await?;
+    Ok(gists.into_iter().map(|gist| convert_gist(gist, username)).collect())
+}
+
+/// Fetches the repositories starred by the authenticated user, owned by whoever owns them.
+///
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_starred(token: &str, cancel: &AtomicBool) -> Result<Vec<Repository>, FetchError> {
+    let octocrab = user_agent::github_client(token)?;
+
+    // Maximum allowed per page
+    let repos: Vec<OctocrabRepo> = get_all_pages(&octocrab, "/user/starred?per_page=100", cancel).await?;
+    Ok(repos
+        .into_iter()
+        .map(|repo| {
+            let owner = repo.owner.as_ref().map(|owner| owner.login.clone()).unwrap_or_default();
+            convert_repo(repo, &owner)
+        })
+        .collect())
+}
+
+/// Check if GitHub rejected the token
+pub fn is_auth_error(error: &FetchError) -> bool {
+    match error {
+        FetchError::InvalidToken => true,
+        FetchError::Api(octocrab::Error::GitHub { source, .. }) => source.status_code.as_u16() == 401,
+        _ => false,
+    }
+}
+
+/// Check if a request failed in a way that may not happen again, like a 5xx or a dropped connection
+pub fn is_transient_error(error: &octocrab::Error) -> bool {
+    match error {
+        octocrab::Error::GitHub { source, .. } => source.status_code.is_server_error(),
+        octocrab::Error::Hyper { .. } | octocrab::Error::Service { .. } => true,
+        _ => false,
     }
+}
+
+/// Returns the API route of a single repository
+fn repo_route(owner: &str, name: &str) -> String {
+    format!("/repos/{}/{}", owner, name)
+}
 
-    println!("✓"); // Show checkmark on its own line
-    println!("Fetched {} repositories from {} pages", all_repos.len(), page_count);
-    Ok((username, all_repos))
+/// Fetches a single repository by owner and name, without listing all repositories
+pub async fn fetch_repo(token: &str, owner: &str, name: &str) -> Result<Repository, FetchError> {
+    let octocrab = user_agent::github_client(token)?;
+    let (repo, _): (OctocrabRepo, _) = get(&octocrab, &repo_route(owner, name)).await?;
+    Ok(convert_repo(repo, owner))
 }
 
 pub fn generate_dummy_repos() -> (String, Vec<Repository>) {
-    println!("Using 100 dummy repositories for testing");
+    // Printed to stderr, so the output of --print and --output json stays clean
+    eprintln!("Using 100 dummy repositories for testing");
     let username = "dima-369".to_string();
 
     // Generate 100 dummy repositories with different names and categories
     let mut dummy_repos = Vec::with_capacity(100);
 
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+    let day = 24 * 60 * 60;
+
     // Add some special repositories that are easy to find
-    dummy_repos.push(("clj-basic-image-cache-server".to_string(), "git@github.com:dima-369/clj-basic-image-cache-server.git".to_string(), "A basic image cache server written in Clojure".to_string(), username.clone(), true, false));
-    dummy_repos.push(("rust-web-server".to_string(), "git@github.com:dima-369/rust-web-server.git".to_string(), "A web server written in Rust".to_string(), username.clone(), false, true));
-    dummy_repos.push(("go-microservices".to_string(), "git@github.com:dima-369/go-microservices.git".to_string(), "Microservices examples in Go".to_string(), username.clone(), false, false));
+    dummy_repos.push(Repository {
+        name: "clj-basic-image-cache-server".to_string(),
+        ssh_url: "git@github.com:dima-369/clj-basic-image-cache-server.git".to_string(),
+        description: "A basic image cache server written in Clojure".to_string(),
+        owner: username.clone(),
+        is_fork: true,
+        language: Some("Clojure".to_string()),
+        topics: vec!["cache".to_string(), "images".to_string()],
+        updated_at: Some(now - 400 * day),
+        created_at: Some(now - 2000 * day),
+        stars: Some(3),
+        open_issues: Some(0),
+        default_branch: Some("master".to_string()),
+        pushed_at: Some(now - 400 * day),
+        ..Repository::default()
+    });
+    dummy_repos.push(Repository {
+        name: "rust-web-server".to_string(),
+        ssh_url: "git@github.com:dima-369/rust-web-server.git".to_string(),
+        description: "A web server written in Rust".to_string(),
+        owner: username.clone(),
+        is_private: true,
+        language: Some("Rust".to_string()),
+        topics: vec!["web".to_string(), "server".to_string()],
+        updated_at: Some(now - day),
+        created_at: Some(now - 30 * day),
+        stars: Some(120),
+        open_issues: Some(8),
+        default_branch: Some("main".to_string()),
+        pushed_at: Some(now - 2 * 60 * 60),
+        ..Repository::default()
+    });
+    dummy_repos.push(Repository {
+        name: "go-microservices".to_string(),
+        ssh_url: "git@github.com:dima-369/go-microservices.git".to_string(),
+        description: "Microservices examples in Go".to_string(),
+        owner: username.clone(),
+        language: Some("Go".to_string()),
+        topics: vec!["microservices".to_string()],
+        is_archived: true,
+        ..Repository::default()
+    });
 
     // Add repositories by category
     let categories = ["api", "web", "mobile", "backend", "frontend", "database", "utils", "tools", "docs", "test"];
+    let languages = ["Rust", "Python", "TypeScript", "Go", "Kotlin"];
 
     for i in 1..=97 {
         let category = categories[i % categories.len()];
         let name = format!("{}-project-{}", category, i);
-        let url = format!("git@github.com:{}/{}.git", username, name);
+        let ssh_url = format!("git@github.com:{}/{}.git", username, name);
         let description = format!("A {} project for {}", category, if i % 2 == 0 { "development" } else { "production" });
         // Make some repos forks and some private for variety
         let is_fork = i % 5 == 0;  // Every 5th repo is a fork
         let is_private = i % 7 == 0; // Every 7th repo is private
-        dummy_repos.push((name, url, description, username.clone(), is_fork, is_private));
+        // Leave every 11th repo without a language, like repos that only hold docs
+        let language = if i % 11 == 0 { None } else { Some(languages[i % languages.len()].to_string()) };
+        let topics = vec![category.to_string()];
+        let updated_at = Some(now - i as u64 * 3 * day);
+        let created_at = Some(now - i as u64 * 40 * day);
+        let is_archived = i % 13 == 0; // Every 13th repo is archived
+        let stars = Some((i as u64 * 37) % 50);
+        let open_issues = Some(i as u64 % 4);
+        // Pushed a few days before the last update, like an edited description afterwards
+        let pushed_at = updated_at.map(|updated_at| updated_at - (i as u64 % 3) * day);
+        dummy_repos.push(Repository {
+            name,
+            ssh_url,
+            description,
+            owner: username.clone(),
+            is_fork,
+            is_private,
+            language,
+            topics,
+            updated_at,
+            created_at,
+            is_archived,
+            stars,
+            open_issues,
+            default_branch: Some("main".to_string()),
+            pushed_at,
+        });
     }
 
     (username, dummy_repos)
 }
 
-pub fn extract_repo_info(selection: &str, username: &str) -> Option<(String, String, Option<String>)> {
-    // First, remove the GitHub indicator [GH] if present
-    let cleaned_selection = selection.replace(" [GH]", "");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Remove the private indicator if present
-    let cleaned_selection = cleaned_selection.replace(" 🔒", "");
+    #[test]
+    fn test_parse_host() {
+        assert_eq!(parse_host("github.example.com"), Ok("github.example.com".to_string()));
+        assert_eq!(parse_host("https://github.example.com/api/v3/"), Ok("github.example.com".to_string()));
+        assert!(parse_host("").is_err());
+        assert!(parse_host("github.example.com/team").is_err());
+    }
 
-    // Extract repository name and description from selection
-    let repo_name = if let Some((name, _description_part)) = cleaned_selection.split_once(" (") {
-        // Selection has a description in parentheses
-        name.trim()
-    } else {
-        // Selection is just the repo name without description
-        cleaned_selection.trim()
-    };
+    #[test]
+    fn test_invalid_token_is_an_auth_error() {
+        assert_eq!(FetchError::InvalidToken.to_string(), "Invalid GitHub token");
+        assert!(is_auth_error(&FetchError::InvalidToken));
+
+        // Running out of requests or cancelling does not ask for a new token
+        assert!(!is_auth_error(&FetchError::Cancelled));
+        assert!(!is_auth_error(&FetchError::RateLimited(Utc::now())));
+    }
+
+    #[test]
+    fn test_rate_limit_reset() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700000000".parse().unwrap());
+
+        let resets_at = DateTime::from_timestamp(1_700_000_000, 0);
+        assert_eq!(rate_limit_reset(reqwest::StatusCode::FORBIDDEN, &headers), resets_at);
+        assert_eq!(rate_limit_reset(reqwest::StatusCode::TOO_MANY_REQUESTS, &headers), resets_at);
+        assert_eq!(rate_limit_reset(reqwest::StatusCode::OK, &headers), None);
+
+        // A 403 with requests left is a missing permission instead
+        headers.insert("x-ratelimit-remaining", "4999".parse().unwrap());
+        assert_eq!(rate_limit_reset(reqwest::StatusCode::FORBIDDEN, &headers), None);
+    }
+
+    #[test]
+    fn test_enterprise_urls() {
+        assert_eq!(api_base_uri(DEFAULT_HOST), None);
+        assert_eq!(api_base_uri("github.example.com").unwrap(), "https://github.example.com/api/v3");
+
+        assert_eq!(repo_web_url("github.example.com", "team", "api"), "https://github.example.com/team/api");
+        assert_eq!(gist_web_url(DEFAULT_HOST, "dima-369", "aa5a"), "https://gist.github.com/dima-369/aa5a");
+        assert_eq!(gist_web_url("github.example.com", "dima-369", "aa5a"), "https://github.example.com/gist/dima-369/aa5a");
+        assert_eq!(gist_clone_url("github.example.com", "aa5a"), "git@github.example.com:gist/aa5a.git");
+    }
+
+    #[test]
+    fn test_convert_gist() {
+        let gist: GitHubGist = serde_json::from_str(
+            r#"{
+                "id": "aa5a315d61ae9438b18d",
+                "description": "Hello world examples",
+                "public": true,
+                "files": {"hello.rs": {"size": 42}, "hello.py": {"size": 21}},
+                "created_at": "2020-01-01T00:00:00Z",
+                "updated_at": "2020-01-02T00:00:00Z"
+            }"#,
+        )
+        .unwrap();
+        let repo = convert_gist(gist, "dima-369");
+
+        assert_eq!(repo.name, "aa5a315d61ae9438b18d");
+        assert_eq!(repo.ssh_url, "git@gist.github.com:aa5a315d61ae9438b18d.git");
+        assert_eq!(repo.description, "Hello world examples");
+        assert_eq!(repo.owner, "dima-369");
+        assert!(!repo.is_private);
+        assert!(repo.topics.is_empty());
+        assert_eq!(repo.updated_at, Some(1_577_923_200));
+        assert_eq!(repo.created_at, Some(1_577_836_800));
+
+        // Secret gists without a description show their file names
+        let gist: GitHubGist = serde_json::from_str(
+            r#"{"id": "b2", "description": "", "public": false, "files": {"notes.md": {}}}"#,
+        )
+        .unwrap();
+        let repo = convert_gist(gist, "dima-369");
+        assert_eq!(repo.description, "notes.md");
+        assert!(repo.is_private);
+    }
+
+    fn repo_updated_at(name: &str, updated_at: Option<u64>) -> Repository {
+        Repository { name: name.to_string(), updated_at, ..Repository::default() }
+    }
+
+    #[test]
+    fn test_repo_route() {
+        assert_eq!(repo_route("dima-369", "rust-web-server"), "/repos/dima-369/rust-web-server");
+    }
+
+    #[test]
+    fn test_take_updated_since() {
+        let page = vec![
+            repo_updated_at("newest", Some(300)),
+            repo_updated_at("newer", Some(200)),
+            repo_updated_at("at-cache-time", Some(100)),
+            repo_updated_at("older", Some(50)),
+        ];
+        let (updated, reached_older) = take_updated_since(page, 100);
+        let names: Vec<String> = updated.into_iter().map(|repo| repo.name).collect();
+
+        assert_eq!(names, vec!["newest", "newer"]);
+        assert!(reached_older);
+
+        let (updated, reached_older) = take_updated_since(vec![repo_updated_at("newest", Some(300))], 100);
+        assert_eq!(updated.len(), 1);
+        assert!(!reached_older);
+    }
+
+    #[test]
+    fn test_parse_token_expiration() {
+        let expected = DateTime::parse_from_rfc3339("2021-09-13T06:47:25Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_token_expiration("2021-09-13 06:47:25 UTC"), Some(expected));
+        assert_eq!(parse_token_expiration(" 2021-09-12 23:47:25 -0700 "), Some(expected));
 
-    // Construct a URL based on the repository name and username
-    let url = format!("git@github.com:{}/{}.git", username, repo_name);
+        assert_eq!(parse_token_expiration("2021-09-13"), None);
+        assert_eq!(parse_token_expiration("never"), None);
+    }
 
-    // Extract GitHub repo path for browser URL
-    let browser_url = Some(format!("https://github.com/{}/{}", username, repo_name));
+    #[test]
+    fn test_token_expiration_from_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(token_expiration(&headers), None);
 
-    Some((repo_name.to_string(), url, browser_url))
+        headers.insert(TOKEN_EXPIRATION_HEADER, "2021-09-13 06:47:25 UTC".parse().unwrap());
+        let expected = DateTime::parse_from_rfc3339("2021-09-13T06:47:25Z").unwrap().with_timezone(&Utc);
+        assert_eq!(token_expiration(&headers), Some(expected));
+    }
 }
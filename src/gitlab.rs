@@ -1,30 +1,108 @@
+use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
-use std::io::Write;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::OnceLock;
+
+use crate::concurrency;
+use crate::retry;
+use crate::terminal;
+use crate::user_agent;
+
+// Projects are fetched in the same format as GitHub repositories
+pub use crate::github::Repository;
+
+/// Base URL of the public GitLab, used without `--gitlab-host`
+pub const DEFAULT_BASE_URL: &str = "https://gitlab.com";
+
+static BASE_URL: OnceLock<String> = OnceLock::new();
+
+/// Parses a self-hosted GitLab like `gitlab.example.com` or `https://gitlab.example.com/`
+/// into its base URL without a trailing slash.
+///
+/// A host without a scheme is served over https, and a path is kept for instances
+/// installed under one.
+pub fn parse_base_url(value: &str) -> Result<String, String> {
+    let trimmed = value.trim().trim_end_matches('/');
+    let base_url = if trimmed.contains("://") {
+        trimmed.to_string()
+    } else {
+        format!("https://{}", trimmed)
+    };
+
+    let invalid = || format!("expected a URL like https://gitlab.example.com, got '{}'", value);
+    let url = reqwest::Url::parse(&base_url).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() || url.query().is_some() || url.fragment().is_some() {
+        return Err(invalid());
+    }
+    Ok(base_url)
+}
 
-// Define our Repository type to match GitHub's format
-pub type Repository = (String, String, String, String, bool, bool); // (name, ssh_url, description, owner, is_fork, is_private)
+/// Sets the base URL all GitLab requests and URLs use, only the first call has an effect
+pub fn set_base_url(base_url: String) {
+    let _ = BASE_URL.set(base_url);
+}
+
+/// Returns the configured base URL, or the one of gitlab.com if none was set
+pub fn base_url() -> &'static str {
+    BASE_URL.get().map_or(DEFAULT_BASE_URL, String::as_str)
+}
+
+/// Returns the host SSH clone URLs use, which is the base URL without scheme, port and path
+pub fn ssh_host(base_url: &str) -> String {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "gitlab.com".to_string())
+}
+
+/// Returns the URL of an API endpoint like `projects` on an instance
+fn api_url(base_url: &str, endpoint: &str) -> String {
+    format!("{}/api/v4/{}", base_url, endpoint)
+}
+
+/// Returns the web page of a project on an instance
+pub fn project_web_url(base_url: &str, namespace: &str, name: &str) -> String {
+    format!("{}/{}/{}", base_url, namespace, name)
+}
+
+/// Returns the web page of a personal snippet on an instance
+pub fn snippet_web_url(base_url: &str, id: &str) -> String {
+    format!("{}/-/snippets/{}", base_url, id)
+}
 
 // GitLab API response structures
 #[derive(Debug, Deserialize, Clone)]
 struct GitLabProject {
-    #[allow(dead_code)]
     id: u64,
     name: String,
     description: Option<String>,
     ssh_url_to_repo: String,
-    #[allow(dead_code)]
     namespace: GitLabNamespace,
     forked_from_project: Option<GitLabForkedFrom>,
     visibility: String,
+    #[serde(default)]
+    topics: Vec<String>,
+    last_activity_at: Option<DateTime<Utc>>,
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    archived: bool,
+    star_count: Option<u64>,
+    // Missing if the project has issues disabled
+    open_issues_count: Option<u64>,
+    // Missing for empty projects
+    default_branch: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct GitLabNamespace {
     #[allow(dead_code)]
     name: String,
-    #[allow(dead_code)]
-    path: String,
+    full_path: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -33,164 +111,658 @@ struct GitLabForkedFrom {
     id: u64,
 }
 
+// GitLab snippet API response, only with the fields needed here
+#[derive(Debug, Deserialize)]
+struct GitLabSnippet {
+    id: u64,
+    title: String,
+    visibility: String,
+    ssh_url_to_repo: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// Returns the SSH clone URL of a personal snippet on an instance, which is cloned by its id
+fn snippet_clone_url(base_url: &str, id: &str) -> String {
+    format!("git@{}:snippets/{}.git", ssh_host(base_url), id)
+}
+
+// Helper function to convert a snippet to our Repository type, named by its id
+fn convert_snippet(snippet: GitLabSnippet, username: &str) -> Repository {
+    let id = snippet.id.to_string();
+    let url = snippet.ssh_url_to_repo.unwrap_or_else(|| snippet_clone_url(base_url(), &id));
+
+    Repository {
+        name: id,
+        ssh_url: url,
+        description: snippet.title,
+        owner: username.to_string(),
+        is_private: snippet.visibility != "public",
+        updated_at: snippet.updated_at.map(|updated_at| updated_at.timestamp() as u64),
+        created_at: snippet.created_at.map(|created_at| created_at.timestamp() as u64),
+        pushed_at: snippet.updated_at.map(|updated_at| updated_at.timestamp() as u64),
+        ..Repository::default()
+    }
+}
+
 // Helper function to convert GitLab project to our Repository type
 fn convert_project(project: GitLabProject, username: &str) -> Repository {
-    (
-        project.name,
-        project.ssh_url_to_repo,
-        project.description.unwrap_or_default(),
-        username.to_string(),
-        project.forked_from_project.is_some(),
-        project.visibility != "public",
-    )
+    Repository {
+        name: project.name,
+        ssh_url: project.ssh_url_to_repo,
+        description: project.description.unwrap_or_default(),
+        owner: username.to_string(),
+        is_fork: project.forked_from_project.is_some(),
+        is_private: project.visibility != "public",
+        language: None, // The projects listing does not include the language, see fetch_primary_language
+        topics: project.topics,
+        updated_at: project.last_activity_at.map(|last_activity_at| last_activity_at.timestamp() as u64),
+        created_at: project.created_at.map(|created_at| created_at.timestamp() as u64),
+        is_archived: project.archived,
+        stars: project.star_count,
+        open_issues: project.open_issues_count,
+        default_branch: project.default_branch,
+        // GitLab has no push date, the last activity includes pushes
+        pushed_at: project.last_activity_at.map(|last_activity_at| last_activity_at.timestamp() as u64),
+    }
 }
 
-// Helper function to update progress display
+// Helper function to update progress display, on stderr to keep it out of --print and --output json
 fn update_progress(page_count: usize, repos_count: usize) {
-    print!("\r                                                  "); // Clear the line
-    print!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
-    std::io::stdout().flush().unwrap();
+    eprint!("\r                                                  "); // Clear the line
+    eprint!("\rFetched page {} ({} repos so far)... ", page_count, repos_count);
 }
 
-pub async fn fetch_repos(token: &str) -> Result<(String, Vec<Repository>), Box<dyn std::error::Error>> {
-    print!("Fetching GitLab user information... ");
-    std::io::stdout().flush().unwrap();
+// Same for pages fetched at once, which finish in any order
+fn update_page_progress(fetched_pages: usize, total_pages: usize) {
+    eprint!("\r                                                  "); // Clear the line
+    eprint!("\rFetched {} of {} pages... ", fetched_pages, total_pages);
+}
 
-    // Create HTTP client with authorization header
-    let client = reqwest::Client::new();
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", token))?,
-    );
+/// Returns the language making up the largest share of a project, if any
+fn primary_language(languages: &HashMap<String, f64>) -> Option<String> {
+    languages
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(language, _)| language.clone())
+}
 
-    // Get user information
-    let response = client
-        .get("https://gitlab.com/api/v4/user")
-        .headers(headers.clone())
-        .send()
-        .await?;
+/// Fetches the primary language of a project, which the projects listing does not include
+async fn fetch_primary_language(client: reqwest::Client, headers: HeaderMap, id: u64) -> Option<String> {
+    let response = send_with_retry(client.get(api_url(base_url(), &format!("projects/{}/languages", id))).headers(headers))
+        .await
+        .ok()?;
 
-    // Check if response is successful
+    let languages: HashMap<String, f64> = response.error_for_status().ok()?.json().await.ok()?;
+    primary_language(&languages)
+}
+
+/// Returns the username of the token's user
+async fn fetch_username(client: &reqwest::Client, headers: HeaderMap) -> Result<String, FetchError> {
+    let response = send_with_retry(client.get(api_url(base_url(), "user")).headers(headers)).await?;
+
+    // Check if response is successful, which also checks the token before any projects are requested
     if !response.status().is_success() {
         let status = response.status();
         let text = response.text().await?;
-        return Err(format!("GitLab API error: {} - {}", status, text).into());
+        return Err(api_error(status, &text));
     }
 
     let user: serde_json::Value = response.json().await?;
 
     let username = user["username"]
         .as_str()
-        .ok_or("Failed to get GitLab username. Please check your GitLab token.")?
+        .ok_or_else(|| FetchError::Api("Failed to get GitLab username. Please check your GitLab token.".to_string()))?
         .to_string();
+    Ok(username)
+}
 
-    println!("✓"); // Show checkmark on its own line
-    print!("Fetching repositories for GitLab user {}... ", username);
-    std::io::stdout().flush().unwrap();
+/// Parses the `expires_at` date of a personal access token, which expires at the start of that day
+pub fn parse_token_expires_at(value: &str) -> Option<DateTime<Utc>> {
+    let date = chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
 
-    let mut all_repos = Vec::new();
-    let mut page_count = 1;
-    let per_page = 100; // Maximum allowed per page
+/// Returns when a personal access token expires, or None if it never does or is another kind of token
+async fn fetch_token_expiry(client: &reqwest::Client, headers: HeaderMap) -> Option<DateTime<Utc>> {
+    let response = send_with_retry(client.get(api_url(base_url(), "personal_access_tokens/self")).headers(headers))
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+
+    let token: serde_json::Value = response.json().await.ok()?;
+    parse_token_expires_at(token["expires_at"].as_str()?)
+}
+
+/// Items requested per page of a listing, the maximum GitLab allows
+const PER_PAGE: usize = 100;
+
+/// Why fetching the projects of the user failed
+#[derive(Debug)]
+pub enum FetchError {
+    /// The refresh was cancelled between pages
+    Cancelled,
+    /// GitLab answered 401, so the token is wrong, revoked or expired
+    InvalidToken,
+    /// Any other failure, like another error status or a dropped connection
+    Api(String),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Cancelled => write!(f, "Fetching GitLab repositories was cancelled"),
+            FetchError::InvalidToken => write!(f, "Invalid GitLab token"),
+            FetchError::Api(message) => write!(f, "GitLab error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(error: reqwest::Error) -> Self {
+        FetchError::Api(error.to_string())
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderValue> for FetchError {
+    fn from(error: reqwest::header::InvalidHeaderValue) -> Self {
+        FetchError::Api(error.to_string())
+    }
+}
+
+/// Returns the error for a failed API response, naming a rejected token instead of the body
+fn api_error(status: reqwest::StatusCode, text: &str) -> FetchError {
+    match status {
+        reqwest::StatusCode::UNAUTHORIZED => FetchError::InvalidToken,
+        _ => FetchError::Api(format!("GitLab API error: {} - {}", status, text)),
+    }
+}
 
-    // Fetch first page
-    let response = client
-        .get("https://gitlab.com/api/v4/projects")
-        .headers(headers.clone())
+/// Returns how many pages a listing has, which GitLab leaves out for very large ones
+fn total_pages(headers: &HeaderMap) -> Option<usize> {
+    headers.get("x-total-pages")?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Returns the URL of the next page from an RFC 5988 `Link` header, which the last page has none of
+fn parse_next_link(headers: &HeaderMap) -> Option<String> {
+    let links = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+
+    links.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| matches!(param.trim(), "rel=\"next\"" | "rel=next"));
+
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        is_next.then(|| url.to_string())
+    })
+}
+
+/// A page of a listing
+struct Page<T> {
+    items: Vec<T>,
+    /// How many pages there are, if GitLab tells
+    total_pages: Option<usize>,
+    next_link: Option<String>,
+}
+
+/// Returns the request of a page of the projects the user is a member of
+fn project_page_request(
+    client: &reqwest::Client,
+    headers: HeaderMap,
+    page: usize,
+    last_activity_after: &[(&str, String)],
+) -> reqwest::RequestBuilder {
+    client
+        .get(api_url(base_url(), "projects"))
+        .headers(headers)
         .query(&[
             ("membership", "true"), // Get projects user is a member of
-            ("per_page", &per_page.to_string()),
-            ("page", &page_count.to_string()),
+            ("per_page", &PER_PAGE.to_string()),
+            ("page", &page.to_string()),
         ])
-        .send()
-        .await?;
+        .query(last_activity_after)
+}
+
+/// Fetches a page of a listing, with how many pages there are and where the next one is
+async fn fetch_page<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<Page<T>, FetchError> {
+    let response = send_with_retry(request).await?;
 
     // Check if response is successful
     if !response.status().is_success() {
         let status = response.status();
         let text = response.text().await?;
-        return Err(format!("GitLab API error: {} - {}", status, text).into());
+        return Err(api_error(status, &text));
     }
 
-    // Parse the response as JSON
-    let mut projects: Vec<GitLabProject> = response.json().await?;
+    let total_pages = total_pages(response.headers());
+    let next_link = parse_next_link(response.headers());
+    let items = response.json().await?;
+    Ok(Page { items, total_pages, next_link })
+}
 
-    // Add repos from the first page
-    all_repos.extend(
-        projects.clone()
-            .into_iter()
-            .map(|project| convert_project(project, &username))
+/// Fetches the pages after a first one by following their next links, one by one.
+///
+/// Every page is passed to `add_page` as it arrives. Setting `cancel` stops before the next page.
+async fn follow_next_links<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    mut next_link: Option<String>,
+    cancel: &AtomicBool,
+    mut add_page: impl FnMut(Vec<T>),
+) -> Result<(), FetchError> {
+    while let Some(next) = next_link {
+        // Add a small sleep to allow Ctrl+C to be processed
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        if terminal::should_stop(cancel) {
+            return Err(FetchError::Cancelled);
+        }
+
+        let page = fetch_page(client.get(next).headers(headers.clone())).await?;
+        next_link = page.next_link;
+        add_page(page.items);
+    }
+    Ok(())
+}
+
+/// Fetches every page of a listing, starting with the one of `request`
+async fn fetch_all_pages<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    request: reqwest::RequestBuilder,
+    cancel: &AtomicBool,
+) -> Result<Vec<T>, FetchError> {
+    let first_page = fetch_page(request.headers(headers.clone())).await?;
+    let mut items = first_page.items;
+    follow_next_links(client, headers, first_page.next_link, cancel, |page| items.extend(page)).await?;
+    Ok(items)
+}
+
+/// Returns the authorization headers of a token
+fn auth_headers(token: &str) -> Result<HeaderMap, FetchError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token))?,
     );
+    Ok(headers)
+}
 
-    update_progress(page_count, all_repos.len());
+/// Fetches the projects the authenticated user is a member of, and when the token expires if it does.
+///
+/// With `updated_since`, only projects with activity after that timestamp are fetched.
+/// With `language_concurrency`, the primary language of every project is fetched too,
+/// with at most that many requests at once. Pages after the first are fetched with at
+/// most `page_concurrency` requests at once, if GitLab tells how many there are.
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_repos(
+    token: &str,
+    updated_since: Option<u64>,
+    language_concurrency: Option<usize>,
+    page_concurrency: usize,
+    known_username: Option<&str>,
+    cancel: &Arc<AtomicBool>,
+) -> Result<(String, Vec<Repository>, Option<DateTime<Utc>>), FetchError> {
+    // Create HTTP client with authorization header
+    let client = user_agent::gitlab_client()?;
+    let headers = auth_headers(token)?;
+
+    // A username known from the cache skips the user call
+    let username = match known_username {
+        Some(username) => username.to_string(),
+        None => {
+            eprint!("Fetching GitLab user information... ");
+
+            let username = fetch_username(&client, headers.clone()).await?;
+            eprintln!("✓"); // Show checkmark on its own line
+            username
+        }
+    };
 
-    // Fetch all remaining pages
-    while !projects.is_empty() && projects.len() == per_page {
-        // Add a small sleep to allow Ctrl+C to be processed
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    // The user response has no expiry, unlike GitHub's, so ask for the token itself
+    let expires_at = fetch_token_expiry(&client, headers.clone()).await;
+
+    eprint!("Fetching repositories for GitLab user {}... ", username);
 
-        page_count += 1;
-
-        let response = client
-            .get("https://gitlab.com/api/v4/projects")
-            .headers(headers.clone())
-            .query(&[
-                ("membership", "true"),
-                ("per_page", &per_page.to_string()),
-                ("page", &page_count.to_string()),
-            ])
-            .send()
+    let mut all_repos = Vec::new();
+    // Project ids in the same order as all_repos, for fetching languages
+    let mut project_ids = Vec::new();
+    let mut page_count = 1;
+
+    // Only request projects with activity since the given time, if any
+    let last_activity_after: Vec<(&str, String)> = updated_since
+        .and_then(|since| DateTime::<Utc>::from_timestamp(since as i64, 0))
+        .map(|since| vec![("last_activity_after", since.to_rfc3339())])
+        .unwrap_or_default();
+
+    let fetch_page = |page: usize| fetch_page::<GitLabProject>(project_page_request(&client, headers.clone(), page, &last_activity_after));
+    let mut add_projects = |projects: Vec<GitLabProject>, all_repos: &mut Vec<Repository>| {
+        project_ids.extend(projects.iter().map(|project| project.id));
+        all_repos.extend(projects.into_iter().map(|project| convert_project(project, &username)));
+    };
+
+    // Fetch first page, which tells how many pages there are
+    let first_page = fetch_page(page_count).await?;
+    let next_link = first_page.next_link;
+    add_projects(first_page.items, &mut all_repos);
+
+    update_progress(page_count, all_repos.len());
+
+    match first_page.total_pages {
+        // Fetch the remaining pages at once, in their order
+        Some(total_pages) => {
+            let fetched_pages = Arc::new(AtomicUsize::new(page_count));
+            let pages = concurrency::map_bounded((page_count + 1..=total_pages).collect(), page_concurrency, |page| {
+                let fetched_pages = fetched_pages.clone();
+                let cancel = cancel.clone();
+                let projects = fetch_page(page);
+                async move {
+                    if terminal::should_stop(&cancel) {
+                        return Err(FetchError::Cancelled);
+                    }
+                    let projects = projects.await;
+                    let done = fetched_pages.fetch_add(1, Ordering::SeqCst) + 1;
+                    update_page_progress(done, total_pages);
+                    projects
+                }
+            })
+            .await;
+
+            for page in pages {
+                add_projects(page?.items, &mut all_repos);
+                page_count += 1;
+            }
+        }
+        // Without the header, follow the next links one by one
+        None => {
+            follow_next_links(&client, &headers, next_link, cancel, |projects| {
+                page_count += 1;
+                add_projects(projects, &mut all_repos);
+                update_progress(page_count, all_repos.len());
+            })
             .await?;
+        }
+    }
+
+    eprintln!("✓"); // Show checkmark on its own line
+    eprintln!("Fetched {} GitLab repositories from {} pages", all_repos.len(), page_count);
 
-        // Check if response is successful
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await?;
-            return Err(format!("GitLab API error: {} - {}", status, text).into());
+    if let Some(max_concurrency) = language_concurrency {
+        eprint!("Fetching GitLab languages... ");
+
+        let languages = concurrency::map_bounded(project_ids, max_concurrency, |id| {
+            fetch_primary_language(client.clone(), headers.clone(), id)
+        })
+        .await;
+
+        for (repo, language) in all_repos.iter_mut().zip(languages) {
+            repo.language = language;
         }
+        eprintln!("✓");
+    }
 
-        // Parse the response as JSON
-        projects = response.json().await?;
+    Ok((username, all_repos, expires_at))
+}
 
-        all_repos.extend(
-            projects.clone()
-                .into_iter()
-                .map(|project| convert_project(project, &username))
-        );
+/// Fetches the personal snippets of the authenticated user.
+///
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_snippets(token: &str, username: &str, cancel: &AtomicBool) -> Result<Vec<Repository>, FetchError> {
+    let client = user_agent::gitlab_client()?;
+    let headers = auth_headers(token)?;
+
+    let request = client
+        .get(api_url(base_url(), "snippets"))
+        .query(&[("per_page", PER_PAGE)]);
+    let snippets: Vec<GitLabSnippet> = fetch_all_pages(&client, &headers, request, cancel).await?;
+
+    Ok(snippets.into_iter().map(|snippet| convert_snippet(snippet, username)).collect())
+}
+
+/// Fetches the projects starred by the authenticated user, owned by their namespace.
+///
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_starred(token: &str, cancel: &AtomicBool) -> Result<Vec<Repository>, FetchError> {
+    let client = user_agent::gitlab_client()?;
+    let headers = auth_headers(token)?;
+
+    let request = client
+        .get(api_url(base_url(), "projects"))
+        .query(&[("starred", "true"), ("per_page", &PER_PAGE.to_string())]);
+    let projects: Vec<GitLabProject> = fetch_all_pages(&client, &headers, request, cancel).await?;
+
+    Ok(projects
+        .into_iter()
+        .map(|project| {
+            let owner = project.namespace.full_path.clone();
+            convert_project(project, &owner)
+        })
+        .collect())
+}
+
+/// Fetches the projects of a group and its subgroups, owned by the group path.
+///
+/// Setting `cancel` stops before the next page.
+pub async fn fetch_group_repos(token: &str, group: &str, cancel: &AtomicBool) -> Result<Vec<Repository>, FetchError> {
+    let client = user_agent::gitlab_client()?;
+    let headers = auth_headers(token)?;
+    let group = group.trim_matches('/');
+
+    let request = client
+        .get(group_projects_api_url(base_url(), group))
+        .query(&[("include_subgroups", "true"), ("per_page", &PER_PAGE.to_string())]);
+    let projects: Vec<GitLabProject> = fetch_all_pages(&client, &headers, request, cancel).await?;
+
+    Ok(projects.into_iter().map(|project| convert_project(project, group)).collect())
+}
+
+/// Check if a request failed in a way that may not happen again, like a 5xx or a dropped connection
+fn is_transient_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || error.status().is_some_and(|status| status.is_server_error())
+}
+
+/// Sends a request, retrying it with backoff on transient errors.
+///
+/// Other error statuses are returned as responses, so their body can be reported.
+async fn send_with_retry(request: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+    retry::with_backoff(is_transient_error, || {
+        // Requests without a streamed body can always be cloned
+        let request = request.try_clone().expect("requests without a body can be cloned");
+        async move {
+            let response = request.send().await?;
+            if response.status().is_server_error() {
+                response.error_for_status_ref()?;
+            }
+            Ok(response)
+        }
+    })
+    .await
+}
+
+/// Check if GitLab rejected the token
+pub fn is_auth_error(error: &FetchError) -> bool {
+    matches!(error, FetchError::InvalidToken)
+}
 
-        update_progress(page_count, all_repos.len());
+/// Returns the API URL of a single project on an instance, whose full path has to be URL-encoded
+fn project_api_url(base_url: &str, namespace: &str, name: &str) -> String {
+    api_url(base_url, &format!("projects/{}%2F{}", namespace.replace('/', "%2F"), name))
+}
+
+/// Returns the API URL listing the projects of a group, whose full path has to be URL-encoded
+fn group_projects_api_url(base_url: &str, group: &str) -> String {
+    api_url(base_url, &format!("groups/{}/projects", group.replace('/', "%2F")))
+}
+
+/// Fetches a single project by namespace and name, without listing all projects
+pub async fn fetch_repo(token: &str, namespace: &str, name: &str) -> Result<Repository, FetchError> {
+    let client = user_agent::gitlab_client()?;
+    let response = send_with_retry(client.get(project_api_url(base_url(), namespace, name)).headers(auth_headers(token)?)).await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await?;
+        return Err(api_error(status, &text));
     }
 
-    println!("✓"); // Show checkmark on its own line
-    println!("Fetched {} GitLab repositories from {} pages", all_repos.len(), page_count);
-    Ok((username, all_repos))
+    let project: GitLabProject = response.json().await?;
+    Ok(convert_project(project, namespace))
 }
 
-pub fn extract_repo_info(selection: &str, username: &str) -> Option<(String, String, Option<String>)> {
-    // First, remove the GitLab indicator [GL] if present
-    let cleaned_selection = selection.replace(" [GL]", "");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primary_language() {
+        let languages = HashMap::from([
+            ("Shell".to_string(), 12.5),
+            ("Rust".to_string(), 80.0),
+            ("Dockerfile".to_string(), 7.5),
+        ]);
+        assert_eq!(primary_language(&languages), Some("Rust".to_string()));
+        assert_eq!(primary_language(&HashMap::new()), None);
+    }
 
-    // Remove the private indicator if present
-    let cleaned_selection = cleaned_selection.replace(" 🔒", "");
+    #[test]
+    fn test_is_auth_error() {
+        let unauthorized = api_error(reqwest::StatusCode::UNAUTHORIZED, "{\"message\":\"401 Unauthorized\"}");
+        assert!(is_auth_error(&unauthorized));
 
-    // Extract repository name and description from selection
-    let repo_name = if let Some((name, _description_part)) = cleaned_selection.split_once(" (") {
-        // Selection has a description in parentheses
-        name.trim()
-    } else {
-        // Selection is just the repo name without description
-        cleaned_selection.trim()
-    };
+        let not_found = api_error(reqwest::StatusCode::NOT_FOUND, "{}");
+        assert!(!is_auth_error(&not_found));
+        assert!(!is_auth_error(&FetchError::Api("error sending request".to_string())));
+        assert!(!is_auth_error(&FetchError::Cancelled));
+    }
+
+    #[test]
+    fn test_rejected_token_error() {
+        let error = api_error(reqwest::StatusCode::UNAUTHORIZED, "{\"message\":\"401 Unauthorized\"}");
+        assert_eq!(error.to_string(), "Invalid GitLab token");
+
+        // Other failures keep the status and body
+        let error = api_error(reqwest::StatusCode::BAD_GATEWAY, "{}");
+        assert_eq!(error.to_string(), format!("GitLab error: GitLab API error: {} - {{}}", reqwest::StatusCode::BAD_GATEWAY));
+    }
+
+    #[test]
+    fn test_project_api_url() {
+        assert_eq!(
+            project_api_url(DEFAULT_BASE_URL, "gira", "notes"),
+            "https://gitlab.com/api/v4/projects/gira%2Fnotes"
+        );
+
+        // Subgroups are part of the encoded path
+        assert_eq!(
+            project_api_url(DEFAULT_BASE_URL, "company/team", "api"),
+            "https://gitlab.com/api/v4/projects/company%2Fteam%2Fapi"
+        );
+    }
 
-    // Convert repo name to kebab-case for GitLab URLs
-    // This is a simple conversion that replaces spaces with hyphens and makes lowercase
-    let repo_path = repo_name.to_lowercase().replace(" ", "-");
+    #[test]
+    fn test_group_projects_api_url() {
+        assert_eq!(
+            group_projects_api_url(DEFAULT_BASE_URL, "company/team"),
+            "https://gitlab.com/api/v4/groups/company%2Fteam/projects"
+        );
+        assert_eq!(
+            group_projects_api_url("https://git.example.com", "company"),
+            "https://git.example.com/api/v4/groups/company/projects"
+        );
+    }
 
-    // Construct a URL based on the repository name and username
-    let url = format!("git@gitlab.com:{}/{}.git", username, repo_path);
+    #[test]
+    fn test_parse_base_url() {
+        assert_eq!(parse_base_url("gitlab.example.com"), Ok("https://gitlab.example.com".to_string()));
+        assert_eq!(parse_base_url("http://gitlab.example.com:8080/"), Ok("http://gitlab.example.com:8080".to_string()));
+        assert!(parse_base_url("").is_err());
+        assert!(parse_base_url("ftp://gitlab.example.com").is_err());
+        assert!(parse_base_url("gitlab example").is_err());
+    }
 
-    // Extract GitLab repo path for browser URL
-    let browser_url = Some(format!("https://gitlab.com/{}/{}", username, repo_path));
+    #[test]
+    fn test_parse_next_link() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::LINK,
+            HeaderValue::from_static(
+                "<https://gitlab.com/api/v4/projects?page=1&per_page=100>; rel=\"prev\", \
+                 <https://gitlab.com/api/v4/projects?page=3&per_page=100>; rel=\"next\", \
+                 <https://gitlab.com/api/v4/projects?page=1&per_page=100>; rel=\"first\"",
+            ),
+        );
+        assert_eq!(
+            parse_next_link(&headers),
+            Some("https://gitlab.com/api/v4/projects?page=3&per_page=100".to_string())
+        );
+
+        // The last page links back, but not onwards
+        headers.insert(
+            reqwest::header::LINK,
+            HeaderValue::from_static(
+                "<https://gitlab.com/api/v4/projects?page=2&per_page=100>; rel=\"prev\", \
+                 <https://gitlab.com/api/v4/projects?page=1&per_page=100>; rel=\"first\"",
+            ),
+        );
+        assert_eq!(parse_next_link(&headers), None);
+        assert_eq!(parse_next_link(&HeaderMap::new()), None);
+    }
 
-    Some((repo_name.to_string(), url, browser_url))
+    #[test]
+    fn test_total_pages() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(total_pages(&headers), None);
+
+        headers.insert("x-total-pages", HeaderValue::from_static("7"));
+        assert_eq!(total_pages(&headers), Some(7));
+
+        // Very large listings send an empty header
+        headers.insert("x-total-pages", HeaderValue::from_static(""));
+        assert_eq!(total_pages(&headers), None);
+    }
+
+    #[test]
+    fn test_self_hosted_urls() {
+        let base_url = "https://gitlab.example.com:8443/gitlab";
+        assert_eq!(api_url(base_url, "user"), "https://gitlab.example.com:8443/gitlab/api/v4/user");
+        assert_eq!(project_web_url(base_url, "team", "api"), "https://gitlab.example.com:8443/gitlab/team/api");
+        assert_eq!(snippet_web_url(base_url, "2814"), "https://gitlab.example.com:8443/gitlab/-/snippets/2814");
+
+        // SSH clones go to the host alone
+        assert_eq!(snippet_clone_url(base_url, "2814"), "git@gitlab.example.com:snippets/2814.git");
+    }
+
+    #[test]
+    fn test_convert_snippet() {
+        let snippet: GitLabSnippet = serde_json::from_str(
+            r#"{
+                "id": 2814,
+                "title": "Shell aliases",
+                "visibility": "private",
+                "file_name": "aliases.sh",
+                "created_at": "2020-01-01T00:00:00Z",
+                "updated_at": "2020-01-02T00:00:00Z"
+            }"#,
+        )
+        .unwrap();
+        let repo = convert_snippet(snippet, "gira");
+
+        assert_eq!(repo.name, "2814");
+        assert_eq!(repo.ssh_url, "git@gitlab.com:snippets/2814.git");
+        assert_eq!(repo.description, "Shell aliases");
+        assert_eq!(repo.owner, "gira");
+        assert!(repo.is_private);
+        assert!(repo.topics.is_empty());
+        assert_eq!(repo.updated_at, Some(1_577_923_200));
+    }
+
+    #[test]
+    fn test_parse_token_expires_at() {
+        let expected = DateTime::parse_from_rfc3339("2025-01-31T00:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_token_expires_at("2025-01-31"), Some(expected));
+        assert_eq!(parse_token_expires_at("31.01.2025"), None);
+    }
 }
@@ -0,0 +1,101 @@
+//! Labeled sections of the fuzzy finder list, set with `--group-by`
+//!
+//! The matching repositories are partitioned into sections which keep the order of
+//! the matches within them. Each section gets a header row which cannot be selected,
+//! and sections without matches are left out.
+
+use crate::cache::RepoData;
+
+/// How the finder list is split into sections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    #[default]
+    None,
+    Visibility,
+}
+
+impl GroupBy {
+    pub const NAMES: &'static [&'static str] = &["none", "visibility"];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(GroupBy::None),
+            "visibility" => Ok(GroupBy::Visibility),
+            _ => Err(format!("unknown grouping '{}', expected one of {}", value, Self::NAMES.join(", "))),
+        }
+    }
+
+    /// Returns the section of a repository, or None without grouping
+    pub fn section(self, repo: &RepoData) -> Option<Section> {
+        match self {
+            GroupBy::None => None,
+            GroupBy::Visibility if repo.is_private => Some(Section::Private),
+            GroupBy::Visibility => Some(Section::Public),
+        }
+    }
+}
+
+/// A section of the list, in the order the sections are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Public,
+    Private,
+}
+
+impl Section {
+    pub fn label(self) -> &'static str {
+        match self {
+            Section::Public => "Public",
+            Section::Private => "Private",
+        }
+    }
+}
+
+/// Moves the items into their sections, keeping their order within each section
+pub fn partition<T>(items: &mut [T], section_of: impl Fn(&T) -> Option<Section>) {
+    items.sort_by_key(|item| section_of(item));
+}
+
+/// Returns the sections of partitioned items with how many items each has.
+///
+/// Sections without items are left out, so their headers are hidden.
+pub fn sections<T>(items: &[T], section_of: impl Fn(&T) -> Option<Section>) -> Vec<(Section, usize)> {
+    let mut sections: Vec<(Section, usize)> = Vec::new();
+    for section in items.iter().filter_map(section_of) {
+        match sections.last_mut() {
+            Some((last, count)) if *last == section => *count += 1,
+            _ => sections.push((section, 1)),
+        }
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_into_sections() {
+        // Private and public repositories by name, in the order they matched
+        let mut items = vec![("private-api", true), ("rust-web", false), ("notes", true), ("dotfiles", false)];
+        let section_of = |&(_, private): &(&str, bool)| Some(if private { Section::Private } else { Section::Public });
+
+        partition(&mut items, section_of);
+        let names: Vec<&str> = items.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["rust-web", "dotfiles", "private-api", "notes"]);
+        assert_eq!(sections(&items, section_of), vec![(Section::Public, 2), (Section::Private, 2)]);
+
+        // A filter leaving only private repositories hides the public section
+        items.retain(|(_, private)| *private);
+        assert_eq!(sections(&items, section_of), vec![(Section::Private, 2)]);
+
+        // Without grouping there are no sections
+        assert!(sections(&items, |_| None).is_empty());
+    }
+
+    #[test]
+    fn test_parse_group_by() {
+        assert_eq!(GroupBy::parse("visibility"), Ok(GroupBy::Visibility));
+        assert!(GroupBy::parse("owner").unwrap_err().contains("expected one of none, visibility"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Logging to a file for `--verbose`, since the fuzzy finder owns the terminal
+
+use crate::cache;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const LOG_FILE: &str = "repo-searcher.log";
+
+/// Returns where the log is written, in the directory of the cache
+pub fn log_path() -> PathBuf {
+    cache::cache_path().with_file_name(LOG_FILE)
+}
+
+/// Appends every log record as one line to a file
+struct FileLogger {
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(
+                file,
+                "{} {} {}: {}",
+                chrono::Local::now().format("%H:%M:%S%.3f"),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Logs everything from debug level up to the log file
+pub fn init_verbose() -> Result<(), Box<dyn std::error::Error>> {
+    let path = log_path();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    // The logger lives until the program exits
+    let logger = Box::leak(Box::new(FileLogger { file: Mutex::new(file) }));
+    log::set_logger(logger).map_err(|e| e.to_string())?;
+    log::set_max_level(LevelFilter::Debug);
+    Ok(())
+}
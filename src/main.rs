@@ -1,19 +1,236 @@
 use std::error::Error;
 use std::process;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 
+mod aliases;
+mod basket;
 mod browser;
 mod cache;
+mod category;
 mod cli;
+mod clipboard;
+mod concurrency;
+mod config;
+mod dedup;
+mod export;
 mod filter;
 mod formatter;
 mod fuzzy_finder;
+mod git;
 mod github;
 mod gitlab;
+mod grouping;
+mod logger;
+mod masked_input;
+mod menu;
+mod multiplexer;
+mod quick_filter;
+mod recording;
 mod repository;
+mod retry;
+mod simple_prompt;
+mod sort;
+mod state;
 mod terminal;
+mod user_agent;
 
 use tokio::sync::mpsc;
 
+/// Check if a repository changed since the cache that was on disk at startup
+fn is_new(repo: &cache::RepoData, previous_timestamp: Option<u64>) -> bool {
+    previous_timestamp.is_some_and(|timestamp| cache::changed_since(repo, timestamp))
+}
+
+/// Removes the repositories hidden by the exclusions
+fn hide_excluded(repos: &mut Vec<cache::RepoData>, args: &cli::AppArgs) {
+    // Hide repositories excluded by language or topic
+    repos.retain(|repo| !filter::is_excluded(repo, &args.exclude_languages, &args.exclude_topics));
+
+    // Gists cached by an earlier run are only shown when asked for
+    if !args.include_gists {
+        repos.retain(|repo| !repo.is_gist());
+    }
+
+    // Same for starred repositories
+    if !args.include_starred {
+        repos.retain(|repo| !repo.is_starred);
+    }
+
+    if args.hide_archived {
+        repos.retain(|repo| !repo.is_archived);
+    }
+
+    // Gists and snippets belong to the platform they are on
+    if let Some(source) = args.source {
+        repos.retain(|repo| repo.source == source);
+    }
+}
+
+/// Compares repositories in the order `prepare_repos` sorts them in
+fn compare_for_display(
+    a: &cache::RepoData,
+    b: &cache::RepoData,
+    args: &cli::AppArgs,
+    previous_timestamp: Option<u64>,
+    now: u64,
+) -> std::cmp::Ordering {
+    let new_first = if args.highlight_new {
+        is_new(b, previous_timestamp).cmp(&is_new(a, previous_timestamp))
+    } else {
+        std::cmp::Ordering::Equal
+    };
+
+    new_first.then_with(|| match &args.sort {
+        Some(sort_spec) => sort::compare_spec(a, b, sort_spec, &args.activity_weights, now),
+        None => std::cmp::Ordering::Equal,
+    })
+}
+
+/// Applies the exclusions and the sort order to a batch of repositories and merges it
+/// into the already prepared ones
+fn merge_prepared(
+    mut prepared: Vec<cache::RepoData>,
+    mut batch: Vec<cache::RepoData>,
+    args: &cli::AppArgs,
+    previous_timestamp: Option<u64>,
+) -> Vec<cache::RepoData> {
+    hide_excluded(&mut batch, args);
+    if args.dedup {
+        dedup::collapse_mirrors(&mut prepared, &mut batch);
+    }
+    let now = sort::now_secs();
+    sort::merge_sorted(prepared, batch, |a, b| compare_for_display(a, b, args, previous_timestamp, now))
+}
+
+/// Applies the exclusions and the sort order to loaded repositories
+fn prepare_repos(repos: &mut Vec<cache::RepoData>, args: &cli::AppArgs, previous_timestamp: Option<u64>) {
+    hide_excluded(repos, args);
+
+    // Collapse mirrors before sorting, so the first fetched source is the one kept
+    if args.dedup {
+        dedup::collapse_mirrors(&mut [], repos);
+    }
+
+    if let Some(sort_spec) = &args.sort {
+        sort::sort_repos(repos, sort_spec, &args.activity_weights);
+    }
+
+    if args.highlight_new {
+        // The sort is stable, so both groups keep their order
+        repos.sort_by_key(|repo| !is_new(repo, previous_timestamp));
+    }
+}
+
+/// Exits with an error if a source required with `--require-nonempty` has no repositories
+fn exit_unless_required_sources(
+    args: &cli::AppArgs,
+    repos: &[cache::RepoData],
+    failed_sources: &[formatter::RepoSource],
+) {
+    if let Err(e) = repository::check_required_sources(&args.require_nonempty, repos, failed_sources) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Formats a repository for the fuzzy finder, marking it if it changed since the previous cache.
+///
+/// The badges like stars, language icons and categories are only added to the default
+/// layout, a `--format` template shows exactly its placeholders.
+fn format_choice(
+    repo: &cache::RepoData,
+    previous_timestamp: Option<u64>,
+    category_order: &category::CategoryOrder,
+    template: &formatter::DisplayTemplate,
+    options: formatter::FormatOptions,
+) -> String {
+    let formatted = formatter::format_repository(repo, template, options);
+    let formatted = if template.is_default() {
+        mark_badges(&formatted, repo, category_order, options)
+    } else {
+        formatted
+    };
+
+    if is_new(repo, previous_timestamp) {
+        formatter::mark_new(&formatted, options)
+    } else {
+        formatted
+    }
+}
+
+/// Appends the star count, language icon, category and markers of a formatted repository
+fn mark_badges(
+    formatted: &str,
+    repo: &cache::RepoData,
+    category_order: &category::CategoryOrder,
+    options: formatter::FormatOptions,
+) -> String {
+    let formatted = formatter::mark_stars(formatted, repo.stars, options);
+    let formatted = formatter::mark_language(&formatted, repo.language.as_deref(), options);
+    let emoji = category::get_category_emoji(&repo.name, &repo.description, category_order, options);
+    let formatted = formatter::mark_category(&formatted, emoji.as_deref());
+    let formatted = if repo.is_starred { formatter::mark_starred(&formatted, options) } else { formatted };
+    let formatted = if repo.is_archived { formatter::mark_archived(&formatted, options) } else { formatted };
+    if repo.mirrored { formatter::mark_mirrored(&formatted, options) } else { formatted }
+}
+
+/// Runs the action picked for a selected repository, from the fuzzy finder or the simple prompt.
+///
+/// Actions on several repositories get them as `several`: the basket or the marked ones.
+async fn run_action(
+    action: fuzzy_finder::FinderAction,
+    repo: Option<&cache::RepoData>,
+    several: &[cache::RepoData],
+    args: &cli::AppArgs,
+) {
+    match (action, repo) {
+        (fuzzy_finder::FinderAction::CopyUrls, _) => {
+            let urls: Vec<&str> = several.iter().map(|repo| repo.url.as_str()).collect();
+            match clipboard::copy_to_clipboard(&urls.join("\n")) {
+                Ok(_) => println!("Copied {} SSH URLs to the clipboard", urls.len()),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                }
+            }
+        }
+        // The basket windows are the only action without a selected repository
+        (fuzzy_finder::FinderAction::BasketWindows, _) => {
+            match multiplexer::open_windows(args.multiplexer, &args.clone_command, several, &args.clone_dir) {
+                Ok(count) => println!("Opened {} windows", count),
+                Err(e) => {
+                    eprintln!("Error opening windows: {}", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                }
+            }
+        }
+        (_, None) => eprintln!("Error: No repository selected"),
+        // The menu reads lines from stdin, which a replay has no keys for
+        (fuzzy_finder::FinderAction::Open, Some(repo)) if args.replay.is_some() => {
+            println!("Repository: {}", repo.name);
+            println!("Skipping the action menu while replaying");
+        }
+        (fuzzy_finder::FinderAction::Open, Some(repo)) => {
+            if let Err(e) = repository::process_repository_selection(repo, args.post_action_delay_ms).await {
+                eprintln!("Error processing repository: {}", e);
+                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            }
+        }
+        (fuzzy_finder::FinderAction::GitTui, Some(repo)) => {
+            if let Err(e) = repository::open_selection_in_git_tui(repo, &args.clone_dir, &args.git_tui) {
+                eprintln!("Error opening repository in {}: {}", args.git_tui, e);
+                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            }
+        }
+        (fuzzy_finder::FinderAction::Tree, Some(repo)) => {
+            if let Err(e) = repository::open_selection_tree(repo, args.path.as_deref(), args.post_action_delay_ms).await {
+                eprintln!("Error opening the file browser: {}", e);
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Set up global Ctrl+C handler
@@ -21,40 +238,200 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Parse command line arguments
     let args = cli::parse_args();
+    user_agent::set_user_agent(args.user_agent.clone());
+    github::set_host(args.github_host.clone());
+    gitlab::set_base_url(args.gitlab_base_url.clone());
+
+    // Log details like filter timings to a file, as the fuzzy finder owns the terminal
+    if args.verbose {
+        if let Err(e) = logger::init_verbose() {
+            eprintln!("Error opening {}: {}", logger::log_path().display(), e);
+        }
+    }
+
+    // Only delete the cache if requested
+    if args.clear_cache {
+        if let Err(e) = cache::clear_cache() {
+            eprintln!("Error deleting the cache: {}", e);
+            process::exit(1);
+        }
+        println!("Cache cleared, the next run fetches all repositories");
+        process::exit(0);
+    }
+
+    // Only check the cache if requested
+    if args.validate_cache {
+        let issues = cache::validate_cache_file();
+        if issues.is_empty() {
+            println!("Cache is valid");
+            process::exit(0);
+        }
+
+        eprintln!("Found {} cache issue(s):", issues.len());
+        for issue in &issues {
+            eprintln!("  {}", issue);
+        }
+        process::exit(1);
+    }
+
+    // Only look up a single repository if requested
+    if let Some(spec) = &args.repo {
+        if let Err(e) = repository::open_single_repository(&args, spec).await {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Only empty the basket if requested
+    if args.clear_basket {
+        let mut basket = basket::load_basket();
+        basket.clear();
+        if let Err(e) = basket::save_basket(&basket) {
+            eprintln!("Error clearing the basket: {}", e);
+            process::exit(1);
+        }
+        println!("Basket cleared");
+        process::exit(0);
+    }
+
+    // Only print what changed since the cache if requested
+    if args.show_diff {
+        let previous = cache::load_cache().map(|c| c.get_all_repositories()).unwrap_or_default();
+
+        let mut fetch_args = args.clone();
+        fetch_args.force_download = true;
+        let (fresh, failed_sources) = repository::load_all_repositories(&fetch_args).await;
+        exit_unless_required_sources(&args, &fresh, &failed_sources);
+
+        // Sources which failed to fetch would otherwise show up as removed
+        let previous: Vec<cache::RepoData> = previous
+            .into_iter()
+            .filter(|repo| fresh.iter().any(|r| r.source == repo.source))
+            .collect();
+
+        let diff = cache::diff_repositories(&previous, &fresh);
+        println!("Changes since the cache: {}", diff.summary());
+        for line in diff.detail_lines() {
+            println!("  {}", line);
+        }
+        process::exit(0);
+    }
+
+    // Only write all repositories as CSV if requested
+    if let Some(path) = &args.csv {
+        let (mut repos, failed_sources) = repository::load_all_repositories(&args).await;
+        exit_unless_required_sources(&args, &repos, &failed_sources);
+        prepare_repos(&mut repos, &args, None);
+
+        if let Err(e) = export::write_csv_file(path, &repos) {
+            eprintln!("Error writing {}: {}", path, e);
+            process::exit(1);
+        }
+        println!("Wrote {} repositories to {}", repos.len(), path);
+        process::exit(0);
+    }
+
+    // Only print the matching repositories if requested, without touching the terminal
+    if args.print || args.output_json {
+        // Any cache is good enough here, a fetch only happens without one
+        let use_cache = !args.force_download && !args.use_dummy && !args.basket;
+        let mut repos = match cache::load_cache().filter(|_| use_cache) {
+            Some(cache_data) => cache_data.get_all_repositories(),
+            None => {
+                let (repos, failed_sources) = repository::load_all_repositories(&args).await;
+                exit_unless_required_sources(&args, &repos, &failed_sources);
+                repos
+            }
+        };
+        prepare_repos(&mut repos, &args, None);
+
+        let matching = filter::filter_human(&repos, &args.query, |repo| {
+            format_choice(repo, None, &args.category_order, &args.display_template, args.format_options)
+        });
+        if args.output_json {
+            match export::to_json(&matching) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    eprintln!("Error serializing the repositories: {}", e);
+                    process::exit(1);
+                }
+            }
+        } else {
+            for repo in matching {
+                println!("{}", repo.url);
+            }
+        }
+        process::exit(0);
+    }
+
+    // Use the line-based prompt instead of the fuzzy finder if requested
+    if args.simple {
+        let (mut repos, _) = repository::load_all_repositories(&args).await;
+        prepare_repos(&mut repos, &args, None);
+
+        let items = repos
+            .iter()
+            .map(|repo| format_choice(repo, None, &args.category_order, &args.display_template, args.format_options))
+            .collect();
+        let mut prompt = simple_prompt::SimplePrompt::new(items);
+        prompt.refine(&args.query);
+
+        while let Some((action, id)) = prompt.run() {
+            run_action(action, Some(&repos[id]), &[], &args).await;
+            if terminal::shutdown_requested() {
+                break;
+            }
+        }
+        println!("No selection made");
+        process::exit(0);
+    }
 
     // Use the RepoData struct from the cache module
     use cache::RepoData;
 
-    // Initialize repository data and usernames
+    // The finder hides archived repositories with its Ctrl+X filter instead, so they can be shown again
+    let archived_filter = if args.hide_archived {
+        filter::ArchivedFilter::Active
+    } else {
+        filter::ArchivedFilter::All
+    };
+    let args = cli::AppArgs { hide_archived: false, ..args };
+
+    // Initialize repository data
     let mut all_repos: Vec<RepoData> = Vec::new();
-    let mut github_username = String::new();
-    let mut gitlab_username = String::new();
 
     // Create a channel for repository updates
     let (tx, mut rx) = mpsc::channel::<repository::RepoUpdateMessage>(100);
 
     // Create a channel for updating the fuzzy finder
-    let (update_tx, mut update_rx) = mpsc::channel::<(Vec<String>, String)>(100);
+    let (update_tx, update_rx) = mpsc::channel::<fuzzy_finder::FinderUpdate>(100);
+
+    // Cancels the background refresh when it is started over from the finder
+    let cancel = Arc::new(AtomicBool::new(false));
 
     // Load repositories based on the mode (dummy or real)
-    if args.use_dummy {
+    let previous_timestamp = if args.use_dummy {
         // Use dummy data for testing
-        repository::load_dummy_repositories(
-            &mut all_repos,
-            &mut github_username,
-            &mut gitlab_username,
-        );
+        repository::load_dummy_repositories(&mut all_repos);
+        None
+    } else if args.basket {
+        // Only review the repositories collected in the basket
+        repository::load_basket_repositories(&mut all_repos);
+        None
     } else {
         // Load real repositories with background refresh
         repository::load_repositories_with_background_refresh(
             &args,
             &mut all_repos,
-            &mut github_username,
-            &mut gitlab_username,
             tx.clone(),
+            cancel.clone(),
         )
-        .await?;
-    }
+        .await?
+    };
+
+    // Apply exclusions and sorting
+    prepare_repos(&mut all_repos, &args, previous_timestamp);
 
     // Print summary of repositories found
     let github_count = all_repos
@@ -72,63 +449,151 @@ async fn main() -> Result<(), Box<dyn Error>> {
         gitlab_count
     );
 
-    // Create formatted choices for the fuzzy finder
-    let choices: Vec<String> = all_repos
-        .iter()
-        .map(|repo| {
-            formatter::format_repository(
-                &repo.name,
-                &repo.description,
-                repo.is_fork,
-                repo.is_private,
-                repo.source,
-            )
-        })
-        .collect();
-
     // Create the fuzzy finder
-    let mut finder = fuzzy_finder::FuzzyFinder::new(choices);
+    let category_order = args.category_order.clone();
+    let display_template = args.display_template.clone();
+    let format_options = args.format_options;
+    let mut finder = fuzzy_finder::FuzzyFinder::new(all_repos, move |repo| {
+        format_choice(repo, previous_timestamp, &category_order, &display_template, format_options)
+    });
+    finder.set_strip_common_prefix(args.strip_common_prefix);
+    finder.set_min_score(args.min_score);
+    finder.set_archived_filter(archived_filter);
+    finder.set_group_by(args.group_by);
+    finder.set_wrap(args.cycle);
+    finder.set_quick_filters(args.quick_filters.clone());
+    finder.set_confirm_quit_when_busy(args.confirm_quit_when_busy);
+    finder.set_update_receiver(update_rx);
+    // A background refresh is running unless everything came from a fresh cache
+    finder.set_loading(terminal::is_busy());
+    finder.set_aliases(aliases::load_aliases(), args.show_aliases);
+    // Start from the query of the previous run unless one was given
+    let query = if args.query.is_empty() && args.restore_query {
+        state::load_last_query().unwrap_or_default()
+    } else {
+        args.query.clone()
+    };
+    finder.set_query(&query);
+    finder.set_remember_query(args.restore_query);
+    finder.set_ellipsis(args.ellipsis.clone());
+    finder.set_show_owner_source(args.show_owner_source);
+    finder.set_basket(basket::load_basket(), args.basket);
+    finder.set_basket_windows(args.multiplexer != multiplexer::Multiplexer::None);
+    finder.set_multi_select(args.multi);
+    if args.check_local {
+        finder.set_local_clone_dir(args.clone_dir.clone());
+    }
+    if let Some(path) = &args.record {
+        finder.set_recording(path.clone());
+    }
+    if let Some(path) = &args.replay {
+        match recording::Recording::load_from(path) {
+            Ok(recording) => finder.set_replay(recording),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Refetch with a new token entered in the finder after one was rejected, or on F5 and Ctrl+R
+    let refresher = (!args.use_dummy && !args.basket)
+        .then(|| Arc::new(Mutex::new(repository::Refresher::new(&args, tx.clone(), cancel))));
+    if let Some(refresher) = refresher.clone() {
+        let retry_refresher = refresher.clone();
+        finder.set_token_retry(move |source, token| {
+            retry_refresher.lock().unwrap().retry_with_token(source, token);
+        });
+
+        let (refresh_tx, mut refresh_rx) = mpsc::channel::<fuzzy_finder::RefreshRequest>(1);
+        finder.set_refresh_requests(refresh_tx);
+        tokio::spawn(async move {
+            while let Some(request) = refresh_rx.recv().await {
+                // The finder may not have seen the running refresh yet, so never start a second one
+                if request == fuzzy_finder::RefreshRequest::Refresh && terminal::is_busy() {
+                    continue;
+                }
+                refresher.lock().unwrap().restart();
+            }
+        });
+    }
 
     // Spawn a task to handle repository updates
     let update_tx_clone = update_tx.clone();
+    let task_args = args.clone();
+    let task_refresher = refresher.clone();
     tokio::spawn(async move {
+        // The repositories of the current fetch as received, and prepared for display
+        let mut received: Vec<cache::RepoData> = Vec::new();
+        let mut prepared: Vec<cache::RepoData> = Vec::new();
+        // Warnings about expiring tokens, shown again once loading is complete
+        let mut expiry_warnings: Vec<String> = Vec::new();
 
         while let Some(message) = rx.recv().await {
             match message {
-                repository::RepoUpdateMessage::NewRepos { repos, github_username: _new_gh_username, gitlab_username: _new_gl_username } => {
-
-                    // Format the new repositories
-                    let new_choices: Vec<String> = repos
-                        .iter()
-                        .map(|repo| {
-                            formatter::format_repository(
-                                &repo.name,
-                                &repo.description,
-                                repo.is_fork,
-                                repo.is_private,
-                                repo.source,
-                            )
-                        })
-                        .collect();
-
-                    // Send update to the main thread
-                    let _ = update_tx_clone.send((new_choices, String::new())).await;
+                repository::RepoUpdateMessage::NewRepos(repos) => {
+                    // Each message repeats the earlier repositories, so only merge in the new ones
+                    let appended = repository::appended_since(&received, &repos).map(<[_]>::to_vec);
+                    let fresh = appended.is_none();
+                    // Another fetch started over, like one retried with a new token
+                    if fresh {
+                        prepared.clear();
+                    }
+                    let batch = appended.unwrap_or_else(|| repos.clone());
+                    received = repos;
+                    prepared = merge_prepared(std::mem::take(&mut prepared), batch, &task_args, previous_timestamp);
+
+                    // Send update to the main thread, which replaces the listed repositories after a fresh start
+                    let update = if fresh {
+                        fuzzy_finder::FinderUpdate::FreshRepos(prepared.clone())
+                    } else {
+                        fuzzy_finder::FinderUpdate::Repos(prepared.clone())
+                    };
+                    let _ = update_tx_clone.send(update).await;
                 },
                 repository::RepoUpdateMessage::Status(status) => {
                     // Send status update to the main thread
-                    let _ = update_tx_clone.send((Vec::new(), status)).await;
+                    let _ = update_tx_clone.send(fuzzy_finder::FinderUpdate::Status(status)).await;
                 },
                 repository::RepoUpdateMessage::Error(error) => {
                     // Send error update to the main thread
-                    let _ = update_tx_clone.send((Vec::new(), format!("ERROR: {}", error))).await;
+                    let _ = update_tx_clone.send(fuzzy_finder::FinderUpdate::Error(error)).await;
+                },
+                // The error was already shown
+                repository::RepoUpdateMessage::FetchFailed(_) => {},
+                repository::RepoUpdateMessage::TokenExpires(source, expires_at) => {
+                    let now = chrono::Utc::now();
+                    if let Some(warning) = repository::token_expiry_warning(source, expires_at, now, task_args.token_expiry_warning_days) {
+                        let _ = update_tx_clone.send(fuzzy_finder::FinderUpdate::Status(warning.clone())).await;
+                        expiry_warnings.push(warning);
+                    }
                 },
-                repository::RepoUpdateMessage::LoadingComplete => {
+                repository::RepoUpdateMessage::AuthFailed(source) => {
+                    // Let the fuzzy finder ask for a new token
+                    let _ = update_tx_clone.send(fuzzy_finder::FinderUpdate::AuthFailed(source)).await;
+                },
+                repository::RepoUpdateMessage::LoadingComplete(changes) => {
                     // Send completion message to the main thread
-                    let _ = update_tx_clone.send((Vec::new(), "Repository loading complete".to_string())).await;
+                    let message = match changes {
+                        Some(changes) => format!("{}: {}", fuzzy_finder::LOADING_COMPLETE_STATUS, changes),
+                        None => fuzzy_finder::LOADING_COMPLETE_STATUS.to_string(),
+                    };
+                    let _ = update_tx_clone.send(fuzzy_finder::FinderUpdate::LoadingComplete(message)).await;
+
+                    // Tokens entered while the refresh was running are tried once it is done
+                    if let Some(refresher) = &task_refresher {
+                        refresher.lock().unwrap().run_retries();
+                    }
 
-                    // Clear the message after a delay
+                    // Clear the message after a delay, keeping token expiry warnings visible
                     tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-                    let _ = update_tx_clone.send((Vec::new(), String::new())).await;
+                    let update = if expiry_warnings.is_empty() {
+                        fuzzy_finder::FinderUpdate::ClearMessages
+                    } else {
+                        fuzzy_finder::FinderUpdate::Status(expiry_warnings.join(" · "))
+                    };
+                    let _ = update_tx_clone.send(update).await;
+                    expiry_warnings.clear();
                 }
             }
         }
@@ -136,26 +601,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Run the fuzzy finder in a loop
     loop {
-        // Check for updates before running the fuzzy finder
-        while let Ok((new_items, status)) = update_rx.try_recv() {
-            if !new_items.is_empty() {
-                finder.update_items(new_items);
-            }
-
-            if !status.is_empty() {
-                if status.starts_with("ERROR:") {
-                    finder.set_error_message(Some(status));
-                } else {
-                    finder.set_status_message(Some(status));
-                }
-            } else {
-                finder.set_status_message(None);
-                finder.set_error_message(None);
-            }
+        // Exit gracefully if Ctrl+C was pressed while an operation was running
+        if terminal::shutdown_requested() {
+            terminal::cleanup_terminal();
+            println!("Exiting...");
+            process::exit(0);
         }
 
         // Run the fuzzy finder
-        let selection = match finder.run() {
+        let (action, repo) = match finder.run() {
             Some(selected) => selected,
             None => {
                 terminal::cleanup_terminal();
@@ -165,14 +619,56 @@ async fn main() -> Result<(), Box<dyn Error>> {
         };
 
         // Process the selected repository
-        if let Err(e) =
-            repository::process_repository_selection(&selection, &github_username, &gitlab_username)
-                .await
-        {
-            eprintln!("Error processing repository: {}", e);
+        let several = match action {
+            fuzzy_finder::FinderAction::CopyUrls => finder.take_marked_repositories(),
+            _ => finder.basket_repositories().to_vec(),
+        };
+        run_action(action, repo.as_ref(), &several, &args).await;
+
+        // The action may have cloned the repository or changed its files
+        if let Some(repo) = &repo {
+            finder.recheck_local_clone(repo);
         }
     }
 
     // The loop above never exits normally, only through Ctrl+C or Esc
     // which call process::exit(0), so this is unreachable
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cache::{RepoData, RepoKind};
+    use formatter::RepoSource;
+
+    fn args(flags: &[&str]) -> cli::AppArgs {
+        let matches = cli::command().get_matches_from([&["repo-url-picker", "--dummy"], flags].concat());
+        cli::args_from(matches, config::Config::default())
+    }
+
+    fn names(repos: &[RepoData]) -> Vec<&str> {
+        repos.iter().map(|repo| repo.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_source_filter_keeps_gists_of_the_platform() {
+        let repos = vec![
+            RepoData::named("api"),
+            RepoData { source: RepoSource::GitLab, ..RepoData::named("game-demo") },
+            RepoData { kind: RepoKind::Gist, ..RepoData::named("aa5a315d") },
+            RepoData { source: RepoSource::GitLab, kind: RepoKind::Gist, ..RepoData::named("2814") },
+        ];
+        let hidden = |flags: &[&str]| {
+            let mut repos = repos.clone();
+            prepare_repos(&mut repos, &args(flags), None);
+            repos
+        };
+
+        assert_eq!(names(&hidden(&["--source", "gitlab", "--include-gists"])), vec!["game-demo", "2814"]);
+        assert_eq!(names(&hidden(&["--source", "github", "--include-gists"])), vec!["api", "aa5a315d"]);
+        assert_eq!(names(&hidden(&["--source", "all", "--include-gists"])), vec!["api", "game-demo", "aa5a315d", "2814"]);
+
+        // Gists stay hidden unless asked for, whichever source is picked
+        assert_eq!(names(&hidden(&["--source", "gitlab"])), vec!["game-demo"]);
+    }
+}
@@ -0,0 +1,60 @@
+//! Input buffer for secrets like tokens, which are never echoed
+
+/// Collects typed characters while only ever showing a mask
+#[derive(Default)]
+pub struct MaskedInput {
+    value: String,
+}
+
+impl MaskedInput {
+    pub fn push(&mut self, c: char) {
+        self.value.push(c);
+    }
+
+    /// Removes the last character, if any
+    pub fn backspace(&mut self) {
+        self.value.pop();
+    }
+
+    /// Returns what to show instead of the typed value
+    pub fn masked(&self) -> String {
+        "*".repeat(self.value.chars().count())
+    }
+
+    /// Returns the typed value, trimmed of whitespace pasted along with it
+    pub fn into_value(self) -> String {
+        self.value.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masked_input_never_shows_value() {
+        let mut input = MaskedInput::default();
+        for c in "ghp_sécret".chars() {
+            input.push(c);
+        }
+
+        assert_eq!(input.masked(), "**********");
+        assert!(!input.masked().contains("ghp"));
+        assert_eq!(input.into_value(), "ghp_sécret");
+    }
+
+    #[test]
+    fn test_masked_input_backspace() {
+        let mut input = MaskedInput::default();
+        input.backspace();
+        assert_eq!(input.masked(), "");
+
+        input.push('a');
+        input.push('b');
+        input.backspace();
+        assert_eq!(input.masked(), "*");
+
+        input.push(' ');
+        assert_eq!(input.into_value(), "a");
+    }
+}
@@ -0,0 +1,113 @@
+//! The actions offered for a repository selected with Enter
+//!
+//! The menu is a numbered list read as a line, once the fuzzy finder has left raw mode
+//! and the alternate screen. An empty line opens the browser, like Enter did before.
+
+use crate::browser;
+use crate::cache::RepoData;
+use crate::clipboard;
+use crate::git;
+use crate::repository;
+use std::io::{self, BufRead, Write};
+
+/// An action picked from the menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuChoice {
+    CopyCloneCommand,
+    CopySshUrl,
+    OpenInBrowser,
+    /// Clone the repository into the current directory
+    CloneHere,
+    /// Go back to the fuzzy finder without an action
+    Cancel,
+}
+
+/// The menu entries in the order they are numbered
+const MENU_ITEMS: &[(MenuChoice, &str)] = &[
+    (MenuChoice::CopyCloneCommand, "Copy git clone command"),
+    (MenuChoice::CopySshUrl, "Copy SSH URL"),
+    (MenuChoice::OpenInBrowser, "Open in browser"),
+    (MenuChoice::CloneHere, "Clone into the current directory"),
+];
+
+/// Parses a menu line, where numbers count from 1, empty opens the browser and q cancels
+pub fn parse_choice(line: &str) -> Option<MenuChoice> {
+    match line.trim() {
+        "" => Some(MenuChoice::OpenInBrowser),
+        "q" => Some(MenuChoice::Cancel),
+        number => {
+            let index = number.parse::<usize>().ok()?.checked_sub(1)?;
+            MENU_ITEMS.get(index).map(|(choice, _)| *choice)
+        }
+    }
+}
+
+/// Returns the command cloning a repository by its SSH URL
+pub fn clone_command(url: &str) -> String {
+    format!("git clone {}", url)
+}
+
+/// Prints the menu and reads lines until an action is picked, returning None at the end of input
+pub fn display_menu(repo: &RepoData) -> Option<MenuChoice> {
+    let stdin = io::stdin();
+
+    println!("\n{} ({})", repo.name, repo.url);
+    for (i, (_, label)) in MENU_ITEMS.iter().enumerate() {
+        println!("{:>3}) {}", i + 1, label);
+    }
+
+    loop {
+        print!("Action (empty to open in browser, q to go back): ");
+        io::stdout().flush().ok()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        match parse_choice(&line) {
+            Some(choice) => return Some(choice),
+            None => println!("There is no action '{}'", line.trim()),
+        }
+    }
+}
+
+/// Performs an action picked from the menu
+pub async fn handle_menu_choice(choice: MenuChoice, repo: &RepoData) -> Result<(), Box<dyn std::error::Error>> {
+    match choice {
+        MenuChoice::CopyCloneCommand => println!("{}", clipboard::copy_to_clipboard(&clone_command(&repo.url))?),
+        MenuChoice::CopySshUrl => println!("{}", clipboard::copy_to_clipboard(&repo.url)?),
+        MenuChoice::OpenInBrowser => browser::open_in_browser(&repository::browser_url(repo)).await?,
+        MenuChoice::CloneHere => {
+            let target = git::clone_target(&std::env::current_dir()?, &repo.name);
+            git::clone_into(&repo.url, &target)?;
+            println!("Cloned into {}", target.display());
+        }
+        MenuChoice::Cancel => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("1\n"), Some(MenuChoice::CopyCloneCommand));
+        assert_eq!(parse_choice(" 2 "), Some(MenuChoice::CopySshUrl));
+        assert_eq!(parse_choice("3"), Some(MenuChoice::OpenInBrowser));
+        assert_eq!(parse_choice("4"), Some(MenuChoice::CloneHere));
+        assert_eq!(parse_choice("\n"), Some(MenuChoice::OpenInBrowser));
+        assert_eq!(parse_choice("q"), Some(MenuChoice::Cancel));
+
+        assert_eq!(parse_choice("0"), None);
+        assert_eq!(parse_choice("5"), None);
+        assert_eq!(parse_choice("open"), None);
+    }
+
+    #[test]
+    fn test_clone_command() {
+        assert_eq!(clone_command("git@github.com:user/notes.git"), "git clone git@github.com:user/notes.git");
+    }
+}
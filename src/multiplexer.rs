@@ -0,0 +1,171 @@
+//! Opening every repository in the basket in its own terminal multiplexer window
+//!
+//! With `--multiplexer tmux` or `iterm`, Ctrl+E in the fuzzy finder runs the clone
+//! command of each basket repository in a new tmux window or iTerm tab. The command
+//! comes from the `--clone-command` template.
+
+use crate::cache::RepoData;
+use crate::git;
+use std::path::Path;
+use std::process;
+
+/// Clone command run for each repository unless `--clone-command` is given
+pub const DEFAULT_CLONE_COMMAND: &str = "git clone {url} {dir}";
+
+/// Selections larger than this ask for confirmation before opening their windows
+pub const CONFIRM_ABOVE: usize = 5;
+
+/// Where the clone commands of the basket are opened, set with `--multiplexer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Multiplexer {
+    Tmux,
+    Iterm,
+    #[default]
+    None,
+}
+
+impl Multiplexer {
+    pub const NAMES: &'static [&'static str] = &["tmux", "iterm", "none"];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "tmux" => Ok(Multiplexer::Tmux),
+            "iterm" => Ok(Multiplexer::Iterm),
+            "none" => Ok(Multiplexer::None),
+            _ => Err(format!("unknown multiplexer '{}', expected one of {}", value, Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Quotes a value for a POSIX shell, leaving simple values like URLs and paths as they are
+fn shell_quote(value: &str) -> String {
+    let simple = !value.is_empty()
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "@%+=:,./-_~".contains(c));
+
+    if simple {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Fills in the `{url}`, `{name}` and `{dir}` placeholders of a clone command template.
+///
+/// The values are shell quoted, and `{dir}` is where the repository is cloned into.
+pub fn clone_command(template: &str, repo: &RepoData, clone_dir: &Path) -> String {
+    let dir = git::clone_target(clone_dir, &repo.name);
+
+    template
+        .replace("{url}", &shell_quote(&repo.url))
+        .replace("{name}", &shell_quote(&repo.name))
+        .replace("{dir}", &shell_quote(&dir.to_string_lossy()))
+}
+
+/// Escapes text for a double quoted AppleScript string
+fn applescript_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// Returns the program and arguments opening a window named after the repository which
+/// runs the command, or None without a multiplexer.
+///
+/// tmux windows start a shell after the command, so they stay open once it is done.
+/// iTerm tabs type the command into their shell, which stays open anyway.
+pub fn window_command(multiplexer: Multiplexer, name: &str, command: &str) -> Option<Vec<String>> {
+    match multiplexer {
+        Multiplexer::Tmux => Some(vec![
+            "tmux".to_string(),
+            "new-window".to_string(),
+            "-n".to_string(),
+            name.to_string(),
+            format!("{}; exec \"${{SHELL:-sh}}\"", command),
+        ]),
+        Multiplexer::Iterm => {
+            let script = format!(
+                "tell application \"iTerm2\"\n\
+                 tell current window\n\
+                 create tab with default profile\n\
+                 tell current session of current tab to write text {}\n\
+                 end tell\n\
+                 end tell",
+                applescript_quote(command)
+            );
+            Some(vec!["osascript".to_string(), "-e".to_string(), script])
+        }
+        Multiplexer::None => None,
+    }
+}
+
+/// Returns the window commands of all repositories, in their order
+pub fn window_commands(
+    multiplexer: Multiplexer,
+    template: &str,
+    repos: &[RepoData],
+    clone_dir: &Path,
+) -> Vec<Vec<String>> {
+    repos
+        .iter()
+        .filter_map(|repo| window_command(multiplexer, &repo.name, &clone_command(template, repo, clone_dir)))
+        .collect()
+}
+
+/// Opens a window for each repository, stopping at the first one which fails
+pub fn open_windows(multiplexer: Multiplexer, template: &str, repos: &[RepoData], clone_dir: &Path) -> Result<usize, String> {
+    if multiplexer == Multiplexer::Tmux && std::env::var_os("TMUX").is_none() {
+        return Err("tmux windows can only be opened from inside tmux".to_string());
+    }
+
+    let commands = window_commands(multiplexer, template, repos, clone_dir);
+    for command in &commands {
+        let status = process::Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+            .map_err(|e| format!("Failed to run {}: {}", command[0], e))?;
+
+        if !status.success() {
+            return Err(format!("{} exited with {}", command[0], status));
+        }
+    }
+    Ok(commands.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_command_template() {
+        let command = clone_command(DEFAULT_CLONE_COMMAND, &RepoData::named("rust-web"), Path::new("/src"));
+        assert_eq!(command, "git clone git@github.com:user/rust-web.git /src/rust-web");
+
+        // Values with spaces or quotes are quoted for the shell
+        let command = clone_command("cd {dir} && echo {name}", &RepoData::named("it's here"), Path::new("/my src"));
+        assert_eq!(command, r"cd '/my src/it'\''s here' && echo 'it'\''s here'");
+    }
+
+    #[test]
+    fn test_window_commands() {
+        let repos = vec![RepoData::named("rust-web"), RepoData::named("notes")];
+        let commands = window_commands(Multiplexer::Tmux, "git clone {url}", &repos, Path::new("."));
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(
+            commands[1],
+            vec!["tmux", "new-window", "-n", "notes", "git clone git@github.com:user/notes.git; exec \"${SHELL:-sh}\""]
+        );
+
+        // iTerm gets the command as an AppleScript string
+        let commands = window_commands(Multiplexer::Iterm, "echo \"{name}\"", &repos[..1], Path::new("."));
+        assert_eq!(commands[0][0], "osascript");
+        assert!(commands[0][2].contains(r#"write text "echo \"rust-web\"""#));
+
+        assert!(window_commands(Multiplexer::None, DEFAULT_CLONE_COMMAND, &repos, Path::new(".")).is_empty());
+    }
+
+    #[test]
+    fn test_parse_multiplexer() {
+        assert_eq!(Multiplexer::parse("tmux"), Ok(Multiplexer::Tmux));
+        assert_eq!(Multiplexer::parse("none"), Ok(Multiplexer::None));
+        assert!(Multiplexer::parse("screen").unwrap_err().contains("expected one of tmux, iterm, none"));
+    }
+}
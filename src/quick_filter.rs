@@ -0,0 +1,132 @@
+//! Preset filters toggled with Alt+1 to Alt+9 in the fuzzy finder
+//!
+//! Each `--quick-filter` is either one of the predicates below or a text query written
+//! as `name=query`, like `rust=rust -deprecated`. Active presets narrow the list on top
+//! of the typed query, which they never change.
+
+use crate::cache::RepoData;
+
+/// Quick filters shown without `--quick-filter`
+pub const DEFAULT_QUICK_FILTERS: &[&str] = &["forks", "private", "stale"];
+
+/// At most this many presets fit on the number keys
+pub const MAX_QUICK_FILTERS: usize = 9;
+
+/// Repositories not updated for this many days count as stale
+const STALE_DAYS: u64 = 365;
+
+/// What a quick filter keeps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Preset {
+    Forks,
+    Private,
+    /// Not updated within a year, or without an update date
+    Stale,
+    /// Query terms matched like typed ones
+    Query(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickFilter {
+    pub name: String,
+    pub preset: Preset,
+}
+
+impl QuickFilter {
+    /// Parses a predicate name like `forks`, or a text query like `rust=rust -deprecated`.
+    ///
+    /// Any other single word is a text query named after itself.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if let Some((name, query)) = value.split_once('=') {
+            let (name, query) = (name.trim(), query.trim());
+            if name.is_empty() || query.is_empty() {
+                return Err(format!("expected a name and a query like rust=rust, got '{}'", value));
+            }
+            return Ok(Self {
+                name: name.to_string(),
+                preset: Preset::Query(query.to_string()),
+            });
+        }
+
+        let preset = match value {
+            "forks" => Preset::Forks,
+            "private" => Preset::Private,
+            "stale" => Preset::Stale,
+            "" => return Err("the quick filter must not be empty".to_string()),
+            _ if value.contains(' ') => return Err(format!("a query needs a name like name={}", value)),
+            _ => Preset::Query(value.to_string()),
+        };
+        Ok(Self {
+            name: value.to_string(),
+            preset,
+        })
+    }
+
+    /// Returns the query terms of a text preset
+    pub fn query(&self) -> Option<&str> {
+        match &self.preset {
+            Preset::Query(query) => Some(query),
+            _ => None,
+        }
+    }
+
+    /// Check a repository against a predicate, text presets are matched with the query instead
+    pub fn matches(&self, repo: &RepoData, now: u64) -> bool {
+        match &self.preset {
+            Preset::Forks => repo.is_fork,
+            Preset::Private => repo.is_private,
+            Preset::Stale => repo
+                .updated_at
+                .is_none_or(|updated_at| now.saturating_sub(updated_at) > STALE_DAYS * 24 * 60 * 60),
+            Preset::Query(_) => true,
+        }
+    }
+}
+
+/// Returns the bar listing the presets by their keys, like "1 forks  2 private"
+pub fn bar_labels(filters: &[QuickFilter]) -> Vec<String> {
+    filters
+        .iter()
+        .enumerate()
+        .map(|(i, filter)| format!("{} {}", i + 1, filter.name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(updated_at: Option<u64>) -> RepoData {
+        RepoData {
+            updated_at,
+            ..RepoData::named("notes")
+        }
+    }
+
+    #[test]
+    fn test_parse_quick_filter() {
+        assert_eq!(QuickFilter::parse("forks").unwrap().preset, Preset::Forks);
+
+        let filter = QuickFilter::parse("rust = rust -deprecated").unwrap();
+        assert_eq!(filter.name, "rust");
+        assert_eq!(filter.query(), Some("rust -deprecated"));
+
+        // A single word is a query named after itself
+        assert_eq!(QuickFilter::parse("clojure").unwrap().query(), Some("clojure"));
+
+        assert!(QuickFilter::parse("=rust").is_err());
+        assert!(QuickFilter::parse("rust web").is_err());
+    }
+
+    #[test]
+    fn test_stale_preset() {
+        let stale = QuickFilter::parse("stale").unwrap();
+        let now = 1_700_000_000;
+        let updated = |days_ago: u64| repo(Some(now - days_ago * 24 * 60 * 60));
+
+        assert!(!stale.matches(&updated(30), now));
+        assert!(stale.matches(&updated(400), now));
+        assert!(stale.matches(&repo(None), now));
+    }
+}
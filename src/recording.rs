@@ -0,0 +1,240 @@
+//! Recorded key presses of the fuzzy finder, for reproducible demos
+//!
+//! `--record FILE` saves every key pressed in the finder with the milliseconds since
+//! the finder started, and `--replay FILE` feeds them back at the same pace.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+use termion::event::Key;
+
+/// A key pressed at some point after the finder started, like `{"at_ms": 250, "key": "Ctrl+b"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyEvent {
+    pub at_ms: u64,
+    pub key: String,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub keys: Vec<KeyEvent>,
+}
+
+/// Returns the name a key is recorded as, or None for keys the finder ignores anyway
+pub fn key_name(key: Key) -> Option<String> {
+    let name = match key {
+        Key::Char('\n') | Key::Char('\r') => "Enter".to_string(),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("Ctrl+{}", c),
+        Key::Alt(c) => format!("Alt+{}", c),
+        Key::Backspace => "Backspace".to_string(),
+        Key::Delete => "Delete".to_string(),
+        Key::Left => "Left".to_string(),
+        Key::Right => "Right".to_string(),
+        Key::Up => "Up".to_string(),
+        Key::Down => "Down".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::CtrlHome => "Ctrl+Home".to_string(),
+        Key::CtrlEnd => "Ctrl+End".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Esc => "Esc".to_string(),
+        Key::F(n) => format!("F{}", n),
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Parses a key name written by `key_name`
+pub fn parse_key(name: &str) -> Option<Key> {
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+
+    let key = match name {
+        "Enter" => Key::Char('\n'),
+        "Tab" => Key::Char('\t'),
+        "Backspace" => Key::Backspace,
+        "Delete" => Key::Delete,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "Ctrl+Home" => Key::CtrlHome,
+        "Ctrl+End" => Key::CtrlEnd,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Esc" => Key::Esc,
+        _ => {
+            if let Some(c) = single(name) {
+                return Some(Key::Char(c));
+            }
+            if let Some(rest) = name.strip_prefix("Ctrl+") {
+                return single(rest).map(Key::Ctrl);
+            }
+            if let Some(n) = name.strip_prefix('F') {
+                return n.parse().ok().map(Key::F);
+            }
+            return name.strip_prefix("Alt+").and_then(single).map(Key::Alt);
+        }
+    };
+    Some(key)
+}
+
+impl Recording {
+    /// Loads a recording, failing on unknown key names so a typo does not go unnoticed
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let recording: Self =
+            serde_json::from_str(&json).map_err(|e| format!("Invalid recording {}: {}", path.display(), e))?;
+
+        match recording.keys.iter().find(|event| parse_key(&event.key).is_none()) {
+            Some(event) => Err(format!("Unknown key '{}' in recording {}", event.key, path.display())),
+            None => Ok(recording),
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+}
+
+/// Collects the keys pressed in the finder, saved after every selection and on quit
+pub struct Recorder {
+    started: Instant,
+    recording: Recording,
+}
+
+impl Recorder {
+    /// Starts recording, timing the keys from now
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            recording: Recording::default(),
+        }
+    }
+
+    pub fn record(&mut self, key: Key) {
+        if let Some(key) = key_name(key) {
+            let at_ms = self.started.elapsed().as_millis() as u64;
+            self.recording.keys.push(KeyEvent { at_ms, key });
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+}
+
+/// The keys of a recording which were not replayed yet
+pub struct Replay {
+    started: Option<Instant>,
+    keys: VecDeque<KeyEvent>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            started: None,
+            keys: recording.keys.into(),
+        }
+    }
+
+    /// Returns the next key if it is due after `elapsed_ms` since the replay started
+    pub fn next_due(&mut self, elapsed_ms: u64) -> Option<Key> {
+        if self.keys.front()?.at_ms > elapsed_ms {
+            return None;
+        }
+        self.keys.pop_front().and_then(|event| parse_key(&event.key))
+    }
+
+    /// Returns the next key once it is due, timed from the first call
+    pub fn next_key(&mut self) -> Option<Key> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        self.next_due(started.elapsed().as_millis() as u64)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names_round_trip() {
+        let keys = [
+            Key::Char('r'),
+            Key::Char(' '),
+            Key::Char('\n'),
+            Key::Ctrl('b'),
+            Key::Alt('x'),
+            Key::Backspace,
+            Key::Down,
+            Key::PageUp,
+            Key::CtrlHome,
+            Key::Esc,
+            Key::F(5),
+            Key::F(12),
+        ];
+        for key in keys {
+            assert_eq!(parse_key(&key_name(key).unwrap()), Some(key));
+        }
+
+        assert_eq!(key_name(Key::Char('\r')), Some("Enter".to_string()));
+        assert_eq!(key_name(Key::Null), None);
+        assert_eq!(parse_key("F"), Some(Key::Char('F')));
+        assert_eq!(parse_key("Fx"), None);
+        assert_eq!(parse_key("Ctrl+"), None);
+        assert_eq!(parse_key("Shift+a"), None);
+    }
+
+    #[test]
+    fn test_replay_waits_for_due_keys() {
+        let recording = Recording {
+            keys: vec![
+                KeyEvent { at_ms: 0, key: "r".to_string() },
+                KeyEvent { at_ms: 300, key: "Enter".to_string() },
+            ],
+        };
+        let mut replay = Replay::new(recording);
+
+        assert_eq!(replay.next_due(100), Some(Key::Char('r')));
+        assert_eq!(replay.next_due(200), None);
+        assert_eq!(replay.next_due(300), Some(Key::Char('\n')));
+        assert!(replay.is_finished());
+        assert_eq!(replay.next_due(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_recording_round_trip_through_file() {
+        // Unique to this process, so test runs at the same time do not overwrite each other's file
+        let path = std::env::temp_dir().join(format!("repo-searcher-test-recording-{}.json", std::process::id()));
+        let mut recorder = Recorder::start();
+        recorder.record(Key::Char('g'));
+        recorder.record(Key::F(5));
+        recorder.record(Key::Ctrl('g'));
+        recorder.recording().save_to(&path).unwrap();
+
+        let loaded = Recording::load_from(&path).unwrap();
+        let keys: Vec<&str> = loaded.keys.iter().map(|event| event.key.as_str()).collect();
+        assert_eq!(keys, vec!["g", "F5", "Ctrl+g"]);
+
+        // Unknown key names are rejected
+        fs::write(&path, r#"{"keys": [{"at_ms": 0, "key": "Hyper+q"}]}"#).unwrap();
+        let error = Recording::load_from(&path).unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert!(error.contains("Unknown key 'Hyper+q'"));
+    }
+}
@@ -1,124 +1,290 @@
+use crate::basket;
 use crate::browser;
 use crate::cache;
 use crate::cli;
+use crate::export;
 use crate::formatter;
+use crate::fuzzy_finder::FinderAction;
+use crate::git;
 use crate::github;
 use crate::gitlab;
+use crate::menu;
+use crate::terminal;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
-/// Processes a selected repository by extracting its information and opening it in the browser
+/// Splits an `owner/name` repository spec, keeping GitLab subgroups in the owner
+pub fn parse_repo_spec(spec: &str) -> Option<(&str, &str)> {
+    let (owner, name) = spec.trim().trim_matches('/').rsplit_once('/')?;
+    if owner.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some((owner, name))
+}
+
+/// Returns the sources to look up a single repository in, in order.
+///
+/// With a source hint only that source is tried, otherwise GitHub before GitLab.
+/// Sources without a token are skipped.
+pub fn lookup_order(hint: Option<formatter::RepoSource>, has_github: bool, has_gitlab: bool) -> Vec<formatter::RepoSource> {
+    [formatter::RepoSource::GitHub, formatter::RepoSource::GitLab]
+        .into_iter()
+        .filter(|source| hint.is_none_or(|hint| hint == *source))
+        .filter(|source| match source {
+            formatter::RepoSource::GitHub => has_github,
+            formatter::RepoSource::GitLab => has_gitlab,
+        })
+        .collect()
+}
+
+/// Returns the browser URL of a selected repository.
+///
+/// GitLab projects are shown by their display name, so their path is taken from the
+/// clone URL instead, falling back to the name in kebab case.
+pub fn browser_url(repo: &cache::RepoData) -> String {
+    if repo.source == formatter::RepoSource::GitLab && !repo.is_gist() {
+        let ssh_prefix = format!("git@{}:", gitlab::ssh_host(gitlab::base_url()));
+        let path = repo
+            .url
+            .strip_prefix(ssh_prefix.as_str())
+            .and_then(|path| path.strip_suffix(".git"))
+            .and_then(|path| path.rsplit_once('/'));
+        if let Some((owner, name)) = path {
+            return web_url(repo.source, repo.kind, owner, name);
+        }
+        return web_url(repo.source, repo.kind, &repo.owner, &repo.name.to_lowercase().replace(' ', "-"));
+    }
+    web_url(repo.source, repo.kind, &repo.owner, &repo.name)
+}
+
+/// Returns the web page of a repository
+fn web_url(source: formatter::RepoSource, kind: cache::RepoKind, owner: &str, name: &str) -> String {
+    match (source, kind) {
+        (formatter::RepoSource::GitHub, cache::RepoKind::Repository) => github::repo_web_url(github::host(), owner, name),
+        (formatter::RepoSource::GitLab, cache::RepoKind::Repository) => gitlab::project_web_url(gitlab::base_url(), owner, name),
+        (formatter::RepoSource::GitHub, cache::RepoKind::Gist) => github::gist_web_url(github::host(), owner, name),
+        (formatter::RepoSource::GitLab, cache::RepoKind::Gist) => gitlab::snippet_web_url(gitlab::base_url(), name),
+    }
+}
+
+/// Fetches a single repository by its `owner/name` spec, trying each source in lookup order
+pub async fn lookup_single_repository(args: &cli::AppArgs, spec: &str) -> Result<cache::RepoData, String> {
+    let (owner, name) = parse_repo_spec(spec)
+        .ok_or_else(|| format!("Invalid repository '{}', expected owner/name", spec))?;
+
+    let order = lookup_order(args.source, args.github_token.is_some(), args.gitlab_token.is_some());
+    if order.is_empty() {
+        return Err("No token provided for the requested source".to_string());
+    }
+
+    let mut errors = Vec::new();
+    for source in order {
+        let result = match (source, &args.github_token, &args.gitlab_token) {
+            (formatter::RepoSource::GitHub, Some(token), _) => github::fetch_repo(token, owner, name)
+                .await
+                .map(|repo| cache::github_repo_to_repo_data(&repo, args.max_description_length))
+                .map_err(|e| format!("GitHub: {}", e)),
+            (formatter::RepoSource::GitLab, _, Some(token)) => gitlab::fetch_repo(token, owner, name)
+                .await
+                .map(|repo| cache::gitlab_repo_to_repo_data(&repo, args.max_description_length))
+                .map_err(|e| format!("GitLab: {}", e)),
+            _ => continue,
+        };
+
+        match result {
+            Ok(repo) => return Ok(repo),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    Err(format!("Could not find {}: {}", spec, errors.join("; ")))
+}
+
+/// Looks up a single repository and opens it in the browser, or prints it as JSON
+pub async fn open_single_repository(args: &cli::AppArgs, spec: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = lookup_single_repository(args, spec).await?;
+
+    if args.json {
+        let json = match &args.json_fields {
+            Some(fields) => serde_json::to_string_pretty(&export::project_json(&repo, fields))?,
+            None => serde_json::to_string_pretty(&repo)?,
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+
+    // The spec is the repository path, unlike GitLab's display name
+    let (owner, name) = parse_repo_spec(spec).ok_or("Invalid repository spec")?;
+    println!("Repository: {}", repo.name);
+    browser::open_in_browser(&web_url(repo.source, repo.kind, owner, name)).await
+}
+
+/// Returns how long to wait after an action before returning to the fuzzy finder
+///
+/// Only opening the browser waits, so the new tab can take focus. The git TUI
+/// returns once the user quits it, so there is nothing to wait for.
+pub fn post_action_delay(action: FinderAction, delay_ms: u64) -> Option<Duration> {
+    match action {
+        FinderAction::Open | FinderAction::Tree if delay_ms > 0 => Some(Duration::from_millis(delay_ms)),
+        FinderAction::Open
+        | FinderAction::Tree
+        | FinderAction::GitTui
+        | FinderAction::BasketWindows
+        | FinderAction::CopyUrls => None,
+    }
+}
+
+/// Asks what to do with a selected repository, like copying its clone command or opening it in the browser
 pub async fn process_repository_selection(
-    selection: &str,
-    github_username: &str,
-    gitlab_username: &str
+    repo: &cache::RepoData,
+    delay_ms: u64
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Determine if this is a GitHub or GitLab repository based on the [GH] or [GL] tag
-    let is_gitlab = selection.contains(" [GL]");
+    // Display repository information
+    println!("Repository: {}", repo.name);
+    println!("Username: {}", repo.owner);
 
-    // Extract repository information based on the source
-    let repo_info = if is_gitlab {
-        gitlab::extract_repo_info(selection, gitlab_username)
-    } else {
-        github::extract_repo_info(selection, github_username)
+    // The end of input goes back to the fuzzy finder like cancelling
+    let choice = menu::display_menu(repo).unwrap_or(menu::MenuChoice::Cancel);
+    menu::handle_menu_choice(choice, repo).await?;
+
+    // Continue running the fuzzy finder, only the browser needs time to take focus
+    if choice != menu::MenuChoice::OpenInBrowser {
+        return Ok(());
+    }
+    if let Some(delay) = post_action_delay(FinderAction::Open, delay_ms) {
+        println!("\nPress any key to continue searching or Ctrl+C/Esc to exit...");
+        tokio::time::sleep(delay).await;
+    }
+
+    Ok(())
+}
+
+/// Returns the file browser URL of a repository at a branch, deep-linking a path if given.
+///
+/// Without a known branch there is no ref to show, so the repository page is returned.
+/// Gists and snippets have no file browser either.
+pub fn tree_url(
+    browser_url: &str,
+    source: formatter::RepoSource,
+    kind: cache::RepoKind,
+    branch: Option<&str>,
+    path: Option<&str>
+) -> String {
+    let browser_url = browser_url.trim_end_matches('/');
+    let Some(branch) = branch.filter(|branch| !branch.is_empty() && kind == cache::RepoKind::Repository) else {
+        return browser_url.to_string();
     };
 
-    // Process the repository information
-    if let Some((repo_name, _url, browser_url)) = repo_info {
-        // Open in browser if URL is available
-        if let Some(browser_url) = browser_url {
-            // Display repository information
-            let username = if is_gitlab { gitlab_username } else { github_username };
-            println!("Repository: {}", repo_name);
-            println!("Username: {}", username);
-
-            // Open the URL in the browser
-            browser::open_in_browser(&browser_url).await?;
-
-            // Continue running the fuzzy finder
-            println!("\nPress any key to continue searching or Ctrl+C/Esc to exit...");
-            tokio::time::sleep(Duration::from_secs(1)).await;
-        } else {
-            println!("No browser URL available for repository: {}", repo_name);
-        }
-    } else {
-        println!("Error: Could not parse repository information from selection");
+    let tree = match source {
+        formatter::RepoSource::GitHub => "tree",
+        formatter::RepoSource::GitLab => "-/tree",
+    };
+    let mut url = format!("{}/{}/{}", browser_url, tree, branch);
+
+    if let Some(path) = path.map(|path| path.trim_matches('/')).filter(|path| !path.is_empty()) {
+        url.push('/');
+        url.push_str(path);
+    }
+    url
+}
+
+/// Opens the file browser of a selected repository at its default branch
+pub async fn open_selection_tree(
+    repo: &cache::RepoData,
+    path: Option<&str>,
+    delay_ms: u64
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = tree_url(&browser_url(repo), repo.source, repo.kind, repo.default_branch.as_deref(), path);
+    println!("Repository: {}", repo.name);
+    println!("Opening {}", url);
+    browser::open_in_browser(&url).await?;
+
+    if let Some(delay) = post_action_delay(FinderAction::Tree, delay_ms) {
+        println!("\nPress any key to continue searching or Ctrl+C/Esc to exit...");
+        tokio::time::sleep(delay).await;
     }
 
     Ok(())
 }
 
+/// Opens a selected repository in a git TUI, cloning it into the clone directory first if needed
+pub fn open_selection_in_git_tui(
+    repo: &cache::RepoData,
+    clone_dir: &Path,
+    git_tui: &str
+) -> Result<(), String> {
+    let repo_dir = git::clone_target(clone_dir, &repo.name);
+    git::open_in_git_tui(
+        &repo_dir,
+        |target| git::clone_repo(&repo.url, target),
+        |target| git::run_git_tui(git_tui, target),
+    )
+}
+
 /// Loads dummy repositories for testing
-pub fn load_dummy_repositories(
-    all_repos: &mut Vec<cache::RepoData>,
-    github_username: &mut String,
-    gitlab_username: &mut String
-) {
+pub fn load_dummy_repositories(all_repos: &mut Vec<cache::RepoData>) {
     // Get dummy GitHub repositories
-    let (dummy_username, dummy_repos) = github::generate_dummy_repos();
-    *github_username = dummy_username.clone();
-    *gitlab_username = "Gira".to_string(); // Default GitLab username for dummy data
-
-    // Convert to RepoData with GitHub source
-    all_repos.extend(dummy_repos.into_iter().map(|(name, url, description, owner, is_fork, is_private)| {
-        cache::RepoData {
-            name,
-            url,
-            description,
-            owner,
-            is_fork,
-            is_private,
-            source: formatter::RepoSource::GitHub,
-        }
-    }));
+    let (_, dummy_repos) = github::generate_dummy_repos();
+
+    // Convert to RepoData with GitHub source, their descriptions are short enough already
+    all_repos.extend(dummy_repos.iter().map(|repo| cache::github_repo_to_repo_data(repo, usize::MAX)));
+}
+
+/// Loads the repositories collected in the basket instead of the cache or the APIs
+pub fn load_basket_repositories(all_repos: &mut Vec<cache::RepoData>) {
+    *all_repos = basket::load_basket().repositories;
 }
 
 /// Message type for repository updates
 pub enum RepoUpdateMessage {
     /// New repositories have been loaded
-    NewRepos {
-        repos: Vec<cache::RepoData>,
-        github_username: String,
-        gitlab_username: String,
-    },
-    /// Background loading has completed
-    LoadingComplete,
+    NewRepos(Vec<cache::RepoData>),
+    /// Background loading has completed, with a summary of the changes since the previous cache
+    LoadingComplete(Option<String>),
     /// An error occurred during loading
     Error(String),
     /// Status update message
     Status(String),
+    /// A source rejected its token, so a new one can be entered
+    AuthFailed(formatter::RepoSource),
+    /// Fetching the repositories of a source failed, after its error was sent
+    FetchFailed(formatter::RepoSource),
+    /// The token of a source expires at this time
+    TokenExpires(formatter::RepoSource, DateTime<Utc>),
 }
 
 /// Loads repositories with background refresh
+///
+/// Returns the timestamp of the previous cache, if there is one, so repositories
+/// changed since then can be highlighted once the refresh arrives.
 pub async fn load_repositories_with_background_refresh(
     args: &cli::AppArgs,
     all_repos: &mut Vec<cache::RepoData>,
-    github_username: &mut String,
-    gitlab_username: &mut String,
-    tx: mpsc::Sender<RepoUpdateMessage>
-) -> Result<(), Box<dyn std::error::Error>> {
+    tx: mpsc::Sender<RepoUpdateMessage>,
+    cancel: Arc<AtomicBool>
+) -> Result<Option<u64>, Box<dyn std::error::Error>> {
     // Check if we should use cache
     let use_cache = !args.force_download;
     let mut cache_loaded = false;
 
+    // The previous cache is read even when forcing a download, for its timestamp
+    let cache_data = cache::load_cache();
+    let previous_timestamp = cache_data.as_ref().and_then(|c| c.oldest_timestamp());
+
     if use_cache {
         // Try to load from cache first
-        if let Some(cache_data) = cache::load_cache() {
-            if !cache_data.is_expired() {
+        if let Some(cache_data) = &cache_data {
+            if !cache_data.is_expired(args.cache_expiry) {
                 // Send status message
                 let _ = tx.send(RepoUpdateMessage::Status("Using cached repositories".to_string())).await;
 
                 // Get all repositories from cache
                 *all_repos = cache_data.get_all_repositories();
 
-                // Set usernames from GitHub or GitLab cache
-                if let Some(github) = &cache_data.github {
-                    *github_username = github.cache_info.username.clone();
-                }
-                if let Some(gitlab) = &cache_data.gitlab {
-                    *gitlab_username = gitlab.cache_info.username.clone();
-                }
-
                 let _ = tx.send(RepoUpdateMessage::Status(
                     format!("Loaded {} repositories from cache", all_repos.len())
                 )).await;
@@ -139,23 +305,318 @@ pub async fn load_repositories_with_background_refresh(
     let gitlab_token = args.gitlab_token.clone();
     let tx_clone = tx.clone();
 
+    // An incremental refresh only fetches updates to the previous cache
+    let incremental_base = if args.incremental && use_cache { cache_data } else { None };
+
     // Start background task to fetch fresh data
-    spawn_background_task(github_token.clone(), gitlab_token.clone(), tx_clone.clone());
+    spawn_background_task(
+        github_token.clone(),
+        gitlab_token.clone(),
+        incremental_base,
+        FetchOptions::from_args(args),
+        tx_clone.clone(),
+        cancel,
+    );
 
     // If we didn't load from cache, we need to wait for the background task to provide initial data
     if !cache_loaded && all_repos.is_empty() {
         let _ = tx.send(RepoUpdateMessage::Status("Waiting for initial repository data...".to_string())).await;
     }
 
-    Ok(())
+    Ok(previous_timestamp)
+}
+
+/// Returns the repositories appended to `previous`, if `current` starts with the same ones.
+///
+/// The background refresh sends all repositories fetched so far with every update, so
+/// this finds the batch which is new since the previous update.
+pub fn appended_since<'a>(previous: &[cache::RepoData], current: &'a [cache::RepoData]) -> Option<&'a [cache::RepoData]> {
+    let extends = current.len() >= previous.len()
+        && previous.iter().zip(current).all(|(a, b)| a.url == b.url);
+    extends.then(|| &current[previous.len()..])
+}
+
+/// Returns a warning if a token expires within the window, like "GitHub token expires in 3 days".
+///
+/// Tokens which already expired are rejected by the API instead, so they get no warning.
+pub fn token_expiry_warning(
+    source: formatter::RepoSource,
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    window_days: u64
+) -> Option<String> {
+    let remaining = expires_at.signed_duration_since(now);
+    // The last day is always warned about, even with a window of 0 days
+    let last_day = remaining.num_days() == 0;
+    if remaining < chrono::Duration::zero() || (remaining > chrono::Duration::days(window_days as i64) && !last_day) {
+        return None;
+    }
+
+    let when = match remaining.num_days() {
+        0 => "today".to_string(),
+        1 => "in 1 day".to_string(),
+        days => format!("in {} days", days),
+    };
+    Some(format!("{:?} token expires {}", source, when))
+}
+
+/// Checks that every source required with `--require-nonempty` has repositories.
+///
+/// A source which failed to fetch is reported as such, since that is a different
+/// problem than a token which cannot see any repositories.
+pub fn check_required_sources(
+    required: &[formatter::RepoSource],
+    repos: &[cache::RepoData],
+    failed: &[formatter::RepoSource]
+) -> Result<(), String> {
+    let mut problems = Vec::new();
+    for &source in required {
+        if failed.contains(&source) {
+            problems.push(format!("{:?} could not be fetched", source));
+        } else if !repos.iter().any(|repo| repo.source == source) {
+            problems.push(format!("{:?} returned no repositories, check the token and its permissions", source));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+/// Loads all repositories before returning, from the cache or by fetching them
+///
+/// Used when the repositories are written out instead of shown in the fuzzy finder.
+/// Also returns the sources which failed to fetch.
+pub async fn load_all_repositories(args: &cli::AppArgs) -> (Vec<cache::RepoData>, Vec<formatter::RepoSource>) {
+    if args.use_dummy || args.basket {
+        let mut all_repos = Vec::new();
+        if args.use_dummy {
+            load_dummy_repositories(&mut all_repos);
+        } else {
+            load_basket_repositories(&mut all_repos);
+        }
+        return (all_repos, Vec::new());
+    }
+
+    if !args.force_download {
+        if let Some(cache_data) = cache::load_cache().filter(|c| !c.is_expired(args.cache_expiry)) {
+            return (cache_data.get_all_repositories(), Vec::new());
+        }
+    }
+
+    // Fetch like the background refresh, which also updates the cache
+    let (tx, mut rx) = mpsc::channel(100);
+    spawn_background_task(
+        args.github_token.clone(),
+        args.gitlab_token.clone(),
+        None,
+        FetchOptions::from_args(args),
+        tx,
+        Arc::default(),
+    );
+
+    let mut all_repos = Vec::new();
+    let mut failed_sources = Vec::new();
+    while let Some(message) = rx.recv().await {
+        match message {
+            RepoUpdateMessage::NewRepos(repos) => all_repos = repos,
+            RepoUpdateMessage::Error(error) => eprintln!("Error: {}", error),
+            RepoUpdateMessage::FetchFailed(source) => failed_sources.push(source),
+            RepoUpdateMessage::TokenExpires(source, expires_at) => {
+                if let Some(warning) = token_expiry_warning(source, expires_at, Utc::now(), args.token_expiry_warning_days) {
+                    eprintln!("Warning: {}", warning);
+                }
+            }
+            RepoUpdateMessage::Status(_) | RepoUpdateMessage::AuthFailed(_) => {}
+            RepoUpdateMessage::LoadingComplete(_) => break,
+        }
+    }
+
+    (all_repos, failed_sources)
+}
+
+/// Returns the cached identity to reuse instead of asking the API who the token belongs to.
+///
+/// The identity is fetched again with `--refresh-identity`, when no username was cached,
+/// or when it was cached for another token.
+pub fn reusable_identity<'a>(
+    cached: Option<&'a cache::SourceCache>,
+    token: &str,
+    refresh_identity: bool,
+) -> Option<&'a cache::SourceCache> {
+    cached.filter(|cached| {
+        !refresh_identity
+            && !cached.username.is_empty()
+            && cached.token_fingerprint.as_deref() == Some(cache::token_fingerprint(token).as_str())
+    })
+}
+
+/// Merges incrementally fetched repositories into the cached ones of the same user.
+///
+/// Returns None if there is no cache for the user, so everything has to be fetched.
+fn merge_incremental(
+    base: Option<&cache::SourceData>,
+    username: &str,
+    updates: Vec<cache::RepoData>
+) -> Option<Vec<cache::RepoData>> {
+    base.filter(|base| base.cache_info.username == username)
+        .map(|base| cache::merge_repositories(&base.repositories, updates))
+}
+
+/// How the background task fetches repositories, taken from the command line
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    pub github_orgs: Vec<String>,
+    pub gitlab_groups: Vec<String>,
+    pub gitlab_language_concurrency: Option<usize>,
+    pub gitlab_page_concurrency: usize,
+    pub include_gists: bool,
+    pub include_starred: bool,
+    pub max_description_length: usize,
+    pub refresh_identity: bool,
+    /// Sources listed and cached with their previous repositories instead of being fetched
+    pub kept_sources: Vec<formatter::RepoSource>,
+}
+
+impl FetchOptions {
+    pub fn from_args(args: &cli::AppArgs) -> Self {
+        Self {
+            github_orgs: args.orgs.clone(),
+            gitlab_groups: args.gitlab_groups.clone(),
+            gitlab_language_concurrency: args.gitlab_languages.then_some(args.max_concurrency),
+            gitlab_page_concurrency: args.max_concurrency,
+            include_gists: args.include_gists,
+            include_starred: args.include_starred,
+            max_description_length: args.max_description_length,
+            refresh_identity: args.refresh_identity,
+            kept_sources: Vec::new(),
+        }
+    }
+}
+
+/// Starts the background refresh over, cancelling the one running, like after a new token
+pub struct Refresher {
+    github_token: Option<String>,
+    gitlab_token: Option<String>,
+    options: FetchOptions,
+    tx: mpsc::Sender<RepoUpdateMessage>,
+    cancel: Arc<AtomicBool>,
+    /// Sources whose token was replaced, fetched again once no refresh is running
+    retries: Vec<formatter::RepoSource>,
+}
+
+impl Refresher {
+    /// Takes over the refresh started with `cancel`, if one is running
+    pub fn new(args: &cli::AppArgs, tx: mpsc::Sender<RepoUpdateMessage>, cancel: Arc<AtomicBool>) -> Self {
+        Self {
+            github_token: args.github_token.clone(),
+            gitlab_token: args.gitlab_token.clone(),
+            options: FetchOptions::from_args(args),
+            tx,
+            cancel,
+            retries: Vec::new(),
+        }
+    }
+
+    /// Replaces the token of a source, whose username is then fetched again
+    pub fn set_token(&mut self, source: formatter::RepoSource, token: String) {
+        match source {
+            formatter::RepoSource::GitHub => self.github_token = Some(token),
+            formatter::RepoSource::GitLab => self.gitlab_token = Some(token),
+        }
+        // The cached username may belong to the rejected token, so always ask who the new one is
+        self.options.refresh_identity = true;
+    }
+
+    /// Fetches a source again with the token replacing its rejected one
+    ///
+    /// The other source keeps its cached repositories. While a refresh is running,
+    /// the source is only fetched once it is done, see `run_retries`.
+    pub fn retry_with_token(&mut self, source: formatter::RepoSource, token: String) {
+        self.set_token(source, token);
+        if !self.retries.contains(&source) {
+            self.retries.push(source);
+        }
+        if !terminal::is_busy() {
+            self.run_retries();
+        }
+    }
+
+    /// Fetches the sources whose token was replaced, if any
+    pub fn run_retries(&mut self) {
+        if self.retries.is_empty() {
+            return;
+        }
+        let retries = std::mem::take(&mut self.retries);
+        let tokens = [
+            (formatter::RepoSource::GitHub, self.github_token.clone()),
+            (formatter::RepoSource::GitLab, self.gitlab_token.clone()),
+        ];
+        let kept_sources = tokens
+            .iter()
+            .filter(|(source, token)| token.is_some() && !retries.contains(source))
+            .map(|(source, _)| *source)
+            .collect();
+        let [(_, github_token), (_, gitlab_token)] = tokens;
+
+        self.cancel = Arc::default();
+        spawn_background_task(
+            github_token.filter(|_| retries.contains(&formatter::RepoSource::GitHub)),
+            gitlab_token.filter(|_| retries.contains(&formatter::RepoSource::GitLab)),
+            None,
+            FetchOptions { kept_sources, ..self.options.clone() },
+            self.tx.clone(),
+            self.cancel.clone(),
+        );
+    }
+
+    /// Cancels the running refresh and fetches everything again
+    pub fn restart(&mut self) {
+        self.retries.clear();
+        self.cancel.store(true, Ordering::SeqCst);
+        self.cancel = Arc::default();
+        spawn_background_task(
+            self.github_token.clone(),
+            self.gitlab_token.clone(),
+            None,
+            self.options.clone(),
+            self.tx.clone(),
+            self.cancel.clone(),
+        );
+    }
 }
 
 /// Spawns a background task to fetch repositories
-fn spawn_background_task(
+///
+/// With an incremental base, only repositories updated since each source was cached
+/// are fetched and merged into the cached ones. With a GitLab language concurrency,
+/// the languages of GitLab projects are fetched with at most that many requests at once,
+/// and GitLab pages are fetched with at most the page concurrency at once.
+/// With `include_gists`, GitHub gists and GitLab snippets are fetched along with the
+/// repositories and cached with them, and with `include_starred` so are the starred
+/// repositories not owned by the user. The repositories of `github_orgs` are fetched
+/// completely and replace the listed ones with the same URL, and so are the projects of
+/// `gitlab_groups` with their subgroups. The usernames of the previous cache are reused
+/// instead of fetched, unless `refresh_identity` is set. The `kept_sources` are not
+/// fetched, their repositories of the previous cache are listed and cached again.
+///
+/// Setting `cancel` stops the task before the next page, without sending anything further
+/// or saving the cache, so another task can start over.
+pub fn spawn_background_task(
     github_token: Option<String>,
     gitlab_token: Option<String>,
-    tx: mpsc::Sender<RepoUpdateMessage>
+    incremental_base: Option<cache::CacheData>,
+    options: FetchOptions,
+    tx: mpsc::Sender<RepoUpdateMessage>,
+    cancel: Arc<AtomicBool>
 ) {
+    let FetchOptions { github_orgs, gitlab_groups, gitlab_language_concurrency, gitlab_page_concurrency, include_gists, include_starred, max_description_length, refresh_identity, kept_sources } = options;
+
+    // Quitting from the fuzzy finder can ask for confirmation while this runs
+    terminal::set_busy(true);
+
     // Use a thread instead of a task to avoid Send issues
     std::thread::spawn(move || {
         // Create a new runtime for this thread
@@ -166,35 +627,123 @@ fn spawn_background_task(
             // Create a new cache
             let mut cache_data = cache::CacheData::new();
             let mut all_repos = Vec::new();
-            let mut github_username = String::new();
-            let mut gitlab_username = String::new();
+
+            // The previous cache is compared against, for the sources which could be fetched
+            let previous_cache = cache::load_cache();
+            let mut fetched_sources = Vec::new();
+            let mut failed_sources = Vec::new();
+
+            // The kept sources are listed before the fetched ones, as they were already shown
+            if let Some(previous) = &previous_cache {
+                all_repos.extend(
+                    previous
+                        .get_all_repositories()
+                        .into_iter()
+                        .filter(|repo| kept_sources.contains(&repo.source)),
+                );
+            }
 
             // Fetch from GitHub if token is provided
             if let Some(github_token) = &github_token {
                 let _ = tx.send(RepoUpdateMessage::Status("Fetching GitHub repositories...".to_string())).await;
 
-                match github::fetch_repos(github_token).await {
-                    Ok((gh_username, gh_repos)) => {
-                        github_username = gh_username.clone();
+                let github_base = incremental_base.as_ref().and_then(|c| c.github.as_ref());
+                let github_since = github_base.map(|b| b.cache_info.timestamp);
+                let cached_identity = previous_cache.as_ref().and_then(|c| c.github.as_ref()).map(|c| &c.cache_info);
+                let identity = reusable_identity(cached_identity, github_token, refresh_identity);
+                let known_username = identity.map(|identity| identity.username.as_str());
+                let mut result = github::fetch_repos(github_token, github_since, known_username, &cancel).await;
+
+                // The cached username may be what failed, so try once more with the user call,
+                // unless the rate limit was exceeded or the token rejected, which another request would only hit again
+                if known_username.is_some() && matches!(&result, Err(e) if !matches!(e, github::FetchError::RateLimited(_) | github::FetchError::Cancelled | github::FetchError::InvalidToken)) {
+                    result = github::fetch_repos(github_token, github_since, None, &cancel).await;
+                }
+
+                // The cache belongs to another user, so fetch everything instead
+                if matches!(&result, Ok((gh_username, ..)) if github_base.is_some_and(|b| &b.cache_info.username != gh_username)) {
+                    result = github::fetch_repos(github_token, None, None, &cancel).await;
+                }
+
+                // The busy flag and the channel belong to the task started instead
+                if terminal::should_stop(&cancel) {
+                    return;
+                }
+
+                match result {
+                    Ok((github_username, gh_repos, expires_at)) => {
+
+                        // Reusing the identity skips the call telling when the token expires
+                        let expires_at = expires_at.or(identity.and_then(|identity| identity.token_expires_at));
+
+                        if let Some(expires_at) = expires_at {
+                            let _ = tx.send(RepoUpdateMessage::TokenExpires(formatter::RepoSource::GitHub, expires_at)).await;
+                        }
 
                         // Convert GitHub repos to RepoData
-                        let github_repo_data: Vec<cache::RepoData> = gh_repos
+                        let mut github_repo_data: Vec<cache::RepoData> = gh_repos
                             .iter()
-                            .map(|repo| cache::github_repo_to_repo_data(repo))
+                            .map(|repo| cache::github_repo_to_repo_data(repo, max_description_length))
                             .collect();
 
+                        // Incremental results only contain the updated repositories
+                        if let Some(merged) = merge_incremental(github_base, &github_username, github_repo_data.clone()) {
+                            github_repo_data = merged;
+                        }
+
+                        // Organization repositories are owned by their organization, even if listed as the user's
+                        for org in &github_orgs {
+                            let _ = tx.send(RepoUpdateMessage::Status(format!("Fetching repositories of {}...", org))).await;
+                            match github::fetch_org_repos(github_token, org, &cancel).await {
+                                Ok(org_repos) => {
+                                    let org_repo_data = org_repos
+                                        .iter()
+                                        .map(|repo| cache::github_repo_to_repo_data(repo, max_description_length))
+                                        .collect();
+                                    github_repo_data = cache::merge_repositories(&github_repo_data, org_repo_data);
+                                }
+                                Err(e) => {
+                                    let _ = tx.send(RepoUpdateMessage::Error(format!("GitHub organization {} error: {}", org, e))).await;
+                                }
+                            }
+                        }
+
+                        // Gists are always fetched completely, replacing the cached ones
+                        github_repo_data.retain(|repo| !repo.is_gist());
+                        if include_gists {
+                            match github::fetch_gists(github_token, &github_username, &cancel).await {
+                                Ok(gists) => github_repo_data.extend(
+                                    gists.iter().map(|gist| cache::gist_to_repo_data(gist, formatter::RepoSource::GitHub, max_description_length))
+                                ),
+                                Err(e) => {
+                                    let _ = tx.send(RepoUpdateMessage::Error(format!("GitHub gists error: {}", e))).await;
+                                }
+                            }
+                        }
+
+                        // Starred repositories are fetched completely as well, after the owned ones
+                        github_repo_data.retain(|repo| !repo.is_starred);
+                        if include_starred {
+                            match github::fetch_starred(github_token, &cancel).await {
+                                Ok(starred) => cache::add_starred(
+                                    &mut github_repo_data,
+                                    starred.iter().map(|repo| cache::github_repo_to_repo_data(repo, max_description_length)).collect(),
+                                ),
+                                Err(e) => {
+                                    let _ = tx.send(RepoUpdateMessage::Error(format!("GitHub starred error: {}", e))).await;
+                                }
+                            }
+                        }
+
                         // Add to all_repos
                         all_repos.extend(github_repo_data.clone());
 
                         // Update cache
-                        cache_data.update_github(github_username.clone(), github_repo_data);
+                        cache_data.update_github(github_username.clone(), github_repo_data).set_token(github_token, expires_at);
+                        fetched_sources.push(formatter::RepoSource::GitHub);
 
                         // Send update message with the GitHub repos
-                        let _ = tx.send(RepoUpdateMessage::NewRepos {
-                            repos: all_repos.clone(),
-                            github_username: github_username.clone(),
-                            gitlab_username: gitlab_username.clone(),
-                        }).await;
+                        let _ = tx.send(RepoUpdateMessage::NewRepos(all_repos.clone())).await;
 
                         let _ = tx.send(RepoUpdateMessage::Status(
                             format!("Fetched {} GitHub repositories", gh_repos.len())
@@ -202,8 +751,15 @@ fn spawn_background_task(
                     },
                     Err(e) => {
                         // Format error message before sending to avoid Send issues
-                        let error_msg = format!("GitHub error: {}", e);
+                        let error_msg = e.to_string();
+                        let auth_failed = github::is_auth_error(&e);
                         let _ = tx.send(RepoUpdateMessage::Error(error_msg)).await;
+                        let _ = tx.send(RepoUpdateMessage::FetchFailed(formatter::RepoSource::GitHub)).await;
+                        failed_sources.push(formatter::RepoSource::GitHub);
+
+                        if auth_failed {
+                            let _ = tx.send(RepoUpdateMessage::AuthFailed(formatter::RepoSource::GitHub)).await;
+                        }
                     }
                 }
             }
@@ -212,28 +768,100 @@ fn spawn_background_task(
             if let Some(gitlab_token) = &gitlab_token {
                 let _ = tx.send(RepoUpdateMessage::Status("Fetching GitLab repositories...".to_string())).await;
 
-                match gitlab::fetch_repos(gitlab_token).await {
-                    Ok((gl_username, gl_repos)) => {
-                        gitlab_username = gl_username.clone();
+                let gitlab_base = incremental_base.as_ref().and_then(|c| c.gitlab.as_ref());
+                let gitlab_since = gitlab_base.map(|b| b.cache_info.timestamp);
+                let cached_identity = previous_cache.as_ref().and_then(|c| c.gitlab.as_ref()).map(|c| &c.cache_info);
+                let known_username = reusable_identity(cached_identity, gitlab_token, refresh_identity)
+                    .map(|identity| identity.username.as_str());
+                let mut result = gitlab::fetch_repos(gitlab_token, gitlab_since, gitlab_language_concurrency, gitlab_page_concurrency, known_username, &cancel).await;
+
+                // The cached username may be what failed, so try once more with the user call, unless the token was rejected
+                if known_username.is_some() && matches!(&result, Err(e) if !matches!(e, gitlab::FetchError::InvalidToken | gitlab::FetchError::Cancelled)) {
+                    result = gitlab::fetch_repos(gitlab_token, gitlab_since, gitlab_language_concurrency, gitlab_page_concurrency, None, &cancel).await;
+                }
+
+                // The cache belongs to another user, so fetch everything instead
+                if matches!(&result, Ok((gl_username, ..)) if gitlab_base.is_some_and(|b| &b.cache_info.username != gl_username)) {
+                    result = gitlab::fetch_repos(gitlab_token, None, gitlab_language_concurrency, gitlab_page_concurrency, None, &cancel).await;
+                }
+
+                if terminal::should_stop(&cancel) {
+                    return;
+                }
+
+                match result {
+                    Ok((gitlab_username, gl_repos, expires_at)) => {
+
+                        if let Some(expires_at) = expires_at {
+                            let _ = tx.send(RepoUpdateMessage::TokenExpires(formatter::RepoSource::GitLab, expires_at)).await;
+                        }
 
                         // Convert GitLab repos to RepoData
-                        let gitlab_repo_data: Vec<cache::RepoData> = gl_repos
+                        let mut gitlab_repo_data: Vec<cache::RepoData> = gl_repos
                             .iter()
-                            .map(|repo| cache::gitlab_repo_to_repo_data(repo))
+                            .map(|repo| cache::gitlab_repo_to_repo_data(repo, max_description_length))
                             .collect();
 
+                        // Incremental results only contain the updated repositories
+                        if let Some(merged) = merge_incremental(gitlab_base, &gitlab_username, gitlab_repo_data.clone()) {
+                            gitlab_repo_data = merged;
+                        }
+
+                        // Group projects are owned by their group, even if listed as the user's
+                        for group in &gitlab_groups {
+                            let _ = tx.send(RepoUpdateMessage::Status(format!("Fetching projects of {}...", group))).await;
+                            match gitlab::fetch_group_repos(gitlab_token, group, &cancel).await {
+                                Ok(group_repos) => {
+                                    let group_repo_data = group_repos
+                                        .iter()
+                                        .map(|repo| cache::gitlab_repo_to_repo_data(repo, max_description_length))
+                                        .collect();
+                                    gitlab_repo_data = cache::merge_repositories(&gitlab_repo_data, group_repo_data);
+                                }
+                                Err(e) => {
+                                    let _ = tx.send(RepoUpdateMessage::Error(format!("GitLab group {} error: {}", group, e))).await;
+                                }
+                            }
+                        }
+
+                        // Snippets are always fetched completely, replacing the cached ones
+                        gitlab_repo_data.retain(|repo| !repo.is_gist());
+                        if include_gists {
+                            match gitlab::fetch_snippets(gitlab_token, &gitlab_username, &cancel).await {
+                                Ok(snippets) => gitlab_repo_data.extend(
+                                    snippets.iter().map(|snippet| cache::gist_to_repo_data(snippet, formatter::RepoSource::GitLab, max_description_length))
+                                ),
+                                Err(e) => {
+                                    let error_msg = format!("GitLab snippets error: {}", e);
+                                    let _ = tx.send(RepoUpdateMessage::Error(error_msg)).await;
+                                }
+                            }
+                        }
+
+                        // Starred projects are fetched completely as well, after the owned ones
+                        gitlab_repo_data.retain(|repo| !repo.is_starred);
+                        if include_starred {
+                            match gitlab::fetch_starred(gitlab_token, &cancel).await {
+                                Ok(starred) => cache::add_starred(
+                                    &mut gitlab_repo_data,
+                                    starred.iter().map(|repo| cache::gitlab_repo_to_repo_data(repo, max_description_length)).collect(),
+                                ),
+                                Err(e) => {
+                                    let error_msg = format!("GitLab starred error: {}", e);
+                                    let _ = tx.send(RepoUpdateMessage::Error(error_msg)).await;
+                                }
+                            }
+                        }
+
                         // Add to all_repos
                         all_repos.extend(gitlab_repo_data.clone());
 
                         // Update cache
-                        cache_data.update_gitlab(gitlab_username.clone(), gitlab_repo_data);
+                        cache_data.update_gitlab(gitlab_username.clone(), gitlab_repo_data).set_token(gitlab_token, expires_at);
+                        fetched_sources.push(formatter::RepoSource::GitLab);
 
                         // Send update message with all repos
-                        let _ = tx.send(RepoUpdateMessage::NewRepos {
-                            repos: all_repos.clone(),
-                            github_username: github_username.clone(),
-                            gitlab_username: gitlab_username.clone(),
-                        }).await;
+                        let _ = tx.send(RepoUpdateMessage::NewRepos(all_repos.clone())).await;
 
                         let _ = tx.send(RepoUpdateMessage::Status(
                             format!("Fetched {} GitLab repositories", gl_repos.len())
@@ -241,12 +869,34 @@ fn spawn_background_task(
                     },
                     Err(e) => {
                         // Format error message before sending to avoid Send issues
-                        let error_msg = format!("GitLab error: {}", e);
+                        let error_msg = e.to_string();
+                        let auth_failed = gitlab::is_auth_error(&e);
                         let _ = tx.send(RepoUpdateMessage::Error(error_msg)).await;
+                        let _ = tx.send(RepoUpdateMessage::FetchFailed(formatter::RepoSource::GitLab)).await;
+                        failed_sources.push(formatter::RepoSource::GitLab);
+
+                        if auth_failed {
+                            let _ = tx.send(RepoUpdateMessage::AuthFailed(formatter::RepoSource::GitLab)).await;
+                        }
                     }
                 }
             }
 
+            // Cancelled while fetching organizations, gists or starred repositories
+            if terminal::should_stop(&cancel) {
+                return;
+            }
+
+            // A source which failed keeps its last good data, read again in case it changed meanwhile
+            if let Some(on_disk) = cache::load_cache() {
+                cache_data.keep_sources(on_disk, &[failed_sources, kept_sources].concat());
+            }
+
+            // A newer refresh may have started meanwhile, whose cache must not be overwritten
+            if terminal::should_stop(&cancel) {
+                return;
+            }
+
             // Save the cache
             match cache::save_cache(&cache_data) {
                 Ok(_) => {
@@ -259,8 +909,227 @@ fn spawn_background_task(
                 }
             }
 
+            // Summarize what changed since the previous cache
+            let changes = previous_cache.map(|previous| {
+                let previous_repos: Vec<cache::RepoData> = previous
+                    .get_all_repositories()
+                    .into_iter()
+                    .filter(|repo| fetched_sources.contains(&repo.source))
+                    .collect();
+                let fetched_repos: Vec<cache::RepoData> = all_repos
+                    .iter()
+                    .filter(|repo| fetched_sources.contains(&repo.source))
+                    .cloned()
+                    .collect();
+                cache::diff_repositories(&previous_repos, &fetched_repos).summary()
+            });
+
+            // A newer refresh owns the busy state and its own completion now
+            if terminal::should_stop(&cancel) {
+                return;
+            }
+
             // Signal that background loading is complete
-            let _ = tx.send(RepoUpdateMessage::LoadingComplete).await;
+            terminal::set_busy(false);
+            let _ = tx.send(RepoUpdateMessage::LoadingComplete(changes)).await;
         });
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cache::RepoKind;
+    use formatter::RepoSource;
+
+    #[test]
+    fn test_parse_repo_spec() {
+        assert_eq!(parse_repo_spec("dima-369/rust-web-server"), Some(("dima-369", "rust-web-server")));
+        assert_eq!(parse_repo_spec(" company/team/api/ "), Some(("company/team", "api")));
+
+        assert_eq!(parse_repo_spec("rust-web-server"), None);
+        assert_eq!(parse_repo_spec("/rust-web-server"), None);
+        assert_eq!(parse_repo_spec("dima-369/"), None);
+    }
+
+    #[test]
+    fn test_lookup_order() {
+        // GitHub is tried before GitLab without a hint
+        assert_eq!(lookup_order(None, true, true), vec![RepoSource::GitHub, RepoSource::GitLab]);
+        assert_eq!(lookup_order(None, false, true), vec![RepoSource::GitLab]);
+
+        // A hint restricts the lookup to that source
+        assert_eq!(lookup_order(Some(RepoSource::GitLab), true, true), vec![RepoSource::GitLab]);
+        assert!(lookup_order(Some(RepoSource::GitHub), false, true).is_empty());
+    }
+
+    #[test]
+    fn test_web_url() {
+        assert_eq!(web_url(RepoSource::GitHub, RepoKind::Repository, "dima-369", "notes"), "https://github.com/dima-369/notes");
+        assert_eq!(web_url(RepoSource::GitLab, RepoKind::Repository, "company/team", "api"), "https://gitlab.com/company/team/api");
+    }
+
+    #[test]
+    fn test_browser_url() {
+        let repo = |name: &str, url: &str, source: RepoSource| cache::RepoData {
+            url: url.to_string(),
+            description: "Internal (v2) API".to_string(),
+            owner: "gira".to_string(),
+            is_private: true,
+            source,
+            ..cache::RepoData::named(name)
+        };
+
+        let github = repo("private-api", "git@github.com:gira/private-api.git", RepoSource::GitHub);
+        assert_eq!(browser_url(&github), "https://github.com/gira/private-api");
+
+        // GitLab projects are shown by their name, but opened by their path
+        let gitlab = repo("Game Demo", "git@gitlab.com:company/team/game-demo.git", RepoSource::GitLab);
+        assert_eq!(browser_url(&gitlab), "https://gitlab.com/company/team/game-demo");
+        let gitlab = repo("Game Demo", "", RepoSource::GitLab);
+        assert_eq!(browser_url(&gitlab), "https://gitlab.com/gira/game-demo");
+
+        // Gists and snippets are opened by their id
+        let gist = cache::RepoData {
+            kind: RepoKind::Gist,
+            ..repo("aa5a315d", "git@gist.github.com:aa5a315d.git", RepoSource::GitHub)
+        };
+        assert_eq!(browser_url(&gist), "https://gist.github.com/gira/aa5a315d");
+        let snippet = cache::RepoData {
+            kind: RepoKind::Gist,
+            ..repo("2814", "git@gitlab.com:snippets/2814.git", RepoSource::GitLab)
+        };
+        assert_eq!(browser_url(&snippet), "https://gitlab.com/-/snippets/2814");
+    }
+
+    #[test]
+    fn test_tree_url() {
+        let github = "https://github.com/dima-369/rust-web-server";
+        assert_eq!(
+            tree_url(github, RepoSource::GitHub, RepoKind::Repository, Some("main"), None),
+            "https://github.com/dima-369/rust-web-server/tree/main"
+        );
+        assert_eq!(
+            tree_url(github, RepoSource::GitHub, RepoKind::Repository, Some("main"), Some("/src/main.rs")),
+            "https://github.com/dima-369/rust-web-server/tree/main/src/main.rs"
+        );
+
+        let gitlab = "https://gitlab.com/gira/game-demo/";
+        assert_eq!(
+            tree_url(gitlab, RepoSource::GitLab, RepoKind::Repository, Some("develop"), Some("assets")),
+            "https://gitlab.com/gira/game-demo/-/tree/develop/assets"
+        );
+
+        // Without a branch there is no ref to link the path to
+        assert_eq!(tree_url(gitlab, RepoSource::GitLab, RepoKind::Repository, None, Some("assets")), "https://gitlab.com/gira/game-demo");
+        assert_eq!(tree_url(github, RepoSource::GitHub, RepoKind::Repository, Some(""), None), github);
+
+        // Gists have no file browser
+        let gist = "https://gist.github.com/dima-369/aa5a315d";
+        assert_eq!(tree_url(gist, RepoSource::GitHub, RepoKind::Gist, Some("main"), Some("a.rs")), gist);
+    }
+
+    #[test]
+    fn test_post_action_delay() {
+        assert_eq!(post_action_delay(FinderAction::Open, 1000), Some(Duration::from_secs(1)));
+        assert_eq!(post_action_delay(FinderAction::Open, 250), Some(Duration::from_millis(250)));
+
+        // Zero returns to the finder immediately
+        assert_eq!(post_action_delay(FinderAction::Open, 0), None);
+
+        // The file browser waits like opening the repository
+        assert_eq!(post_action_delay(FinderAction::Tree, 250), Some(Duration::from_millis(250)));
+
+        // The git TUI never waits, whatever is configured
+        assert_eq!(post_action_delay(FinderAction::GitTui, 1000), None);
+        assert_eq!(post_action_delay(FinderAction::GitTui, 0), None);
+    }
+
+    #[test]
+    fn test_appended_since() {
+        let mut repos = Vec::new();
+        load_dummy_repositories(&mut repos);
+
+        let batch = appended_since(&repos[..3], &repos[..5]).unwrap();
+        assert_eq!(batch.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), vec![repos[3].name.as_str(), repos[4].name.as_str()]);
+        assert_eq!(appended_since(&[], &repos[..2]).unwrap().len(), 2);
+        assert!(appended_since(&repos[..2], &repos[..2]).unwrap().is_empty());
+
+        // A list not starting with the previous repositories is a new fetch
+        assert!(appended_since(&repos[..3], &repos[1..5]).is_none());
+        assert!(appended_since(&repos[..3], &repos[..2]).is_none());
+    }
+
+    #[test]
+    fn test_token_expiry_warning() {
+        let now = DateTime::parse_from_rfc3339("2025-01-10T12:00:00Z").unwrap().with_timezone(&Utc);
+        let in_days = |days: f64| now + chrono::Duration::seconds((days * 86_400.0) as i64);
+
+        assert_eq!(
+            token_expiry_warning(RepoSource::GitHub, in_days(3.5), now, 7),
+            Some("GitHub token expires in 3 days".to_string())
+        );
+        assert_eq!(
+            token_expiry_warning(RepoSource::GitLab, in_days(1.0), now, 7),
+            Some("GitLab token expires in 1 day".to_string())
+        );
+        assert_eq!(
+            token_expiry_warning(RepoSource::GitHub, in_days(0.25), now, 7),
+            Some("GitHub token expires today".to_string())
+        );
+
+        // The window includes its last day, but nothing after it
+        assert!(token_expiry_warning(RepoSource::GitHub, in_days(7.0), now, 7).is_some());
+        assert!(token_expiry_warning(RepoSource::GitHub, in_days(7.5), now, 7).is_none());
+        assert!(token_expiry_warning(RepoSource::GitHub, in_days(2.0), now, 0).is_none());
+        assert_eq!(
+            token_expiry_warning(RepoSource::GitHub, in_days(0.5), now, 0),
+            Some("GitHub token expires today".to_string())
+        );
+
+        // An expired token is an authentication error instead
+        assert!(token_expiry_warning(RepoSource::GitHub, in_days(-1.0), now, 7).is_none());
+    }
+
+    #[test]
+    fn test_reusable_identity() {
+        let username = |cached: Option<&cache::SourceCache>, token, refresh| {
+            reusable_identity(cached, token, refresh).map(|identity| identity.username.clone())
+        };
+        let mut octocat = cache::SourceCache::new("octocat".to_string());
+        octocat.set_token("ghp_first", None);
+        assert_eq!(username(Some(&octocat), "ghp_first", false), Some("octocat".to_string()));
+        assert_eq!(username(Some(&octocat), "ghp_first", true), None);
+
+        // A new token may belong to someone else
+        assert_eq!(username(Some(&octocat), "ghp_second", false), None);
+
+        // Without a cached username or token the identity has to be fetched
+        let mut unnamed = cache::SourceCache::new(String::new());
+        unnamed.set_token("ghp_first", None);
+        assert_eq!(username(Some(&unnamed), "ghp_first", false), None);
+        assert_eq!(username(Some(&cache::SourceCache::new("octocat".to_string())), "ghp_first", false), None);
+        assert_eq!(username(None, "ghp_first", false), None);
+    }
+
+    #[test]
+    fn test_check_required_sources() {
+        // The dummy repositories are all on GitHub
+        let mut repos = Vec::new();
+        load_dummy_repositories(&mut repos);
+
+        // Nothing required, or a source with repositories, passes
+        assert_eq!(check_required_sources(&[], &[], &[]), Ok(()));
+        assert_eq!(check_required_sources(&[RepoSource::GitHub], &repos, &[]), Ok(()));
+
+        // An empty source is told apart from one which failed to fetch
+        let empty = check_required_sources(&[RepoSource::GitLab], &repos, &[]).unwrap_err();
+        assert_eq!(empty, "GitLab returned no repositories, check the token and its permissions");
+        let failed = check_required_sources(&[RepoSource::GitHub, RepoSource::GitLab], &[], &[RepoSource::GitHub]);
+        assert_eq!(
+            failed.unwrap_err(),
+            "GitHub could not be fetched; GitLab returned no repositories, check the token and its permissions"
+        );
+    }
+}
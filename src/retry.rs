@@ -0,0 +1,99 @@
+//! Retrying API requests which failed for a transient reason, like a 502 or a dropped connection
+
+use std::future::Future;
+use std::time::Duration;
+
+/// How often a failed request is retried before its error is returned
+pub const MAX_RETRIES: u32 = 3;
+
+/// Wait before the first retry, doubled for each further one
+pub const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Returns how long to wait before a retry, counted from 0: 500ms, 1s, 2s and so on
+pub fn backoff_delay(base_delay: Duration, retry: u32) -> Duration {
+    base_delay.saturating_mul(2u32.saturating_pow(retry))
+}
+
+/// Runs `attempt` until it succeeds, retrying errors which are transient up to `max_retries` times.
+///
+/// Other errors, like a rejected token, are returned right away.
+pub async fn retry<T, E, F, Fut>(
+    max_retries: u32,
+    base_delay: Duration,
+    is_transient: impl Fn(&E) -> bool,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Err(e) if retries < max_retries && is_transient(&e) => {
+                tokio::time::sleep(backoff_delay(base_delay, retries)).await;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Runs `attempt` with the default number of retries and backoff
+pub async fn with_backoff<T, E, F, Fut>(is_transient: impl Fn(&E) -> bool, attempt: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry(MAX_RETRIES, BASE_DELAY, is_transient, attempt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_backoff_doubles() {
+        let delays: Vec<Duration> = (0..3).map(|retry| backoff_delay(BASE_DELAY, retry)).collect();
+        assert_eq!(delays, vec![Duration::from_millis(500), Duration::from_secs(1), Duration::from_secs(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors_until_ok() {
+        let attempts = Cell::new(0);
+        let result: Result<&str, &str> = retry(3, Duration::ZERO, |_| true, || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move { if attempt <= 2 { Err("502 Bad Gateway") } else { Ok("page") } }
+        })
+        .await;
+
+        assert_eq!(result, Ok("page"));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_other_errors() {
+        let attempts = Cell::new(0);
+        let result: Result<(), &str> = retry(3, Duration::ZERO, |e: &&str| e.starts_with('5'), || {
+            attempts.set(attempts.get() + 1);
+            async { Err("401 Unauthorized") }
+        })
+        .await;
+
+        assert_eq!(result, Err("401 Unauthorized"));
+        assert_eq!(attempts.get(), 1);
+
+        // Transient errors are returned once the retries are used up
+        attempts.set(0);
+        let result: Result<(), &str> = retry(2, Duration::ZERO, |_| true, || {
+            attempts.set(attempts.get() + 1);
+            async { Err("503 Service Unavailable") }
+        })
+        .await;
+
+        assert_eq!(result, Err("503 Service Unavailable"));
+        assert_eq!(attempts.get(), 3);
+    }
+}
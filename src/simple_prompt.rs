@@ -0,0 +1,178 @@
+//! Line-based repository selection for terminals where the fuzzy finder misbehaves
+//!
+//! Matches are printed as a numbered list and every line read is either a number to
+//! select, or a query narrowing the current matches down further. Nothing switches
+//! the terminal into raw mode or the alternate screen.
+
+use crate::filter;
+use crate::fuzzy_finder::FinderAction;
+use std::io::{self, BufRead, Write};
+
+/// Most matches printed at once, the rest are only counted
+const MAX_SHOWN: usize = 20;
+
+/// What a line typed at the prompt asks for
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    /// Select the shown match with this index (counted from 0) for an action
+    Select(usize, FinderAction),
+    /// Narrow the current matches down with a query
+    Refine(String),
+    /// Go back to all repositories
+    Reset,
+    Quit,
+    Invalid(String),
+}
+
+/// Parses a prompt line, where numbers count from 1 and `g` before one opens a git TUI
+pub fn parse_input(line: &str, shown: usize) -> Input {
+    let line = line.trim();
+    if line.is_empty() {
+        return Input::Reset;
+    }
+    if line == "q" {
+        return Input::Quit;
+    }
+
+    let (number, action) = match line.strip_prefix('g') {
+        Some(number) => (number, FinderAction::GitTui),
+        None => (line, FinderAction::Open),
+    };
+
+    match number.parse::<usize>() {
+        Ok(n) if (1..=shown).contains(&n) => Input::Select(n - 1, action),
+        Ok(n) => Input::Invalid(format!("There is no match numbered {}", n)),
+        // Anything else, like "go" or "rust web", is a query
+        Err(_) => Input::Refine(line.to_string()),
+    }
+}
+
+/// The matches of a line-based selection, narrowed down by each query
+pub struct SimplePrompt {
+    items: Vec<String>,
+    matches: Vec<usize>,
+}
+
+impl SimplePrompt {
+    pub fn new(items: Vec<String>) -> Self {
+        let matches = (0..items.len()).collect();
+        Self { items, matches }
+    }
+
+    /// Returns the matches which are printed and can be selected by number
+    pub fn shown(&self) -> impl Iterator<Item = &str> {
+        self.matches.iter().take(MAX_SHOWN).map(|&id| self.items[id].as_str())
+    }
+
+    /// Narrows the current matches down, like typing more words into the fuzzy finder
+    pub fn refine(&mut self, query: &str) {
+        self.matches = filter::filter_human(&self.matches, query, |&id| self.items[id].clone());
+    }
+
+    pub fn reset(&mut self) {
+        self.matches = (0..self.items.len()).collect();
+    }
+
+    /// Handles one prompt line, returning the action and the index of the selected item if it made one
+    pub fn handle(&mut self, line: &str) -> Result<Option<(FinderAction, usize)>, String> {
+        let shown = self.matches.len().min(MAX_SHOWN);
+
+        match parse_input(line, shown) {
+            Input::Select(index, action) => Ok(Some((action, self.matches[index]))),
+            Input::Refine(query) => {
+                self.refine(&query);
+                Ok(None)
+            }
+            Input::Reset => {
+                self.reset();
+                Ok(None)
+            }
+            Input::Quit => Err("No selection made".to_string()),
+            Input::Invalid(message) => {
+                println!("{}", message);
+                Ok(None)
+            }
+        }
+    }
+
+    fn print_matches(&self) {
+        println!();
+        for (i, item) in self.shown().enumerate() {
+            println!("{:>3}) {}", i + 1, item);
+        }
+
+        let hidden = self.matches.len().saturating_sub(MAX_SHOWN);
+        if hidden > 0 {
+            println!("     ... and {} more, type a query to narrow them down", hidden);
+        }
+        println!("{}/{} repositories", self.matches.len(), self.items.len());
+    }
+
+    /// Prompts until a repository is selected, returning None on quit or end of input
+    pub fn run(&mut self) -> Option<(FinderAction, usize)> {
+        let stdin = io::stdin();
+
+        loop {
+            self.print_matches();
+            print!("Number to open (g<number> for the git TUI), query to narrow down, empty to reset, q to quit: ");
+            io::stdout().flush().ok()?;
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+
+            match self.handle(&line) {
+                Ok(Some(selection)) => return Some(selection),
+                Ok(None) => {}
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(parse_input(" 2\n", 3), Input::Select(1, FinderAction::Open));
+        assert_eq!(parse_input("g1", 3), Input::Select(0, FinderAction::GitTui));
+        assert_eq!(parse_input("rust web", 3), Input::Refine("rust web".to_string()));
+        assert_eq!(parse_input("go", 3), Input::Refine("go".to_string()));
+        assert_eq!(parse_input("  ", 3), Input::Reset);
+        assert_eq!(parse_input("q", 3), Input::Quit);
+
+        // Numbers outside of the shown matches select nothing
+        assert!(matches!(parse_input("0", 3), Input::Invalid(_)));
+        assert!(matches!(parse_input("4", 3), Input::Invalid(_)));
+        assert!(matches!(parse_input("g9", 0), Input::Invalid(_)));
+    }
+
+    #[test]
+    fn test_refine_loop() {
+        let items = vec![
+            "rust-web-server [GH]".to_string(),
+            "rust-cli [GH]".to_string(),
+            "go-web [GL]".to_string(),
+        ];
+        let mut prompt = SimplePrompt::new(items);
+
+        // Each query narrows down the previous matches
+        assert_eq!(prompt.handle("web").unwrap(), None);
+        assert_eq!(prompt.shown().collect::<Vec<_>>(), vec!["rust-web-server [GH]", "go-web [GL]"]);
+        assert_eq!(prompt.handle("rust").unwrap(), None);
+        assert_eq!(prompt.shown().collect::<Vec<_>>(), vec!["rust-web-server [GH]"]);
+
+        // An empty line starts over
+        assert_eq!(prompt.handle("").unwrap(), None);
+        assert_eq!(prompt.shown().count(), 3);
+
+        assert_eq!(
+            prompt.handle("g2").unwrap(),
+            Some((FinderAction::GitTui, 1))
+        );
+        assert!(prompt.handle("q").is_err());
+    }
+}
@@ -0,0 +1,428 @@
+//! Sort orders for the repository list
+//!
+//! Without a sort key, repositories keep the order the APIs returned them in.
+
+use crate::cache::{RepoData, RepoKind};
+use crate::formatter::RepoSource;
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+/// Days after which the recency part of the activity score halves
+const RECENCY_HALF_LIFE_DAYS: f64 = 90.0;
+
+/// Key to sort the repository list by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Newest created repositories first
+    Created,
+    /// Highest activity score first
+    Activity,
+    /// Alphabetically, ignoring case
+    Name,
+    /// GitHub, then GitLab, then gists and snippets
+    Source,
+    /// Most stars first
+    Stars,
+    /// Most recently updated first
+    Updated,
+    /// Most recently pushed first
+    Recent,
+}
+
+impl SortKey {
+    /// Names accepted by `--sort` and `--sort-by`
+    pub const NAMES: &'static [&'static str] = &["created", "activity", "name", "source", "stars", "updated", "recent"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "created" => Some(SortKey::Created),
+            "activity" => Some(SortKey::Activity),
+            "name" => Some(SortKey::Name),
+            "source" => Some(SortKey::Source),
+            "stars" => Some(SortKey::Stars),
+            "updated" => Some(SortKey::Updated),
+            "recent" => Some(SortKey::Recent),
+            _ => None,
+        }
+    }
+
+    /// The direction used when none is given, which puts the most interesting repositories first
+    pub fn default_direction(self) -> Direction {
+        match self {
+            SortKey::Name | SortKey::Source => Direction::Asc,
+            SortKey::Created | SortKey::Activity | SortKey::Stars | SortKey::Updated | SortKey::Recent => Direction::Desc,
+        }
+    }
+}
+
+/// Whether a sort key orders from low to high or the other way around
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            Direction::Asc => ordering,
+            Direction::Desc => ordering.reverse(),
+        }
+    }
+}
+
+/// Sort keys with their directions, where later keys break ties of earlier ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec {
+    pub keys: Vec<(SortKey, Direction)>,
+}
+
+impl From<SortKey> for SortSpec {
+    fn from(key: SortKey) -> Self {
+        Self { keys: vec![(key, key.default_direction())] }
+    }
+}
+
+impl SortSpec {
+    /// Parses a `--sort-by` value like `source,stars:desc,name:asc`.
+    ///
+    /// Keys without a direction use their default one.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (name, direction) = match part.split_once(':') {
+                Some((name, direction)) => (name.trim(), Some(direction.trim())),
+                None => (part, None),
+            };
+
+            let key = SortKey::parse(name)
+                .ok_or_else(|| format!("unknown sort key '{}', expected one of {}", name, SortKey::NAMES.join(", ")))?;
+            let direction = match direction {
+                None => key.default_direction(),
+                Some("asc") => Direction::Asc,
+                Some("desc") => Direction::Desc,
+                Some(other) => return Err(format!("unknown direction '{}' for '{}', expected asc or desc", other, name)),
+            };
+
+            keys.push((key, direction));
+        }
+
+        if keys.is_empty() {
+            return Err("expected at least one sort key".to_string());
+        }
+        Ok(Self { keys })
+    }
+}
+
+/// Weights of the activity score parts, set with `--activity-weights`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActivityWeights {
+    pub stars: f64,
+    pub issues: f64,
+    pub recency: f64,
+}
+
+impl Default for ActivityWeights {
+    fn default() -> Self {
+        Self {
+            stars: 1.0,
+            issues: 0.5,
+            recency: 2.0,
+        }
+    }
+}
+
+impl ActivityWeights {
+    /// Parses comma-separated `stars,issues,recency` weights like `1,0.5,2`
+    pub fn parse(value: &str) -> Option<Self> {
+        let weights: Vec<f64> = value
+            .split(',')
+            .map(|part| part.trim().parse::<f64>().ok().filter(|w| w.is_finite() && *w >= 0.0))
+            .collect::<Option<_>>()?;
+
+        match weights[..] {
+            [stars, issues, recency] => Some(Self { stars, issues, recency }),
+            _ => None,
+        }
+    }
+}
+
+/// Computes how much attention a repository gets.
+///
+/// The score is `w_stars * ln(1 + stars) + w_issues * ln(1 + open_issues) + w_recency * 0.5^(days_since_update / 90)`,
+/// so the recency part halves every 90 days without an update. The logarithms keep a few
+/// very popular repositories from drowning out everything else. Missing inputs count as
+/// zero, so a repository without any data gets a neutral score of 0.
+pub fn activity_score(repo: &RepoData, weights: &ActivityWeights, now: u64) -> f64 {
+    let stars = repo.stars.map_or(0.0, |stars| (stars as f64).ln_1p());
+    let issues = repo.open_issues.map_or(0.0, |issues| (issues as f64).ln_1p());
+    let recency = repo.updated_at.map_or(0.0, |updated_at| {
+        let days = now.saturating_sub(updated_at) as f64 / (24.0 * 60.0 * 60.0);
+        0.5f64.powf(days / RECENCY_HALF_LIFE_DAYS)
+    });
+
+    weights.stars * stars + weights.issues * issues + weights.recency * recency
+}
+
+/// Compares optional values in the direction, with missing values last either way
+fn compare_missing_last(a: Option<u64>, b: Option<u64>, direction: Direction) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => direction.apply(a.cmp(&b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn source_rank(repo: &RepoData) -> u8 {
+    match (repo.source, repo.kind) {
+        (RepoSource::GitHub, RepoKind::Repository) => 0,
+        (RepoSource::GitLab, RepoKind::Repository) => 1,
+        (RepoSource::GitHub, RepoKind::Gist) => 2,
+        (RepoSource::GitLab, RepoKind::Gist) => 3,
+    }
+}
+
+/// Compares two repositories by one sort key in a direction
+fn compare_by(a: &RepoData, b: &RepoData, key: SortKey, direction: Direction, weights: &ActivityWeights, now: u64) -> Ordering {
+    match key {
+        SortKey::Created => compare_missing_last(a.created_at, b.created_at, direction),
+        SortKey::Updated => compare_missing_last(a.updated_at, b.updated_at, direction),
+        SortKey::Recent => compare_missing_last(a.pushed_at, b.pushed_at, direction),
+        SortKey::Stars => compare_missing_last(a.stars, b.stars, direction),
+        SortKey::Activity => direction.apply(activity_score(a, weights, now).total_cmp(&activity_score(b, weights, now))),
+        SortKey::Name => direction.apply(a.name.to_lowercase().cmp(&b.name.to_lowercase())),
+        SortKey::Source => direction.apply(source_rank(a).cmp(&source_rank(b))),
+    }
+}
+
+/// Compares two repositories key by key until one of the keys tells them apart
+pub fn compare_spec(a: &RepoData, b: &RepoData, spec: &SortSpec, weights: &ActivityWeights, now: u64) -> Ordering {
+    spec.keys
+        .iter()
+        .map(|&(key, direction)| compare_by(a, b, key, direction, weights, now))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Returns the current time in seconds, which the activity score is computed at
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Sorts repositories by the sort keys, keeping the original order for ties
+pub fn sort_repos(repos: &mut [RepoData], spec: &SortSpec, weights: &ActivityWeights) {
+    let now = now_secs();
+    repos.sort_by(|a, b| compare_spec(a, b, spec, weights, now));
+}
+
+/// Merges a batch into an already sorted list, so only the batch has to be sorted.
+///
+/// The result is the same as sorting everything at once with the sorted items
+/// first: ties keep the sorted items before the batch and both in their order.
+pub fn merge_sorted<T, F>(sorted: Vec<T>, mut batch: Vec<T>, compare: F) -> Vec<T>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    batch.sort_by(&compare);
+
+    let mut merged = Vec::with_capacity(sorted.len() + batch.len());
+    let mut sorted = sorted.into_iter().peekable();
+    let mut batch = batch.into_iter().peekable();
+    while let (Some(a), Some(b)) = (sorted.peek(), batch.peek()) {
+        // Only take from the batch if it sorts strictly before, keeping ties stable
+        let next = if compare(b, a).is_lt() { batch.next() } else { sorted.next() };
+        merged.extend(next);
+    }
+    merged.extend(sorted);
+    merged.extend(batch);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 10_000 * DAY;
+
+    fn repo(name: &str, created_at: Option<u64>) -> RepoData {
+        RepoData {
+            created_at,
+            ..RepoData::named(name)
+        }
+    }
+
+    fn active_repo(name: &str, stars: Option<u64>, open_issues: Option<u64>, days_ago: Option<u64>) -> RepoData {
+        RepoData {
+            stars,
+            open_issues,
+            updated_at: days_ago.map(|days| NOW - days * DAY),
+            ..repo(name, None)
+        }
+    }
+
+    #[test]
+    fn test_sort_by_created() {
+        let mut repos = vec![
+            repo("unknown-1", None),
+            repo("old", Some(100)),
+            repo("new", Some(300)),
+            repo("unknown-2", None),
+            repo("middle", Some(200)),
+        ];
+        sort_repos(&mut repos, &SortSpec::from(SortKey::Created), &ActivityWeights::default());
+
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["new", "middle", "old", "unknown-1", "unknown-2"]);
+    }
+
+    #[test]
+    fn test_sort_by_recent_push() {
+        let pushed = |name: &str, pushed_at: Option<u64>| RepoData { pushed_at, ..repo(name, None) };
+        let mut repos = vec![pushed("never", None), pushed("last-year", Some(NOW - 365 * DAY)), pushed("today", Some(NOW))];
+        sort_repos(&mut repos, &SortSpec::from(SortKey::Recent), &ActivityWeights::default());
+
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["today", "last-year", "never"]);
+    }
+
+    #[test]
+    fn test_parse_sort_key() {
+        assert_eq!(SortKey::parse("created"), Some(SortKey::Created));
+        assert_eq!(SortKey::parse("activity"), Some(SortKey::Activity));
+        assert_eq!(SortKey::parse("size"), None);
+    }
+
+    #[test]
+    fn test_parse_sort_spec() {
+        assert_eq!(
+            SortSpec::parse("source, stars:desc,name:asc").unwrap().keys,
+            vec![
+                (SortKey::Source, Direction::Asc),
+                (SortKey::Stars, Direction::Desc),
+                (SortKey::Name, Direction::Asc),
+            ]
+        );
+
+        // Keys without a direction use their default one
+        assert_eq!(
+            SortSpec::parse("updated,name:desc").unwrap().keys,
+            vec![(SortKey::Updated, Direction::Desc), (SortKey::Name, Direction::Desc)]
+        );
+
+        assert!(SortSpec::parse("size").unwrap_err().contains("unknown sort key 'size'"));
+        assert!(SortSpec::parse("name:up").unwrap_err().contains("unknown direction 'up' for 'name'"));
+        assert!(SortSpec::parse(" , ").is_err());
+    }
+
+    #[test]
+    fn test_sort_by_multiple_keys() {
+        let repo_from = |name: &str, source: RepoSource, stars: Option<u64>| RepoData {
+            source,
+            stars,
+            ..repo(name, None)
+        };
+        let mut repos = vec![
+            repo_from("zeta", RepoSource::GitLab, Some(5)),
+            repo_from("beta", RepoSource::GitHub, Some(1)),
+            repo_from("Alpha", RepoSource::GitHub, Some(1)),
+            repo_from("unstarred", RepoSource::GitHub, None),
+            repo_from("gamma", RepoSource::GitHub, Some(9)),
+            repo_from("eta", RepoSource::GitLab, Some(5)),
+        ];
+        let spec = SortSpec::parse("source,stars:desc,name:asc").unwrap();
+        sort_repos(&mut repos, &spec, &ActivityWeights::default());
+
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["gamma", "Alpha", "beta", "unstarred", "eta", "zeta"]);
+
+        // Missing stars stay last when sorting ascending as well
+        let spec = SortSpec::parse("stars:asc,name").unwrap();
+        sort_repos(&mut repos, &spec, &ActivityWeights::default());
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Alpha", "beta", "eta", "zeta", "gamma", "unstarred"]);
+    }
+
+    #[test]
+    fn test_merge_batch_into_sorted() {
+        let spec = SortSpec::parse("stars,name").unwrap();
+        let compare = |a: &RepoData, b: &RepoData| compare_spec(a, b, &spec, &ActivityWeights::default(), NOW);
+        let starred = |name: &str, stars: Option<u64>| active_repo(name, stars, None, None);
+
+        let mut sorted = vec![starred("b", Some(9)), starred("a", Some(5)), starred("c", Some(1)), starred("d", None)];
+        sorted.sort_by(compare);
+        let batch = vec![starred("e", None), starred("f", Some(5)), starred("g", Some(20)), starred("0", Some(5))];
+
+        let mut expected = sorted.clone();
+        expected.extend(batch.clone());
+        expected.sort_by(compare);
+
+        let merged = merge_sorted(sorted, batch, compare);
+        let names: Vec<&str> = merged.iter().map(|r| r.name.as_str()).collect();
+        let expected_names: Vec<&str> = expected.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["g", "b", "0", "a", "f", "c", "d", "e"]);
+        assert_eq!(names, expected_names);
+
+        // Merging into nothing just sorts the batch
+        assert_eq!(merge_sorted(Vec::new(), vec![3, 1, 2], |a: &i32, b: &i32| a.cmp(b)), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_activity_score() {
+        let weights = ActivityWeights::default();
+
+        // Missing inputs give a neutral score
+        assert_eq!(activity_score(&active_repo("empty", None, None, None), &weights, NOW), 0.0);
+
+        // Updated right now gives the full recency weight
+        let fresh = activity_score(&active_repo("fresh", Some(0), Some(0), Some(0)), &weights, NOW);
+        assert!((fresh - weights.recency).abs() < 1e-9);
+
+        // The recency part halves every 90 days
+        let stale = activity_score(&active_repo("stale", None, None, Some(90)), &weights, NOW);
+        assert!((stale - weights.recency / 2.0).abs() < 1e-9);
+
+        // More stars and issues score higher, but only logarithmically
+        let popular = activity_score(&active_repo("popular", Some(1000), Some(10), None), &weights, NOW);
+        let known = activity_score(&active_repo("known", Some(100), Some(10), None), &weights, NOW);
+        assert!(popular > known);
+        assert!(popular < known * 2.0);
+    }
+
+    #[test]
+    fn test_sort_by_activity() {
+        let weights = ActivityWeights::default();
+        let mut repos = [
+            active_repo("no-data", None, None, None),
+            active_repo("abandoned-star", Some(500), Some(0), Some(2000)),
+            active_repo("fresh-small", Some(2), Some(1), Some(1)),
+            active_repo("fresh-popular", Some(500), Some(20), Some(1)),
+        ];
+        let spec = SortSpec::from(SortKey::Activity);
+        repos.sort_by(|a, b| compare_spec(a, b, &spec, &weights, NOW));
+
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["fresh-popular", "abandoned-star", "fresh-small", "no-data"]);
+
+        // Only weighting recency puts the fresh repositories first
+        let recency_only = ActivityWeights { stars: 0.0, issues: 0.0, recency: 1.0 };
+        repos.sort_by(|a, b| compare_spec(a, b, &spec, &recency_only, NOW));
+        let names: Vec<&str> = repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["fresh-popular", "fresh-small", "abandoned-star", "no-data"]);
+    }
+
+    #[test]
+    fn test_parse_activity_weights() {
+        assert_eq!(
+            ActivityWeights::parse("1, 0.5,3"),
+            Some(ActivityWeights { stars: 1.0, issues: 0.5, recency: 3.0 })
+        );
+        assert_eq!(ActivityWeights::parse("1,2"), None);
+        assert_eq!(ActivityWeights::parse("1,-1,2"), None);
+        assert_eq!(ActivityWeights::parse("a,b,c"), None);
+    }
+}
@@ -0,0 +1,58 @@
+//! Small pieces of state kept between runs, next to the cache
+//!
+//! The last query typed into the fuzzy finder is restored on the next launch unless
+//! `--no-restore-query` is passed or a query is given on the command line.
+
+use crate::cache;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const QUERY_FILE: &str = "last-query";
+
+/// Returns where the last query is kept, in the directory of the cache
+pub fn query_path() -> PathBuf {
+    cache::cache_path().with_file_name(QUERY_FILE)
+}
+
+/// Reads a saved query, None if there is none or it is empty
+pub fn load_query_from(path: &Path) -> Option<String> {
+    let query = fs::read_to_string(path).ok()?;
+    let query = query.trim();
+    (!query.is_empty()).then(|| query.to_string())
+}
+
+pub fn save_query_to(path: &Path, query: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, query.trim())
+}
+
+/// Loads the query of the previous run, if it left one
+pub fn load_last_query() -> Option<String> {
+    load_query_from(&query_path())
+}
+
+pub fn save_last_query(query: &str) -> io::Result<()> {
+    save_query_to(&query_path(), query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_round_trip() {
+        let path = std::env::temp_dir().join("repo-searcher-test-state").join(QUERY_FILE);
+        let _ = fs::remove_file(&path);
+        assert_eq!(load_query_from(&path), None);
+
+        save_query_to(&path, "rust web ").unwrap();
+        assert_eq!(load_query_from(&path).as_deref(), Some("rust web"));
+
+        // Clearing the query leaves nothing to restore
+        save_query_to(&path, "").unwrap();
+        assert_eq!(load_query_from(&path), None);
+    }
+}
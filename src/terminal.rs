@@ -1,8 +1,70 @@
 use std::io::Write;
 use std::process;
-use termion;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::SystemTime;
 use termion::input::TermRead;
 
+/// A second press within this window force-quits on Ctrl+C, or quits the busy finder
+const SECOND_PRESS_WINDOW_MS: u64 = 2000;
+
+/// Tracks presses of a quit key, for the ones which need a second press shortly after
+pub struct PressTracker {
+    // Milliseconds since the epoch of the last press, 0 if there was none
+    last_press_ms: AtomicU64,
+}
+
+impl PressTracker {
+    pub const fn new() -> Self {
+        Self {
+            last_press_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a press at the given time, returning whether it is the second one within the window
+    pub fn press(&self, now_ms: u64) -> bool {
+        let previous = self.last_press_ms.swap(now_ms, Ordering::SeqCst);
+        previous != 0 && now_ms.saturating_sub(previous) <= SECOND_PRESS_WINDOW_MS
+    }
+}
+
+impl Default for PressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static CTRL_C_PRESSES: PressTracker = PressTracker::new();
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static BUSY: AtomicBool = AtomicBool::new(false);
+
+/// Returns the milliseconds since the epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Marks whether a background operation like fetching repositories is running
+pub fn set_busy(busy: bool) {
+    BUSY.store(busy, Ordering::SeqCst);
+}
+
+/// Check if a background operation is running
+pub fn is_busy() -> bool {
+    BUSY.load(Ordering::SeqCst)
+}
+
+/// Check if Ctrl+C asked to exit once the current operation is done
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Check if a background operation should stop, because it was cancelled or Ctrl+C asked to exit
+pub fn should_stop(cancel: &AtomicBool) -> bool {
+    cancel.load(Ordering::SeqCst) || shutdown_requested()
+}
+
 /// Cleans up the terminal state before exiting
 pub fn cleanup_terminal() {
     // Ensure terminal is in a clean state
@@ -10,18 +72,50 @@ pub fn cleanup_terminal() {
     std::io::stdout().flush().unwrap();
     
     // Reset terminal attributes to ensure proper cleanup
-    if let Ok(_) = termion::get_tty() {
+    if termion::get_tty().is_ok() {
         let _ = termion::async_stdin().keys().next(); // Consume any pending input
         let _ = termion::terminal_size(); // Force terminal refresh
     }
 }
 
 /// Sets up a Ctrl+C handler that works globally
+///
+/// The first press requests a graceful shutdown, a second press shortly after
+/// force-quits even if an operation like a clone is still running.
 pub fn setup_ctrl_c_handler() {
     // Use the ctrlc crate which works reliably across platforms
     ctrlc::set_handler(move || {
-        cleanup_terminal();
-        println!("\nReceived Ctrl+C, exiting...");
-        process::exit(0);
+        if CTRL_C_PRESSES.press(now_ms()) {
+            cleanup_terminal();
+            println!("\nReceived Ctrl+C again, exiting...");
+            process::exit(0);
+        }
+
+        SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+        println!("\nReceived Ctrl+C, exiting once the current operation finishes. Press Ctrl+C again to force quit");
     }).expect("Error setting Ctrl+C handler");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_press_within_window() {
+        let presses = PressTracker::new();
+
+        assert!(!presses.press(10_000));
+        assert!(presses.press(10_000 + SECOND_PRESS_WINDOW_MS));
+    }
+
+    #[test]
+    fn test_second_press_after_window_is_a_first_one() {
+        let presses = PressTracker::new();
+
+        assert!(!presses.press(10_000));
+        assert!(!presses.press(10_001 + SECOND_PRESS_WINDOW_MS));
+
+        // The window restarts from the latest press
+        assert!(presses.press(10_500 + SECOND_PRESS_WINDOW_MS));
+    }
+}
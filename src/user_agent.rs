@@ -0,0 +1,166 @@
+//! The User-Agent sent to the GitHub and GitLab APIs, set with `--user-agent`
+//!
+//! Some API gateways and self-hosted instances only allow identifiable clients, so both
+//! API clients are built here with the same User-Agent.
+
+use crate::github;
+use octocrab::Octocrab;
+use reqwest::header::{HeaderValue, USER_AGENT};
+use std::sync::OnceLock;
+
+static CONFIGURED: OnceLock<String> = OnceLock::new();
+
+/// Returns the User-Agent used without `--user-agent`, like `github-repo-searcher/0.1.0`
+pub fn default_user_agent() -> String {
+    format!("github-repo-searcher/{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Checks that a User-Agent can be sent as a header value
+pub fn parse_user_agent(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("the user agent must not be empty".to_string());
+    }
+
+    HeaderValue::from_str(value)
+        .map(|_| value.to_string())
+        .map_err(|_| "the user agent must not contain control characters".to_string())
+}
+
+/// Sets the User-Agent of all clients built afterwards, only the first call has an effect
+pub fn set_user_agent(user_agent: String) {
+    let _ = CONFIGURED.set(user_agent);
+}
+
+/// Returns the configured User-Agent, or the default one if none was set
+pub fn user_agent() -> String {
+    CONFIGURED.get().cloned().unwrap_or_else(default_user_agent)
+}
+
+/// Builds a GitHub client authenticated with a token, for the host set with `--github-host`.
+///
+/// octocrab keeps sending its own `octocrab` User-Agent, ours is added after it. Its default
+/// client is kept for the redirects and retries it handles.
+// The error is passed on as is, like the fetch functions do with octocrab's errors
+#[allow(clippy::result_large_err)]
+pub fn github_client(token: &str) -> octocrab::Result<Octocrab> {
+    build_github_client(token, &user_agent(), github::api_base_uri(github::host()).as_deref())
+}
+
+#[allow(clippy::result_large_err)]
+fn build_github_client(token: &str, user_agent: &str, base_uri: Option<&str>) -> octocrab::Result<Octocrab> {
+    let mut builder = Octocrab::builder()
+        .personal_token(token.to_string())
+        .add_header(USER_AGENT, user_agent.to_string());
+
+    if let Some(base_uri) = base_uri {
+        builder = builder.base_uri(base_uri)?;
+    }
+    builder.build()
+}
+
+/// Builds a client for the GitLab API
+pub fn gitlab_client() -> reqwest::Result<reqwest::Client> {
+    build_gitlab_client(&user_agent())
+}
+
+fn build_gitlab_client(user_agent: &str) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().user_agent(user_agent).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+
+    // Answers a single request with an empty JSON object, returning the request headers
+    fn mock_server() -> (String, thread::JoinHandle<String>) {
+        mock_server_answering(vec![OK.to_string()])
+    }
+
+    // Answers one request per response, returning the request headers one after another
+    fn mock_server_answering(responses: Vec<String>) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut requests = String::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push_str(&String::from_utf8_lossy(&request).to_lowercase());
+            }
+            requests
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn test_parse_user_agent() {
+        assert_eq!(parse_user_agent(" my-gateway/1.0 "), Ok("my-gateway/1.0".to_string()));
+        assert!(parse_user_agent("").is_err());
+        assert!(parse_user_agent("bad\nagent").is_err());
+        assert!(default_user_agent().starts_with("github-repo-searcher/"));
+    }
+
+    #[tokio::test]
+    async fn test_gitlab_client_sends_user_agent() {
+        let (url, server) = mock_server();
+
+        let client = build_gitlab_client("searcher-test/1.2").unwrap();
+        client.get(&url).send().await.unwrap();
+
+        assert!(server.join().unwrap().contains("user-agent: searcher-test/1.2\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_github_client_keeps_enterprise_api_path() {
+        let (url, server) = mock_server();
+
+        let client = build_github_client("token", "searcher-test/1.2", Some(&format!("{}/api/v3", url))).unwrap();
+        let _: serde_json::Value = client.get("/user", None::<&()>).await.unwrap();
+
+        assert!(server.join().unwrap().starts_with("get /api/v3/user "));
+    }
+
+    #[tokio::test]
+    async fn test_github_client_sends_user_agent() {
+        let (url, server) = mock_server();
+
+        let client = build_github_client("token", "searcher-test/1.2", Some(&url)).unwrap();
+        let _: serde_json::Value = client.get("/user", None::<&()>).await.unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.contains("user-agent: searcher-test/1.2\r\n"));
+        assert!(request.contains("authorization: bearer token\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_github_client_follows_redirects() {
+        // Like GitHub answers for a renamed repository
+        let moved = "HTTP/1.1 301 Moved Permanently\r\nLocation: /repos/dima-369/renamed\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let (url, server) = mock_server_answering(vec![moved.to_string(), OK.to_string()]);
+
+        let client = build_github_client("token", "searcher-test/1.2", Some(&url)).unwrap();
+        let _: serde_json::Value = client.get("/repos/dima-369/old-name", None::<&()>).await.unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests.starts_with("get /repos/dima-369/old-name "));
+        assert!(requests.contains("get /repos/dima-369/renamed "));
+    }
+}
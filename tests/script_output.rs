@@ -0,0 +1,16 @@
+//! Runs the binary in the modes meant for scripts, whose standard output is piped elsewhere
+
+use std::process::Command;
+
+#[test]
+fn test_dummy_json_output_is_only_json() {
+    let output = Command::new(env!("CARGO_BIN_EXE_repo-searcher-github-and-gitlab"))
+        .args(["--dummy", "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // Notices and progress go to stderr, so stdout parses as a whole
+    let repos: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(repos.as_array().is_some_and(|repos| !repos.is_empty()));
+}